@@ -1,24 +1,121 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Program {
     pub name: String,
+    pub imports: Vec<ProgramImport>,
     pub accounts: Vec<AccountDef>,
+    pub structs: Vec<StructDef>,
+    pub events: Vec<EventDef>,
+    pub externs: Vec<ExternFn>,
     pub instructions: Vec<Instruction>,
+    pub tests: Vec<TestDef>,
+    pub invariants: Vec<InvariantDef>,
+    /// Source files the program was loaded from, root first. `Span::file`
+    /// indexes into this list; it is empty for programs parsed from a string.
+    pub files: Vec<String>,
+}
+
+impl Program {
+    /// `file:line:column` of a declaration, or `None` for declarations that
+    /// were not parsed from source.
+    pub fn location(&self, span: Span) -> Option<String> {
+        if span.line == 0 {
+            return None;
+        }
+        Some(span.display_in(self.files.get(span.file).map(String::as_str)))
+    }
+}
+
+/// Where a declaration's name appears. `line` and `column` are 1-based; the
+/// default span (line 0) marks declarations built in code.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub file: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// `file:line:column`, or `line:column` when the file has no name.
+    pub fn display_in(&self, file: Option<&str>) -> String {
+        match file {
+            Some(file) => format!("{}:{}:{}", file, self.line, self.column),
+            None => format!("{}:{}", self.line, self.column),
+        }
+    }
+}
+
+/// `import program Foo from "idl/foo.json"`: another Anchor program whose
+/// instructions can be invoked through CPI as `foo.instruction(...)`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgramImport {
+    pub name: String,
+    pub idl_path: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AccountDef {
+    pub docs: Vec<String>,
     pub name: String,
+    pub span: Span,
     pub fields: Vec<Field>,
+    pub invariants: Vec<AccountInvariant>,
+}
+
+/// `invariant total_shares <= total_deposits, "Shares exceed deposits"`
+/// inside an account: a property every account of the type keeps. Bare
+/// names are the account's fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountInvariant {
+    pub span: Span,
+    pub condition: Expr,
+    pub message: Option<String>,
+}
+
+/// `struct Fees { rate: u16, recipient: Pubkey }`: a data type for account
+/// and event fields and instruction arguments.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StructDef {
+    pub docs: Vec<String>,
+    pub name: String,
+    pub span: Span,
+    pub fields: Vec<Field>,
+}
+
+/// `event Deposited { user: Pubkey, amount: u64 }`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventDef {
+    pub docs: Vec<String>,
+    pub name: String,
+    pub span: Span,
+    pub fields: Vec<Field>,
+}
+
+/// `extern fn fee(amount: u64) -> u64`: a hand-written Rust function in the
+/// crate's `helpers` module, called from instructions as `helpers.fee(amount)`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExternFn {
+    pub docs: Vec<String>,
+    pub name: String,
+    pub span: Span,
+    pub params: Vec<(String, Type)>,
+    /// `None` for functions only called as statements
+    pub ret: Option<Type>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Field {
+    pub docs: Vec<String>,
     pub name: String,
     pub ty: Type,
 }
 
+/// The type of a field, argument or parameter. Data types (integers,
+/// `Pubkey`, `Vec<u8>`, structs, ...) are values; account types (`Signer`,
+/// program accounts, token accounts and mints) are only valid as instruction
+/// parameters, which the HIR checks.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Type {
     Pubkey,
@@ -34,9 +131,57 @@ pub enum Type {
     String,
     Vec(Box<Type>),
     Option(Box<Type>),
+    /// A `struct` declared in the program
+    Struct(String),
+    Signer,
+    /// An `account` declared in the program
+    Account(String),
+    /// SPL token account
+    TokenAccount,
+    /// SPL mint
+    Mint,
+    /// A declared type by name, as parsed; the HIR resolves it to `Struct`
+    /// or `Account`
+    Named(String),
 }
 
 impl Type {
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::U8
+                | Type::U16
+                | Type::U32
+                | Type::U64
+                | Type::I8
+                | Type::I16
+                | Type::I32
+                | Type::I64
+        )
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Type::I8 | Type::I16 | Type::I32 | Type::I64)
+    }
+
+    /// Whether Rust copies values of this type rather than moving them.
+    pub fn is_copy(&self) -> bool {
+        match self {
+            Type::Option(inner) => inner.is_copy(),
+            Type::Pubkey | Type::Bool => true,
+            ty => ty.is_integer(),
+        }
+    }
+
+    /// Whether a parameter of this type is passed as an account in the
+    /// context struct rather than as an instruction argument.
+    pub fn is_account(&self) -> bool {
+        matches!(
+            self,
+            Type::Signer | Type::Account(_) | Type::TokenAccount | Type::Mint
+        )
+    }
+
     pub fn to_rust_type(&self) -> String {
         match self {
             Type::Pubkey => "Pubkey".to_string(),
@@ -52,31 +197,35 @@ impl Type {
             Type::String => "String".to_string(),
             Type::Vec(inner) => format!("Vec<{}>", inner.to_rust_type()),
             Type::Option(inner) => format!("Option<{}>", inner.to_rust_type()),
+            Type::Struct(name) | Type::Named(name) => name.clone(),
+            Type::Signer => "Signer<'_>".to_string(),
+            Type::Account(name) => format!("Account<'_, {}>", name),
+            Type::TokenAccount => "Account<'_, TokenAccount>".to_string(),
+            Type::Mint => "Account<'_, Mint>".to_string(),
         }
     }
+}
 
-    pub fn to_anchor_type(&self) -> String {
+/// The type as SOL-X source, for messages.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Type::Pubkey => "pubkey".to_string(),
-            Type::U8 => "u8".to_string(),
-            Type::U16 => "u16".to_string(),
-            Type::U32 => "u32".to_string(),
-            Type::U64 => "u64".to_string(),
-            Type::I8 => "i8".to_string(),
-            Type::I16 => "i16".to_string(),
-            Type::I32 => "i32".to_string(),
-            Type::I64 => "i64".to_string(),
-            Type::Bool => "bool".to_string(),
-            Type::String => "string".to_string(),
-            Type::Vec(inner) => format!("vec<{}>", inner.to_anchor_type()),
-            Type::Option(inner) => format!("option<{}>", inner.to_anchor_type()),
+            Type::Vec(inner) => write!(f, "Vec<{}>", inner),
+            Type::Option(inner) => write!(f, "Option<{}>", inner),
+            Type::Struct(name) | Type::Account(name) | Type::Named(name) => f.write_str(name),
+            Type::Signer => f.write_str("Signer"),
+            Type::TokenAccount => f.write_str("TokenAccount"),
+            Type::Mint => f.write_str("Mint"),
+            other => f.write_str(&other.to_rust_type()),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Instruction {
+    pub docs: Vec<String>,
     pub name: String,
+    pub span: Span,
     pub params: Vec<Param>,
     pub body: Vec<Statement>,
 }
@@ -84,51 +233,86 @@ pub struct Instruction {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Param {
     pub name: String,
-    pub ty: ParamType,
+    pub span: Span,
+    pub ty: Type,
+    pub annotations: Vec<ParamAnnotation>,
 }
 
+/// Constraint annotations written after a parameter type, e.g.
+/// `vault: TokenAccount @associated_token(mint = mint, authority = user)`.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub enum ParamType {
-    Signer,
-    Account(String), // Account type name
-    Pubkey,
-    U8,
-    U16,
-    U32,
-    U64,
-    I8,
-    I16,
-    I32,
-    I64,
-    Bool,
-    String,
+pub enum ParamAnnotation {
+    /// `@associated_token(mint = m, authority = a)`
+    AssociatedToken { mint: String, authority: String },
+    /// `@init_if_needed(payer = p)`
+    InitIfNeeded { payer: String },
+    /// `@seeds("vault", user)`: the account is a PDA of the program
+    Seeds(Vec<Seed>),
+    /// `@mut`: the account is writable even where the instruction does not
+    /// write it
+    Mut,
 }
 
-impl ParamType {
-    pub fn to_rust_type(&self) -> String {
-        match self {
-            ParamType::Signer => "Signer<'_>".to_string(),
-            ParamType::Account(name) => format!("Account<'_, {}>", name),
-            ParamType::Pubkey => "Pubkey".to_string(),
-            ParamType::U8 => "u8".to_string(),
-            ParamType::U16 => "u16".to_string(),
-            ParamType::U32 => "u32".to_string(),
-            ParamType::U64 => "u64".to_string(),
-            ParamType::I8 => "i8".to_string(),
-            ParamType::I16 => "i16".to_string(),
-            ParamType::I32 => "i32".to_string(),
-            ParamType::I64 => "i64".to_string(),
-            ParamType::Bool => "bool".to_string(),
-            ParamType::String => "String".to_string(),
-        }
-    }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Seed {
+    /// A string literal, used as its UTF-8 bytes
+    Const(String),
+    /// An account parameter, used as its address
+    Account(String),
+}
+
+/// `test "increment requires authority" { ... }`: a test run in-process by
+/// `solx test`. Tests are not part of the generated program.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestDef {
+    pub name: String,
+    pub span: Span,
+    pub body: Vec<TestStatement>,
+}
+
+/// `invariant counter.count <= 100`: a property `solx fuzz` checks after
+/// every call. Names are test addresses, as in `test` blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvariantDef {
+    pub span: Span,
+    pub condition: Expr,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TestStatement {
+    /// `let state = pda("state", alice)` or `let amount = 100`
+    Let { name: String, value: TestValue },
+    /// `call increment(authority: bob, state: state) expect_err NotTheAuthority`
+    Call(TestCall),
+    /// `expect state.count == 1`
+    Expect { condition: Expr, span: Span },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TestValue {
+    Expr(Expr),
+    /// `pda("state", alice)`: an address derived from the program ID
+    Pda(Vec<Seed>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestCall {
+    pub instruction: String,
+    pub span: Span,
+    /// Accounts and arguments by parameter name
+    pub args: Vec<(String, Expr)>,
+    /// `signed_by(alice, state)`; by default `Signer` accounts and accounts
+    /// created with `init` sign
+    pub signers: Option<Vec<String>>,
+    /// `expect_err Name`: the call must fail with this error
+    pub expect_err: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Statement {
     InitAccount {
-        var_name: String,      // Variable name (e.g., "state")
-        account_name: String,   // Account type name (e.g., "CounterState")
+        var_name: String,     // Variable name (e.g., "state")
+        account_name: String, // Account type name (e.g., "CounterState")
         payer: String,
         signer: Option<String>,
     },
@@ -136,13 +320,36 @@ pub enum Statement {
         condition: Expr,
         message: Option<String>,
     },
+    Emit {
+        event: String,
+        fields: Vec<(String, Expr)>, // `amount` shorthand is stored as ("amount", Ident("amount"))
+    },
     Assign {
         target: Expr,
         value: Expr,
     },
+    /// `close escrow to maker`: the account's lamports go to `recipient`
+    /// and the account is deleted
+    Close {
+        account: String,
+        recipient: String,
+    },
     Expr(Expr),
 }
 
+impl Statement {
+    /// The expressions appearing directly in this statement.
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Statement::Require { condition, .. } => vec![condition],
+            Statement::Assign { target, value } => vec![target, value],
+            Statement::Emit { fields, .. } => fields.iter().map(|(_, v)| v).collect(),
+            Statement::Expr(expr) => vec![expr],
+            Statement::InitAccount { .. } | Statement::Close { .. } => Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Expr {
     Ident(String),
@@ -160,6 +367,62 @@ pub enum Expr {
         op: UnOp,
         operand: Box<Expr>,
     },
+    MethodCall {
+        object: Box<Expr>,
+        method: String,
+        args: Vec<Expr>,
+    },
+}
+
+impl Expr {
+    /// Call `f` on this expression and every subexpression, outermost first.
+    pub fn visit(&self, f: &mut impl FnMut(&Expr)) {
+        f(self);
+        match self {
+            Expr::FieldAccess { object, .. } => object.visit(f),
+            Expr::BinaryOp { left, right, .. } => {
+                left.visit(f);
+                right.visit(f);
+            }
+            Expr::UnaryOp { operand, .. } => operand.visit(f),
+            Expr::MethodCall { object, args, .. } => {
+                object.visit(f);
+                for arg in args {
+                    arg.visit(f);
+                }
+            }
+            Expr::Ident(_) | Expr::Literal(_) => {}
+        }
+    }
+}
+
+/// The expression as SOL-X source, for messages.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Ident(name) => f.write_str(name),
+            Expr::FieldAccess { object, field } => write!(f, "{}.{}", object, field),
+            Expr::Literal(Literal::Int(value)) => write!(f, "{}", value),
+            Expr::Literal(Literal::UInt(value)) => write!(f, "{}", value),
+            Expr::Literal(Literal::Bool(value)) => write!(f, "{}", value),
+            Expr::Literal(Literal::String(value)) => write!(f, "{:?}", value),
+            Expr::BinaryOp { op, left, right } => {
+                write!(f, "{} {} {}", left, op.symbol(), right)
+            }
+            Expr::UnaryOp { op, operand } => match op {
+                UnOp::Not => write!(f, "!{}", operand),
+                UnOp::Neg => write!(f, "-{}", operand),
+            },
+            Expr::MethodCall {
+                object,
+                method,
+                args,
+            } => {
+                let args: Vec<String> = args.iter().map(Expr::to_string).collect();
+                write!(f, "{}.{}({})", object, method, args.join(", "))
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -187,6 +450,34 @@ pub enum BinOp {
     Or,
 }
 
+impl BinOp {
+    /// `+ - * / %`, the operators that can overflow.
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(
+            self,
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod
+        )
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum UnOp {
     Not,
@@ -0,0 +1,231 @@
+//! Keeping the program crate's `Cargo.toml` in line with the backend.
+//!
+//! The project templates depend on Anchor. A backend that generates other
+//! code (e.g. `native`) lists the crates it needs and the ones it replaces,
+//! and `solx build` edits `[dependencies]` to match before cargo sees it.
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+use toml_edit::{value, Array, DocumentMut, InlineTable, Item, TableLike};
+
+use solx_codegen::Dependency;
+
+/// What `sync_dependencies` changed.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Add the missing `dependencies` to `crate_dir/Cargo.toml`, with their
+/// features and forwarded features, and remove the `replaced` crates, along
+/// with feature entries that refer to them like `"anchor-lang/idl-build"`.
+/// Crates already listed keep their version. Added features are reported
+/// as `crate/feature`. Does nothing without a `Cargo.toml`, e.g. for
+/// examples.
+pub fn sync_dependencies(
+    crate_dir: &Path,
+    dependencies: &[Dependency],
+    replaced: &[&str],
+) -> Result<Changes> {
+    let path = crate_dir.join("Cargo.toml");
+    let Ok(source) = fs::read_to_string(&path) else {
+        return Ok(Changes::default());
+    };
+    let mut doc = source
+        .parse::<DocumentMut>()
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let mut changes = Changes::default();
+    let table = doc["dependencies"].or_insert(toml_edit::table());
+    let Some(table) = table.as_table_like_mut() else {
+        anyhow::bail!("[dependencies] in {} is not a table", path.display());
+    };
+    for name in replaced {
+        if table.remove(name).is_some() {
+            changes.removed.push(name.to_string());
+        }
+    }
+    for dependency in dependencies {
+        let entry = match table.get_mut(&dependency.name) {
+            Some(entry) => entry,
+            None => {
+                table.insert(&dependency.name, value(&dependency.version));
+                changes.added.push(dependency.name.clone());
+                table.get_mut(&dependency.name).expect("just inserted")
+            }
+        };
+        for feature in &dependency.features {
+            if enable_feature(entry, feature) {
+                changes
+                    .added
+                    .push(format!("{}/{}", dependency.name, feature));
+            }
+        }
+    }
+
+    if dependencies
+        .iter()
+        .any(|d| !d.forwarded_features.is_empty())
+    {
+        let features = doc["features"].or_insert(toml_edit::table());
+        let Some(features) = features.as_table_like_mut() else {
+            anyhow::bail!("[features] in {} is not a table", path.display());
+        };
+        for dependency in dependencies {
+            for feature in &dependency.forwarded_features {
+                let entry = format!("{}/{}", dependency.name, feature);
+                if add_to_feature(features, feature, &entry) {
+                    changes.added.push(entry);
+                }
+            }
+        }
+    }
+
+    let mut edited = !changes.is_empty();
+    if let Some(features) = doc.get_mut("features").and_then(|f| f.as_table_like_mut()) {
+        for (_, feature) in features.iter_mut() {
+            let Some(entries) = feature.as_array_mut() else {
+                continue;
+            };
+            let mut kept: Array = entries
+                .iter()
+                .filter(|entry| {
+                    let entry = entry.as_str().unwrap_or_default();
+                    !replaced.contains(&entry.split('/').next().unwrap_or_default())
+                })
+                .cloned()
+                .collect();
+            if kept.len() != entries.len() {
+                kept.fmt();
+                *entries = kept;
+                edited = true;
+            }
+        }
+    }
+
+    if edited {
+        fs::write(&path, doc.to_string())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(changes)
+}
+
+/// Enable `feature` on a dependency entry, turning `name = "1.0"` into an
+/// inline table. Returns whether it was missing.
+fn enable_feature(entry: &mut Item, feature: &str) -> bool {
+    if let Some(version) = entry.as_str() {
+        let mut table = InlineTable::new();
+        table.insert("version", version.into());
+        *entry = value(table);
+    }
+    let Some(table) = entry.as_table_like_mut() else {
+        return false;
+    };
+    let features = table
+        .entry("features")
+        .or_insert(value(Array::new()))
+        .as_array_mut();
+    match features {
+        Some(features) if !features.iter().any(|f| f.as_str() == Some(feature)) => {
+            features.push(feature);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Add `entry` to the crate feature `feature`, creating it if needed.
+/// Returns whether it was missing.
+fn add_to_feature(features: &mut dyn TableLike, feature: &str, entry: &str) -> bool {
+    let Some(entries) = features
+        .entry(feature)
+        .or_insert(value(Array::new()))
+        .as_array_mut()
+    else {
+        return false;
+    };
+    if entries.iter().any(|e| e.as_str() == Some(entry)) {
+        return false;
+    }
+    entries.push(entry);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_dependencies_between_backends() {
+        let dir = tempfile::tempdir().unwrap();
+        let cargo_toml = dir.path().join("Cargo.toml");
+        fs::write(
+            &cargo_toml,
+            "[package]\nname = \"vault\"\n\n[features]\ncpi = [\"no-entrypoint\"]\n\
+             idl-build = [\"anchor-lang/idl-build\", \"anchor-spl/idl-build\"]\n\n\
+             [dependencies]\nanchor-lang = \"0.30.1\"\nborsh = \"0.10.3\"\n",
+        )
+        .unwrap();
+        let dependencies = [
+            Dependency::new("solana-program", "1.18"),
+            Dependency::new("borsh", "0.10"),
+        ];
+        let replaced = ["anchor-lang", "anchor-spl"];
+
+        let changes = sync_dependencies(dir.path(), &dependencies, &replaced).unwrap();
+        assert_eq!(changes.added, vec!["solana-program"]);
+        assert_eq!(changes.removed, vec!["anchor-lang"]);
+        assert_eq!(
+            fs::read_to_string(&cargo_toml).unwrap(),
+            "[package]\nname = \"vault\"\n\n[features]\ncpi = [\"no-entrypoint\"]\n\
+             idl-build = []\n\n\
+             [dependencies]\nborsh = \"0.10.3\"\nsolana-program = \"1.18\"\n"
+        );
+
+        let changes = sync_dependencies(dir.path(), &dependencies, &replaced).unwrap();
+        assert!(changes.is_empty());
+        assert!(
+            sync_dependencies(&dir.path().join("missing"), &dependencies, &replaced)
+                .unwrap()
+                .is_empty()
+        );
+
+        // And back to Anchor, for a program with `@init_if_needed`
+        let dependencies = [
+            Dependency::new("anchor-lang", "0.30.1")
+                .feature("init-if-needed")
+                .forward_feature("idl-build"),
+            Dependency::new("anchor-spl", "0.30.1").forward_feature("idl-build"),
+        ];
+        let replaced = ["solana-program", "borsh"];
+        let changes = sync_dependencies(dir.path(), &dependencies, &replaced).unwrap();
+        assert_eq!(
+            changes.added,
+            vec![
+                "anchor-lang",
+                "anchor-lang/init-if-needed",
+                "anchor-spl",
+                "anchor-lang/idl-build",
+                "anchor-spl/idl-build"
+            ]
+        );
+        assert_eq!(changes.removed, vec!["solana-program", "borsh"]);
+        assert_eq!(
+            fs::read_to_string(&cargo_toml).unwrap(),
+            "[package]\nname = \"vault\"\n\n[features]\ncpi = [\"no-entrypoint\"]\n\
+             idl-build = [\"anchor-lang/idl-build\", \"anchor-spl/idl-build\"]\n\n\
+             [dependencies]\nanchor-lang = { version = \"0.30.1\", features = [\"init-if-needed\"] }\n\
+             anchor-spl = \"0.30.1\"\n"
+        );
+        let changes = sync_dependencies(dir.path(), &dependencies, &replaced).unwrap();
+        assert!(changes.is_empty());
+    }
+}
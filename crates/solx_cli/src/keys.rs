@@ -0,0 +1,447 @@
+//! Program ID resolution and `solx keys`.
+//!
+//! Unless `solx.toml` sets `[build] program-id`, the ID embedded in
+//! generated code comes from, in order:
+//! 1. `Anchor.toml` `[programs.<cluster>]` for the provider cluster
+//! 2. the deploy keypair `target/deploy/<program>-keypair.json`
+//! 3. the `declare_id!` already in the program crate's `src/generated.rs`
+//!    (or an older, fully generated `src/lib.rs`)
+//! 4. a placeholder until one of the above is set up
+//!
+//! A source that only holds the placeholder, like the `Anchor.toml` entry
+//! `solx init` writes, counts as not set up.
+
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table};
+
+use solx_hir::naming::to_snake_case;
+
+use crate::manifest::{ProgramIdSource, MANIFEST_FILE};
+
+/// Placeholder program ID (valid Base58) used until a real one is configured
+pub const DEFAULT_PROGRAM_ID: &str = "11111111111111111111111111111111";
+
+/// Files under `src/` that may hold the `declare_id!`.
+const DECLARE_ID_FILES: [&str; 2] = ["generated.rs", "lib.rs"];
+
+/// Cluster used when `Anchor.toml` has no `[provider] cluster`.
+const DEFAULT_CLUSTER: &str = "localnet";
+
+pub struct ProgramId {
+    pub id: String,
+    pub source: IdSource,
+}
+
+pub enum IdSource {
+    AnchorToml { cluster: String },
+    Keypair(PathBuf),
+    Source(PathBuf),
+    Manifest,
+    Default,
+}
+
+impl fmt::Display for IdSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdSource::AnchorToml { cluster } => write!(f, "Anchor.toml [programs.{}]", cluster),
+            IdSource::Keypair(path) => write!(f, "{}", path.display()),
+            IdSource::Source(file) => write!(f, "declare_id! in {}", file.display()),
+            IdSource::Manifest => write!(f, "{} [build] program-id", MANIFEST_FILE),
+            IdSource::Default => {
+                write!(f, "placeholder; run `solx keys sync` after `anchor build`")
+            }
+        }
+    }
+}
+
+/// The program ID to generate code with for `program` in the project at
+/// `path`, whose program crate is `crate_dir`, taken from `source`.
+pub fn resolve(
+    path: &Path,
+    crate_dir: &Path,
+    program: &str,
+    source: &ProgramIdSource,
+) -> Result<ProgramId> {
+    let missing = |setting: &str, what: String| {
+        anyhow::anyhow!(
+            "{} sets program-id = \"{}\" but {}",
+            MANIFEST_FILE,
+            setting,
+            what
+        )
+    };
+    match source {
+        ProgramIdSource::Fixed(id) => Ok(ProgramId {
+            id: id.clone(),
+            source: IdSource::Manifest,
+        }),
+        ProgramIdSource::AnchorToml => from_anchor_toml(path, program)?.ok_or_else(|| {
+            missing(
+                "anchor-toml",
+                format!(
+                    "Anchor.toml lists no {} for the provider cluster",
+                    to_snake_case(program)
+                ),
+            )
+        }),
+        ProgramIdSource::Keypair => from_keypair(path, program)?.ok_or_else(|| {
+            missing(
+                "keypair",
+                format!("{} does not exist", keypair_path(path, program).display()),
+            )
+        }),
+        ProgramIdSource::DeclareId => from_declare_id(crate_dir)
+            .ok_or_else(|| missing("declare-id", "src/ has no declare_id!".to_string())),
+        ProgramIdSource::Auto => {
+            let configured = |id: &ProgramId| id.id != DEFAULT_PROGRAM_ID;
+            if let Some(id) = from_anchor_toml(path, program)?.filter(configured) {
+                return Ok(id);
+            }
+            if let Some(id) = from_keypair(path, program)? {
+                return Ok(id);
+            }
+            Ok(from_declare_id(crate_dir)
+                .filter(configured)
+                .unwrap_or_else(|| ProgramId {
+                    id: DEFAULT_PROGRAM_ID.to_string(),
+                    source: IdSource::Default,
+                }))
+        }
+    }
+}
+
+fn from_anchor_toml(path: &Path, program: &str) -> Result<Option<ProgramId>> {
+    let Some(doc) = read_anchor_toml(path)? else {
+        return Ok(None);
+    };
+    let cluster = provider_cluster(&doc);
+    Ok(anchor_toml_id(&doc, &cluster, program).map(|id| ProgramId {
+        id,
+        source: IdSource::AnchorToml { cluster },
+    }))
+}
+
+fn from_keypair(path: &Path, program: &str) -> Result<Option<ProgramId>> {
+    let keypair = keypair_path(path, program);
+    if !keypair.exists() {
+        return Ok(None);
+    }
+    Ok(Some(ProgramId {
+        id: read_keypair_pubkey(&keypair)?,
+        source: IdSource::Keypair(keypair),
+    }))
+}
+
+fn from_declare_id(crate_dir: &Path) -> Option<ProgramId> {
+    DECLARE_ID_FILES.into_iter().find_map(|file| {
+        let file = crate_dir.join("src").join(file);
+        let source = fs::read_to_string(&file).ok()?;
+        Some(ProgramId {
+            id: declared_id(&source)?.to_string(),
+            source: IdSource::Source(file),
+        })
+    })
+}
+
+/// `target/deploy/<program>-keypair.json`, as written by `anchor build`.
+pub fn keypair_path(path: &Path, program: &str) -> PathBuf {
+    path.join("target")
+        .join("deploy")
+        .join(format!("{}-keypair.json", to_snake_case(program)))
+}
+
+/// The Base58 public key of a Solana CLI keypair file (a JSON array of the
+/// 32 byte secret key followed by the 32 byte public key).
+pub fn read_keypair_pubkey(path: &Path) -> Result<String> {
+    let json = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read keypair {}", path.display()))?;
+    let bytes: Vec<u8> = serde_json::from_str(&json)
+        .with_context(|| format!("Invalid keypair file {}", path.display()))?;
+    if bytes.len() != 64 {
+        anyhow::bail!(
+            "Invalid keypair file {}: expected 64 bytes, found {}",
+            path.display(),
+            bytes.len()
+        );
+    }
+    Ok(bs58::encode(&bytes[32..]).into_string())
+}
+
+/// Print the program ID each source gives for `program`.
+pub fn list(path: &Path, crate_dir: &Path, program: &str, source: &ProgramIdSource) -> Result<()> {
+    let resolved = resolve(path, crate_dir, program, source)?;
+    println!(
+        "{}: {} ({})",
+        to_snake_case(program),
+        resolved.id,
+        resolved.source
+    );
+
+    let keypair = keypair_path(path, program);
+    if keypair.exists() {
+        println!("  keypair   {}", read_keypair_pubkey(&keypair)?);
+    }
+    if let Some(doc) = read_anchor_toml(path)? {
+        for (cluster, programs) in program_tables(&doc) {
+            if let Some(id) = table_id(programs, program) {
+                println!("  {:<9} {}", cluster, id);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Make `Anchor.toml` and the declared ID in the generated code match the deploy
+/// keypair. Returns the ID.
+pub fn sync(path: &Path, crate_dir: &Path, program: &str) -> Result<String> {
+    let keypair = keypair_path(path, program);
+    if !keypair.exists() {
+        anyhow::bail!(
+            "No program keypair at {}; run `anchor build` or `solana-keygen new -o {}`",
+            keypair.display(),
+            keypair.display()
+        );
+    }
+    let id = read_keypair_pubkey(&keypair)?;
+    let name = to_snake_case(program);
+
+    let anchor_toml = path.join("Anchor.toml");
+    if let Some(mut doc) = read_anchor_toml(path)? {
+        let cluster = provider_cluster(&doc);
+        let programs = doc
+            .entry("programs")
+            .or_insert_with(|| Item::Table(implicit_table()))
+            .as_table_mut()
+            .context("Anchor.toml: [programs] is not a table")?;
+        programs
+            .entry(&cluster)
+            .or_insert_with(|| Item::Table(Table::new()));
+        // Update every cluster that lists the program, and add it to the
+        // provider cluster
+        for (table_cluster, table) in programs.iter_mut() {
+            let Some(table) = table.as_table_mut() else {
+                continue;
+            };
+            let key = table
+                .iter()
+                .map(|(k, _)| k.to_string())
+                .find(|k| to_snake_case(k) == name);
+            match key {
+                Some(key) => table[&key] = toml_edit::value(id.as_str()),
+                None if table_cluster.get() == cluster => {
+                    table[&name] = toml_edit::value(id.as_str())
+                }
+                None => {}
+            }
+        }
+        fs::write(&anchor_toml, doc.to_string())
+            .with_context(|| format!("Failed to write {}", anchor_toml.display()))?;
+        println!("Updated: {}", anchor_toml.display());
+    }
+
+    for file in DECLARE_ID_FILES {
+        let file = crate_dir.join("src").join(file);
+        if let Ok(mut source) = fs::read_to_string(&file) {
+            if let Some(range) = declared_id_range(&source) {
+                if source[range.clone()] != id {
+                    source.replace_range(range, &id);
+                    fs::write(&file, source)
+                        .with_context(|| format!("Failed to write {}", file.display()))?;
+                    println!("Updated: {}", file.display());
+                }
+            }
+        }
+    }
+
+    Ok(id)
+}
+
+/// List `program` under the provider cluster in `Anchor.toml` with the
+/// placeholder ID, unless it is listed already. Returns whether it was added.
+pub fn add_program(path: &Path, program: &str) -> Result<bool> {
+    let Some(mut doc) = read_anchor_toml(path)? else {
+        return Ok(false);
+    };
+    let cluster = provider_cluster(&doc);
+    if anchor_toml_id(&doc, &cluster, program).is_some() {
+        return Ok(false);
+    }
+    let programs = doc
+        .entry("programs")
+        .or_insert_with(|| Item::Table(implicit_table()))
+        .as_table_mut()
+        .context("Anchor.toml: [programs] is not a table")?;
+    let table = programs
+        .entry(&cluster)
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .with_context(|| format!("Anchor.toml: [programs.{}] is not a table", cluster))?;
+    table[&to_snake_case(program)] = toml_edit::value(DEFAULT_PROGRAM_ID);
+
+    let anchor_toml = path.join("Anchor.toml");
+    fs::write(&anchor_toml, doc.to_string())
+        .with_context(|| format!("Failed to write {}", anchor_toml.display()))?;
+    Ok(true)
+}
+
+fn read_anchor_toml(path: &Path) -> Result<Option<DocumentMut>> {
+    let file = path.join("Anchor.toml");
+    if !file.exists() {
+        return Ok(None);
+    }
+    let source =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let doc = source
+        .parse::<DocumentMut>()
+        .with_context(|| format!("Failed to parse {}", file.display()))?;
+    Ok(Some(doc))
+}
+
+/// `[provider] cluster`, lowercased as in `[programs.localnet]`. Custom
+/// RPC URLs fall back to localnet.
+fn provider_cluster(doc: &DocumentMut) -> String {
+    doc.get("provider")
+        .and_then(|p| p.get("cluster"))
+        .and_then(|c| c.as_str())
+        .map(|c| c.to_lowercase())
+        .filter(|c| !c.contains("://"))
+        .unwrap_or_else(|| DEFAULT_CLUSTER.to_string())
+}
+
+fn program_tables(doc: &DocumentMut) -> Vec<(&str, &Table)> {
+    doc.get("programs")
+        .and_then(|p| p.as_table())
+        .map(|programs| {
+            programs
+                .iter()
+                .filter_map(|(cluster, table)| Some((cluster, table.as_table()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn anchor_toml_id(doc: &DocumentMut, cluster: &str, program: &str) -> Option<String> {
+    program_tables(doc)
+        .into_iter()
+        .find(|(c, _)| *c == cluster)
+        .and_then(|(_, table)| table_id(table, program))
+}
+
+/// Program keys may be written in any case; `solx` compares them snake_cased.
+fn table_id(table: &Table, program: &str) -> Option<String> {
+    let name = to_snake_case(program);
+    table
+        .iter()
+        .find(|(key, _)| to_snake_case(key) == name)
+        .and_then(|(_, value)| value.as_str())
+        .map(str::to_string)
+}
+
+/// The ID in the first `declare_id!("...")` item of a generated file.
+fn declared_id(source: &str) -> Option<&str> {
+    declared_id_range(source).map(|range| &source[range])
+}
+
+/// The byte range of the ID in the first `declare_id!("...")` item, so it
+/// can be replaced without touching other uses of the same key, e.g. the
+/// System Program ID the placeholder equals. The macro must start a line,
+/// optionally behind a path like `solana_program::`; mentions in comments
+/// and strings do not count.
+fn declared_id_range(source: &str) -> Option<std::ops::Range<usize>> {
+    const MACRO: &str = "declare_id!(\"";
+    let mut offset = 0;
+    let mut in_block_comment = false;
+    for line in source.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let item = line.trim_start();
+        if in_block_comment || item.starts_with("/*") {
+            in_block_comment = !line.contains("*/");
+            continue;
+        }
+        let Some(path_len) = item.find(MACRO) else {
+            continue;
+        };
+        let path = &item[..path_len];
+        let is_path = path.is_empty()
+            || (path.ends_with("::")
+                && path
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':'));
+        if !is_path {
+            continue;
+        }
+        let start = line_start + (line.len() - item.len()) + path_len + MACRO.len();
+        let len = source[start..].find('"')?;
+        return Some(start..start + len);
+    }
+    None
+}
+
+fn implicit_table() -> Table {
+    let mut table = Table::new();
+    table.set_implicit(true);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_and_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join("target").join("deploy")).unwrap();
+        fs::write(
+            root.join("src").join("generated.rs"),
+            format!(
+                "// System program: {}\n// Example: declare_id!(\"Other\");\n\
+                 solana_program::declare_id!(\"{}\");\n",
+                DEFAULT_PROGRAM_ID, DEFAULT_PROGRAM_ID
+            ),
+        )
+        .unwrap();
+        fs::write(
+            root.join("Anchor.toml"),
+            "[programs.localnet]\nmy_counter = \"11111111111111111111111111111111\"\n\n[provider]\ncluster = \"Localnet\"\n",
+        )
+        .unwrap();
+
+        // The placeholder in Anchor.toml and the source counts as unset
+        let resolved = resolve(&root, &root, "MyCounter", &ProgramIdSource::Auto).unwrap();
+        assert!(matches!(resolved.source, IdSource::Default));
+
+        // Public key bytes 1..=32 after a zeroed secret key
+        let keypair: Vec<u8> = (0..32).map(|_| 0).chain(1..=32).collect();
+        fs::write(
+            keypair_path(&root, "MyCounter"),
+            serde_json::to_string(&keypair).unwrap(),
+        )
+        .unwrap();
+        let id = bs58::encode((1..=32).collect::<Vec<u8>>()).into_string();
+        let resolved = resolve(&root, &root, "MyCounter", &ProgramIdSource::Auto).unwrap();
+        assert_eq!(resolved.id, id);
+        assert!(matches!(resolved.source, IdSource::Keypair(_)));
+
+        assert_eq!(sync(&root, &root, "MyCounter").unwrap(), id);
+        assert_eq!(
+            resolve(&root, &root, "MyCounter", &ProgramIdSource::Auto)
+                .unwrap()
+                .id,
+            id
+        );
+        assert!(resolve(&root, &root, "Other", &ProgramIdSource::Keypair).is_err());
+        let generated = fs::read_to_string(root.join("src").join("generated.rs")).unwrap();
+        assert_eq!(declared_id(&generated), Some(id.as_str()));
+        // Only the declared ID changes, not other uses of the placeholder key
+        assert!(generated.starts_with(&format!(
+            "// System program: {}\n// Example: declare_id!(\"Other\");\n",
+            DEFAULT_PROGRAM_ID
+        )));
+    }
+}
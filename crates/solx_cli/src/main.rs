@@ -1,12 +1,28 @@
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
 
-use solx_codegen::generate_anchor_code;
+mod cargo;
+mod keys;
+mod manifest;
+mod templates;
+
+use solx_ast::TestDef;
+use solx_client::{generate_rust_client, generate_ts_client};
+use solx_codegen::{missing_helpers, BackendOptions, BackendRegistry};
+use solx_hir::lints;
+use solx_hir::naming::{to_pascal_case, to_snake_case};
 use solx_hir::Hir;
-use solx_parser::parse;
+use solx_idl::generate_idl;
+use solx_interp::fuzz::{fuzz, FuzzOptions};
+use solx_interp::runner::run_test;
+use solx_parser::load;
+use solx_verify::{lower_program, verify, Solver, Verdict};
+
+use manifest::{Manifest, ProgramIdSource, MANIFEST_FILE};
+use templates::Template;
 
 #[derive(Parser)]
 #[command(name = "solx")]
@@ -19,16 +35,59 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Create a new SOL-X project
+    /// Create a new SOL-X project in an Anchor workspace
     New {
         /// Project name
         name: String,
+        /// Program to start from
+        #[arg(short, long, value_enum, default_value_t = Template::Counter)]
+        template: Template,
+    },
+    /// Add SOL-X to an existing Anchor workspace
+    Init {
+        /// Workspace directory (default: current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Program to add SOL-X to: an existing crate in programs/, or a
+        /// new one (default: the only program in programs/)
+        #[arg(long)]
+        program: Option<String>,
+        /// Program source to start from
+        #[arg(short, long, value_enum, default_value_t = Template::Blank)]
+        template: Template,
     },
-    /// Build the SOL-X project (generates Anchor code)
+    /// Build the SOL-X project (generates Anchor or native Rust code)
     Build {
         /// Project directory (default: current directory)
         #[arg(short, long)]
         path: Option<PathBuf>,
+        /// Code generation backend: anchor or native (default: [program]
+        /// target in solx.toml, or anchor)
+        #[arg(short, long)]
+        target: Option<String>,
+    },
+    /// Generate the Anchor IDL JSON without building the program
+    Idl {
+        /// Project directory (default: current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Output file (default: [clients] idl in solx.toml, or
+        /// target/idl/<program>.json)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Generate a client SDK for the program
+    Client {
+        /// Project directory (default: current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Client language
+        #[arg(short, long, value_enum, default_value_t = ClientLang::Ts)]
+        lang: ClientLang,
+        /// Output file for TypeScript, crate directory for Rust (default:
+        /// [clients] in solx.toml, or clients/ts/<program>.ts or clients/rust)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
     },
     /// Format SOL-X source files
     Fmt {
@@ -36,165 +95,407 @@ enum Commands {
         #[arg(short, long)]
         path: Option<PathBuf>,
     },
-    /// Run tests
+    /// Run the program's `test` blocks in-process
     Test {
+        /// Only run tests whose name contains this
+        filter: Option<String>,
+        /// Project directory (default: current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Run `anchor test` against a local validator instead
+        #[arg(long)]
+        anchor: bool,
+    },
+    /// Call instructions in random sequences and check the invariants
+    Fuzz {
+        /// Project directory (default: current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// Sequences to run
+        #[arg(long, default_value_t = 1000)]
+        runs: u32,
+        /// Calls per sequence
+        #[arg(long, default_value_t = 20)]
+        calls: usize,
+        /// Seed for the random calls, to repeat a run (default: random)
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Prove with an SMT solver that arithmetic cannot overflow and that
+    /// account invariants are preserved
+    Verify {
+        /// Project directory (default: current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// z3 or cvc5, by name or path (default: the first on the PATH)
+        #[arg(long)]
+        solver: Option<String>,
+        /// Write the SMT-LIB scripts to this directory instead of solving
+        #[arg(long)]
+        smt: Option<PathBuf>,
+    },
+    /// Manage the program ID
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Show the program ID and where it comes from
+    List {
+        /// Project directory (default: current directory)
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+    },
+    /// Write the deploy keypair's ID to Anchor.toml, the generated code, the IDL and clients
+    Sync {
         /// Project directory (default: current directory)
         #[arg(short, long)]
         path: Option<PathBuf>,
     },
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ClientLang {
+    /// TypeScript, using @solana/web3.js
+    Ts,
+    /// A Rust crate depending only on solana-program and borsh
+    Rust,
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Commands::New { name } => cmd_new(&name)?,
-        Commands::Build { path } => cmd_build(path.as_deref().unwrap_or(PathBuf::from(".").as_path()))?,
+        Commands::New { name, template } => cmd_new(&name, template)?,
+        Commands::Init {
+            path,
+            program,
+            template,
+        } => cmd_init(
+            path.as_deref().unwrap_or(PathBuf::from(".").as_path()),
+            program.as_deref(),
+            template,
+        )?,
+        Commands::Build { path, target } => cmd_build(
+            path.as_deref().unwrap_or(PathBuf::from(".").as_path()),
+            target.as_deref(),
+        )?,
+        Commands::Idl { path, out } => cmd_idl(
+            path.as_deref().unwrap_or(PathBuf::from(".").as_path()),
+            out.as_deref(),
+        )?,
+        Commands::Client { path, lang, out } => cmd_client(
+            path.as_deref().unwrap_or(PathBuf::from(".").as_path()),
+            lang,
+            out.as_deref(),
+        )?,
         Commands::Fmt { path } => cmd_fmt(path.as_deref().unwrap_or(PathBuf::from(".").as_path()))?,
-        Commands::Test { path } => cmd_test(path.as_deref().unwrap_or(PathBuf::from(".").as_path()))?,
+        Commands::Test {
+            filter,
+            path,
+            anchor,
+        } => {
+            let path = path.unwrap_or_else(|| PathBuf::from("."));
+            if anchor {
+                cmd_anchor_test(&path)?
+            } else {
+                cmd_test(&path, filter.as_deref())?
+            }
+        }
+        Commands::Fuzz {
+            path,
+            runs,
+            calls,
+            seed,
+        } => cmd_fuzz(
+            path.as_deref().unwrap_or(PathBuf::from(".").as_path()),
+            runs,
+            calls,
+            seed,
+        )?,
+        Commands::Verify { path, solver, smt } => cmd_verify(
+            path.as_deref().unwrap_or(PathBuf::from(".").as_path()),
+            solver.as_deref(),
+            smt.as_deref(),
+        )?,
+        Commands::Keys { command } => match command {
+            KeysCommand::List { path } => {
+                cmd_keys_list(path.as_deref().unwrap_or(PathBuf::from(".").as_path()))?
+            }
+            KeysCommand::Sync { path } => {
+                cmd_keys_sync(path.as_deref().unwrap_or(PathBuf::from(".").as_path()))?
+            }
+        },
     }
 
     Ok(())
 }
 
-fn cmd_new(name: &str) -> Result<()> {
+fn cmd_new(name: &str, template: Template) -> Result<()> {
     let dir = PathBuf::from(name);
     if dir.exists() {
         anyhow::bail!("Directory {} already exists", name);
     }
+    let program = program_name(name)?;
 
-    fs::create_dir_all(&dir)?;
-    fs::create_dir_all(dir.join("src"))?;
+    for (file, contents) in templates::workspace(&program, template) {
+        write_file(&dir.join(file), &contents)?;
+    }
 
-    // Create Anchor.toml (program ID must be valid Base58; same as declare_id! in generated lib.rs)
-    const DEFAULT_PROGRAM_ID: &str = "11111111111111111111111111111111";
-    let anchor_toml = format!(
-        r#"[features]
-resolution = true
-skip-lint = false
+    println!("Created new SOL-X project: {}", name);
+    println!("  cd {}", name);
+    println!("  solx test");
+    println!("  yarn install");
+    println!("  solx build");
+    println!("  solx keys sync && solx build");
+    println!("  anchor test");
 
-[programs.localnet]
-{} = "{}"
+    Ok(())
+}
 
-[registry]
-url = "https://api.apr.dev"
+/// Add SOL-X to the Anchor workspace at `path`: write solx.toml, and the
+/// program source for an existing program crate or a whole new crate.
+fn cmd_init(path: &std::path::Path, program: Option<&str>, template: Template) -> Result<()> {
+    if !path.join("Anchor.toml").exists() {
+        anyhow::bail!(
+            "No Anchor.toml in {}; run `solx new` to create a workspace",
+            path.display()
+        );
+    }
+    if path.join(MANIFEST_FILE).exists() {
+        anyhow::bail!("{} already exists in {}", MANIFEST_FILE, path.display());
+    }
 
-[provider]
-cluster = "Localnet"
-wallet = "~/.config/solana/id.json"
+    // Crate directories may use dashes, as `anchor init` creates them
+    let programs = workspace_programs(path)?;
+    let crate_name = match program {
+        Some(name) => {
+            let name = to_snake_case(&program_name(name)?);
+            programs
+                .into_iter()
+                .find(|p| to_snake_case(p) == name)
+                .unwrap_or(name)
+        }
+        None => match programs.as_slice() {
+            [program] => program.clone(),
+            [] => anyhow::bail!(
+                "No programs in {}; name the program to create with --program",
+                path.join("programs").display()
+            ),
+            _ => anyhow::bail!(
+                "{} has several programs ({}); choose one with --program",
+                path.display(),
+                programs.join(", ")
+            ),
+        },
+    };
+    let program = program_name(&crate_name)?;
+    let dir = format!("programs/{}", crate_name);
+    let crate_dir = path.join(&dir);
+
+    if crate_dir.join("Cargo.toml").exists() {
+        let solx_file = crate_dir.join("src").join("program.solx");
+        if solx_file.exists() {
+            println!("Using existing {}", solx_file.display());
+        } else {
+            write_file(&solx_file, &template.program(&program))?;
+            println!("Created: {}", solx_file.display());
+        }
+        let lib_rs = crate_dir.join("src").join("lib.rs");
+        let lib_rs_source = fs::read_to_string(&lib_rs).unwrap_or_default();
+        if lib_rs.exists() && !lib_rs_source.contains("include!(\"generated.rs\")") {
+            println!(
+                "Note: {} is hand-written Anchor code. Port its instructions to program.solx, \
+                 then move it aside and run `solx build` to recreate it.",
+                lib_rs.display()
+            );
+        }
+        let cargo_toml = fs::read_to_string(crate_dir.join("Cargo.toml")).unwrap_or_default();
+        if template.uses_tokens() && !cargo_toml.contains("anchor-spl") {
+            println!(
+                "Note: the template uses token accounts; add anchor-spl = \"{}\" to {}",
+                manifest::ANCHOR_VERSION,
+                crate_dir.join("Cargo.toml").display()
+            );
+        }
+    } else {
+        for (file, contents) in templates::program_crate(&program, template) {
+            let file = crate_dir.join(file);
+            write_file(&file, &contents)?;
+            println!("Created: {}", file.display());
+        }
+        let test = path
+            .join("tests")
+            .join(format!("{}.ts", to_snake_case(&program)));
+        if !test.exists() {
+            write_file(&test, &template.test(&program))?;
+            println!("Created: {}", test.display());
+        }
+        if keys::add_program(path, &program)? {
+            println!("Updated: {}", path.join("Anchor.toml").display());
+        }
+    }
 
-[scripts]
-test = "yarn run ts-mocha -p ./tsconfig.json -t 1000000 tests/**/*.ts"
-"#,
-        name, DEFAULT_PROGRAM_ID
+    let manifest = path.join(MANIFEST_FILE);
+    write_file(
+        &manifest,
+        &manifest::template(&to_snake_case(&program), &dir),
+    )?;
+    println!("Created: {}", manifest.display());
+    println!(
+        "Run `solx build` to generate {}",
+        crate_dir.join("src").display()
     );
-    fs::write(dir.join("Anchor.toml"), anchor_toml)?;
-
-    // Create Cargo.toml
-    let cargo_toml = format!(
-        r#"[package]
-name = "{}"
-version = "0.1.0"
-description = "Generated Anchor program from SOL-X"
-edition = "2021"
-
-[lib]
-crate-type = ["cdylib", "lib"]
-name = "{}"
-
-[features]
-no-entrypoint = []
-no-idl = []
-no-log-ix-name = []
-cpi = ["no-entrypoint"]
-default = []
-
-[dependencies]
-anchor-lang = "0.30.0"
-
-[profile.release]
-overflow-checks = true
-"#,
-        name, name
-    );
-    fs::write(dir.join("Cargo.toml"), cargo_toml)?;
-
-    // Create default src/lib.rs (will be overwritten by build)
-    let lib_rs = r#"use anchor_lang::prelude::*;
-
-declare_id!("11111111111111111111111111111111");
-"#;
-    fs::write(dir.join("src").join("lib.rs"), lib_rs)?;
-
-    // Create default program.solx
-    let program_solx = format!(
-        r#"program {}
-
-account CounterState {{
-  authority: Pubkey
-  count: u64
-}}
-
-instruction initialize(authority: Signer, state: CounterState) {{
-  init account state: CounterState payer authority
-  state.authority = authority.key
-  state.count = 0
-}}
-
-instruction increment(authority: Signer, state: CounterState) {{
-  require state.authority == authority.key
-  state.count += 1
-}}
-"#,
-        name
-    );
-    fs::write(dir.join("src").join("program.solx"), program_solx)?;
+    Ok(())
+}
 
-    println!("Created new SOL-X project: {}", name);
-    println!("  cd {}", name);
-    println!("  solx build");
+/// The program name for a project or crate name, e.g. `my-vault` ->
+/// `MyVault`.
+fn program_name(name: &str) -> Result<String> {
+    let program = to_pascal_case(name);
+    if !program.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        anyhow::bail!(
+            "Invalid program name {:?}: it must start with a letter",
+            name
+        );
+    }
+    Ok(program)
+}
 
-    Ok(())
+/// Names of the program crates in `programs/`, sorted.
+fn workspace_programs(path: &std::path::Path) -> Result<Vec<String>> {
+    let dir = path.join("programs");
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut programs = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let entry = entry?;
+        if entry.path().join("Cargo.toml").exists() {
+            programs.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    programs.sort();
+    Ok(programs)
 }
 
-fn cmd_build(path: &std::path::Path) -> Result<()> {
-    let src_dir = path.join("src");
-    let solx_in_src = src_dir.join("program.solx");
-    let solx_in_root = path.join("program.solx");
+/// Parse the project's program and the files it imports, and type check
+/// them as one program.
+fn load_hir(path: &std::path::Path, manifest: &Manifest) -> Result<Hir> {
+    let solx_file = manifest.entry_file(path)?;
 
-    let solx_file = if solx_in_src.exists() {
-        solx_in_src
-    } else if solx_in_root.exists() {
-        solx_in_root
-    } else {
+    println!("Parsing SOL-X source...");
+    let ast = load(&solx_file)?;
+    if ast.files.len() > 1 {
+        println!("  {} files", ast.files.len());
+    }
+    println!("Type checking...");
+    let solx_dir = solx_file.parent().unwrap_or(path);
+    Ok(Hir::from_ast_in(ast, solx_dir)?
+        .with_arithmetic(manifest.arithmetic)
+        .with_invariant_checks(manifest.invariant_checks)
+        .with_lint_levels(manifest.lints.clone()))
+}
+
+/// Print the program's lint diagnostics, failing if any lint is denied.
+fn check_lints(hir: &Hir) -> Result<()> {
+    let diagnostics = hir.lints();
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic.render(&hir.program));
+    }
+    let denied = diagnostics
+        .iter()
+        .filter(|d| d.level == lints::Level::Deny)
+        .count();
+    if denied > 0 {
         anyhow::bail!(
-            "No program.solx found in {} or {}",
-            src_dir.display(),
-            path.display()
+            "{} denied lint{}; fix them or lower their level under [lints] in solx.toml",
+            denied,
+            if denied == 1 { "" } else { "s" }
         );
+    }
+    Ok(())
+}
+
+fn cmd_build(path: &std::path::Path, target: Option<&str>) -> Result<()> {
+    let manifest = manifest::load(path)?;
+    let registry = BackendRegistry::default();
+    let backend = registry.resolve(target.unwrap_or(&manifest.target))?;
+    let hir = load_hir(path, &manifest)?;
+    check_lints(&hir)?;
+    let program_id = resolve_program_id(path, &hir, &manifest)?;
+    println!("Generating {} code...", backend.name());
+    let options = BackendOptions {
+        program_id: program_id.clone(),
     };
 
-    println!("Parsing SOL-X source...");
-    let source = fs::read_to_string(&solx_file)
-        .with_context(|| format!("Failed to read {}", solx_file.display()))?;
+    // Write generated files into the program crate (creating src/ for
+    // examples with program.solx in root)
+    let crate_dir = manifest.crate_dir(path);
+    for file in backend.generate(&hir, &options)? {
+        let file_path = crate_dir.join(&file.path);
+        if !file.overwrite && file_path.exists() {
+            continue;
+        }
+        write_file(&file_path, &file.contents)?;
+        println!("Generated: {}", file_path.display());
+    }
+    let changes =
+        cargo::sync_dependencies(&crate_dir, &backend.dependencies(&hir), backend.replaces())?;
+    if !changes.is_empty() {
+        println!(
+            "Updated {} for the {} target: added [{}], removed [{}]",
+            crate_dir.join("Cargo.toml").display(),
+            backend.name(),
+            changes.added.join(", "),
+            changes.removed.join(", ")
+        );
+    }
 
-    let ast = parse(&source)?;
-    println!("Type checking...");
-    let hir = Hir::from_ast(ast)?;
-    println!("Generating Anchor code...");
-    let anchor_code = generate_anchor_code(&hir)?;
-
-    // Write generated code (ensure src/ exists for examples with program.solx in root)
-    fs::create_dir_all(&src_dir)?;
-    let lib_rs_path = src_dir.join("lib.rs");
-    let full_code = format!(
-        "use anchor_lang::prelude::*;\n\ndeclare_id!(\"11111111111111111111111111111111\");\n\n{}",
-        anchor_code
-    );
-    fs::write(&lib_rs_path, full_code)
-        .with_context(|| format!("Failed to write {}", lib_rs_path.display()))?;
+    // lib.rs is hand-written after the first build; older projects still
+    // have a fully generated one
+    let lib_rs = fs::read_to_string(crate_dir.join("src").join("lib.rs")).unwrap_or_default();
+    if !lib_rs.contains("include!(\"generated.rs\")") {
+        println!(
+            "Warning: src/lib.rs does not include!(\"generated.rs\"); delete it to recreate it, \
+             or add the include and move hand-written code next to it."
+        );
+    } else {
+        let missing = missing_helpers(&hir, &lib_rs);
+        if !missing.is_empty() {
+            anyhow::bail!(
+                "src/lib.rs does not implement every extern fn; add to `impl ExternFns for Helpers` \
+                 in `mod helpers`:\n{}",
+                missing
+                    .iter()
+                    .map(|signature| format!("    {} {{ ... }}", signature))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+    }
 
-    println!("Generated Anchor code: {}", lib_rs_path.display());
+    // Outputs listed in solx.toml follow every build
+    if let Some(idl) = &manifest.clients.idl {
+        let out = path.join(idl);
+        write_idl(&out, &hir, &program_id)?;
+        println!("Generated: {}", out.display());
+    }
+    for lang in [ClientLang::Ts, ClientLang::Rust] {
+        if let Some(out) = manifest_client(&manifest, lang) {
+            let out = path.join(out);
+            write_client(&out, &hir, lang, &program_id)?;
+            println!("Generated: {}", out.display());
+        }
+    }
+
+    if backend.name() != "anchor" {
+        return Ok(());
+    }
+    check_anchor_version(&crate_dir, &manifest);
 
     // Run anchor build only when this is an Anchor workspace (has Anchor.toml)
     let anchor_toml = path.join("Anchor.toml");
@@ -213,24 +514,350 @@ fn cmd_build(path: &std::path::Path) -> Result<()> {
                 anyhow::bail!("Anchor build failed with exit code: {:?}", s.code());
             }
             Err(e) => {
-                anyhow::bail!("Failed to run anchor build: {}. Make sure Anchor is installed.", e);
+                anyhow::bail!(
+                    "Failed to run anchor build: {}. Make sure Anchor is installed.",
+                    e
+                );
             }
         }
     } else {
-        println!("Skipping anchor build (no Anchor.toml in {}).", path.display());
+        println!(
+            "Skipping anchor build (no Anchor.toml in {}).",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Warn when the program crate depends on another anchor-lang version than
+/// solx.toml pins.
+fn check_anchor_version(crate_dir: &std::path::Path, manifest: &Manifest) {
+    let Some(expected) = &manifest.anchor_version else {
+        return;
+    };
+    let Ok(cargo_toml) = fs::read_to_string(crate_dir.join("Cargo.toml")) else {
+        return;
+    };
+    let Ok(doc) = cargo_toml.parse::<toml_edit::DocumentMut>() else {
+        return;
+    };
+    let dependency = doc.get("dependencies").and_then(|d| d.get("anchor-lang"));
+    let version = dependency
+        .and_then(|d| d.as_str().or_else(|| d.get("version")?.as_str()))
+        .map(|v| v.trim_start_matches(['=', '^', '~']));
+    if let Some(version) = version {
+        if version != expected {
+            println!(
+                "Warning: Cargo.toml depends on anchor-lang {} but {} pins {}",
+                version, MANIFEST_FILE, expected
+            );
+        }
+    }
+}
+
+/// Resolve the program ID and report where it came from.
+fn resolve_program_id(path: &std::path::Path, hir: &Hir, manifest: &Manifest) -> Result<String> {
+    let program_id = keys::resolve(
+        path,
+        &manifest.crate_dir(path),
+        &hir.program.name,
+        &manifest.program_id,
+    )?;
+    println!("Program ID: {} ({})", program_id.id, program_id.source);
+    Ok(program_id.id)
+}
+
+fn cmd_idl(path: &std::path::Path, out: Option<&std::path::Path>) -> Result<()> {
+    let manifest = manifest::load(path)?;
+    let hir = load_hir(path, &manifest)?;
+    let program_id = resolve_program_id(path, &hir, &manifest)?;
+    println!("Generating IDL...");
+    let out = out
+        .map(|o| o.to_path_buf())
+        .unwrap_or_else(|| idl_path(path, &hir, &manifest));
+    write_idl(&out, &hir, &program_id)?;
+    println!("Generated IDL: {}", out.display());
+    Ok(())
+}
+
+fn idl_path(path: &std::path::Path, hir: &Hir, manifest: &Manifest) -> PathBuf {
+    if let Some(idl) = &manifest.clients.idl {
+        return path.join(idl);
+    }
+    path.join("target")
+        .join("idl")
+        .join(format!("{}.json", to_snake_case(&hir.program.name)))
+}
+
+fn write_idl(out: &std::path::Path, hir: &Hir, program_id: &str) -> Result<()> {
+    let json = serde_json::to_string_pretty(&generate_idl(hir, program_id))?;
+    write_file(out, &(json + "\n"))
+}
+
+fn cmd_client(
+    path: &std::path::Path,
+    lang: ClientLang,
+    out: Option<&std::path::Path>,
+) -> Result<()> {
+    let manifest = manifest::load(path)?;
+    let hir = load_hir(path, &manifest)?;
+    let program_id = resolve_program_id(path, &hir, &manifest)?;
+    println!("Generating client...");
+
+    let out = out
+        .map(|o| o.to_path_buf())
+        .unwrap_or_else(|| client_path(path, &hir, &manifest, lang));
+    write_client(&out, &hir, lang, &program_id)?;
+
+    println!("Generated client: {}", out.display());
+    Ok(())
+}
+
+/// The client path set in solx.toml, or `clients/ts/<program>.ts` or the
+/// `clients/rust` crate directory.
+fn client_path(
+    path: &std::path::Path,
+    hir: &Hir,
+    manifest: &Manifest,
+    lang: ClientLang,
+) -> PathBuf {
+    if let Some(out) = manifest_client(manifest, lang) {
+        return path.join(out);
+    }
+    match lang {
+        ClientLang::Ts => path
+            .join("clients")
+            .join("ts")
+            .join(format!("{}.ts", to_snake_case(&hir.program.name))),
+        ClientLang::Rust => path.join("clients").join("rust"),
+    }
+}
+
+fn manifest_client(manifest: &Manifest, lang: ClientLang) -> Option<&std::path::Path> {
+    match lang {
+        ClientLang::Ts => manifest.clients.ts.as_deref(),
+        ClientLang::Rust => manifest.clients.rust.as_deref(),
+    }
+}
+
+fn write_client(
+    out: &std::path::Path,
+    hir: &Hir,
+    lang: ClientLang,
+    program_id: &str,
+) -> Result<()> {
+    match lang {
+        ClientLang::Ts => write_file(out, &generate_ts_client(hir, program_id)),
+        ClientLang::Rust => {
+            let client = generate_rust_client(hir, program_id);
+            write_file(&out.join("Cargo.toml"), &client.cargo_toml)?;
+            write_file(&out.join("src").join("lib.rs"), &client.lib_rs)
+        }
+    }
+}
+
+fn write_file(path: &std::path::Path, contents: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn cmd_keys_list(path: &std::path::Path) -> Result<()> {
+    let manifest = manifest::load(path)?;
+    let hir = load_hir(path, &manifest)?;
+    keys::list(
+        path,
+        &manifest.crate_dir(path),
+        &hir.program.name,
+        &manifest.program_id,
+    )
+}
+
+/// Sync the program ID from the deploy keypair and regenerate the IDL and
+/// clients that exist at their default locations.
+fn cmd_keys_sync(path: &std::path::Path) -> Result<()> {
+    let manifest = manifest::load(path)?;
+    if let ProgramIdSource::Fixed(id) = &manifest.program_id {
+        anyhow::bail!(
+            "{} sets program-id = \"{}\"; change it there or set it to \"auto\" to sync",
+            MANIFEST_FILE,
+            id
+        );
+    }
+    let hir = load_hir(path, &manifest)?;
+    let program_id = keys::sync(path, &manifest.crate_dir(path), &hir.program.name)?;
+
+    let idl = idl_path(path, &hir, &manifest);
+    if idl.exists() {
+        write_idl(&idl, &hir, &program_id)?;
+        println!("Updated: {}", idl.display());
+    }
+    for lang in [ClientLang::Ts, ClientLang::Rust] {
+        let out = client_path(path, &hir, &manifest, lang);
+        if out.exists() {
+            write_client(&out, &hir, lang, &program_id)?;
+            println!("Updated: {}", out.display());
+        }
     }
 
+    println!("Program ID: {}", program_id);
     Ok(())
 }
 
-fn cmd_fmt(_path: &std::path::Path) -> Result<()> {
+fn cmd_fmt(path: &std::path::Path) -> Result<()> {
+    manifest::load(path)?;
     // TODO: Implement formatter
     println!("Formatting not yet implemented. Coming soon!");
     Ok(())
 }
 
-fn cmd_test(path: &std::path::Path) -> Result<()> {
-    println!("Running tests...");
+fn cmd_test(path: &std::path::Path, filter: Option<&str>) -> Result<()> {
+    let manifest = manifest::load(path)?;
+    let hir = load_hir(path, &manifest)?;
+    let tests: Vec<&TestDef> = hir
+        .program
+        .tests
+        .iter()
+        .filter(|t| filter.is_none_or(|f| t.name.contains(f)))
+        .collect();
+    println!(
+        "\nrunning {} test{}",
+        tests.len(),
+        if tests.len() == 1 { "" } else { "s" }
+    );
+    let mut failures = Vec::new();
+    for test in &tests {
+        match run_test(&hir, test) {
+            Ok(()) => println!("test {} ... ok", test.name),
+            Err(err) => {
+                println!("test {} ... FAILED", test.name);
+                failures.push((test.name.as_str(), err));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, err) in &failures {
+            println!("\n---- {} ----\n{:#}", name, err);
+        }
+    }
+    let filtered = hir.program.tests.len() - tests.len();
+    println!(
+        "\ntest result: {}. {} passed; {} failed; {} filtered out",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        tests.len() - failures.len(),
+        failures.len(),
+        filtered
+    );
+    if !failures.is_empty() {
+        anyhow::bail!("{} of {} tests failed", failures.len(), tests.len());
+    }
+    Ok(())
+}
+
+fn cmd_fuzz(path: &std::path::Path, runs: u32, calls: usize, seed: Option<u64>) -> Result<()> {
+    let manifest = manifest::load(path)?;
+    let hir = load_hir(path, &manifest)?;
+    let seed = seed.unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64)
+    });
+    let account_invariants = hir
+        .program
+        .accounts
+        .iter()
+        .any(|a| !a.invariants.is_empty());
+    if hir.program.invariants.is_empty() && !account_invariants {
+        println!("No invariants declared; only looking for panics.");
+    }
+    println!(
+        "Fuzzing {}: {} runs of {} calls (seed {})",
+        hir.program.name, runs, calls, seed
+    );
+
+    let options = FuzzOptions { runs, calls, seed };
+    let report = fuzz(&hir, &options)?;
+    let Some(failure) = report.failure else {
+        println!(
+            "No failures in {} calls ({} rejected by the program).",
+            report.calls, report.rejected
+        );
+        return Ok(());
+    };
+
+    println!("\n{}", failure.message);
+    println!(
+        "Shrunk to {} call{}. Reproduce with:\n",
+        failure.calls.len(),
+        if failure.calls.len() == 1 { "" } else { "s" }
+    );
+    print!("{}", failure.to_test(&format!("fuzz seed {}", seed)));
+    anyhow::bail!("Fuzzing failed after {} runs", report.runs);
+}
+
+fn cmd_verify(
+    path: &std::path::Path,
+    solver: Option<&str>,
+    smt: Option<&std::path::Path>,
+) -> Result<()> {
+    let manifest = manifest::load(path)?;
+    let hir = load_hir(path, &manifest)?;
+
+    if let Some(dir) = smt {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        for lowered in lower_program(&hir)? {
+            let file = dir.join(format!("{}.smt2", lowered.instruction));
+            fs::write(&file, lowered.script())
+                .with_context(|| format!("Failed to write {}", file.display()))?;
+            println!(
+                "  {} ({} obligations)",
+                file.display(),
+                lowered.obligations.len()
+            );
+        }
+        return Ok(());
+    }
+
+    let solver = Solver::find(solver)?;
+    println!("Verifying {} with {}...", hir.program.name, solver.name);
+    let checked = verify(&hir, &solver)?;
+    let mut failed = 0;
+    let mut instruction = None;
+    for c in &checked {
+        if instruction != Some(&c.instruction) {
+            println!("{}", c.instruction);
+            instruction = Some(&c.instruction);
+        }
+        match &c.verdict {
+            Verdict::Proved => println!("  ok      {}", c.obligation.description),
+            Verdict::Counterexample(values) => {
+                failed += 1;
+                println!("  FAILED  {}", c.obligation.description);
+                for (name, value) in values {
+                    println!("          {} = {}", name, value);
+                }
+            }
+            Verdict::Unknown(reason) => {
+                failed += 1;
+                println!("  UNKNOWN {} ({})", c.obligation.description, reason);
+            }
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} obligations not proved", failed, checked.len());
+    }
+    println!("All {} obligations proved.", checked.len());
+    Ok(())
+}
+
+fn cmd_anchor_test(path: &std::path::Path) -> Result<()> {
+    manifest::load(path)?;
+    println!("Running anchor test...");
     let status = Command::new("anchor")
         .arg("test")
         .current_dir(path)
@@ -245,7 +872,10 @@ fn cmd_test(path: &std::path::Path) -> Result<()> {
             anyhow::bail!("Tests failed with exit code: {:?}", s.code());
         }
         Err(e) => {
-            anyhow::bail!("Failed to run anchor test: {}. Make sure Anchor is installed.", e);
+            anyhow::bail!(
+                "Failed to run anchor test: {}. Make sure Anchor is installed.",
+                e
+            );
         }
     }
 }
@@ -0,0 +1,395 @@
+//! `solx.toml`, the project manifest.
+//!
+//! ```toml
+//! [program]
+//! dir = "programs/counter"     # program crate, in an Anchor workspace
+//! entry = "programs/counter/src/program.solx"   # root source file
+//! target = "anchor"            # backend used by `solx build`
+//!
+//! [anchor]
+//! version = "0.30.1"           # anchor-lang version of the program crate
+//!
+//! [build]
+//! arithmetic = "checked"       # panic, checked or wrapping
+//! program-id = "auto"          # auto, anchor-toml, keypair, declare-id or a Base58 ID
+//! invariant-checks = false     # check account invariants in every instruction
+//!
+//! [clients]                    # written on every build when set
+//! idl = "target/idl/counter.json"
+//! ts = "clients/ts/counter.ts"
+//! rust = "clients/rust"
+//!
+//! [lints]
+//! missing-close = "deny"       # allow, warn or deny
+//! ```
+//!
+//! Every key is optional. Projects without a `solx.toml` get the defaults:
+//! the program crate is the project directory, and the entry file is found
+//! in its `src/` or at its root. Anchor.toml, `target/` and the IDL and
+//! client paths are always relative to the project directory.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table};
+
+use solx_codegen::BackendRegistry;
+use solx_hir::lints::{self, Level};
+use solx_hir::Arithmetic;
+
+pub const MANIFEST_FILE: &str = "solx.toml";
+
+/// anchor-lang version for new projects, the one the Anchor backend targets.
+pub const ANCHOR_VERSION: &str = solx_codegen::backend::ANCHOR_VERSION;
+
+pub struct Manifest {
+    /// Program crate directory, relative to the project directory
+    pub dir: Option<PathBuf>,
+    /// Root source file, relative to the project directory
+    pub entry: Option<PathBuf>,
+    pub target: String,
+    /// anchor-lang version the program crate should depend on, if pinned
+    pub anchor_version: Option<String>,
+    pub arithmetic: Arithmetic,
+    /// Whether instructions check the invariants of the accounts they write
+    pub invariant_checks: bool,
+    pub program_id: ProgramIdSource,
+    pub clients: Clients,
+    pub lints: BTreeMap<String, Level>,
+}
+
+/// Where `solx` takes the program ID from; see [`crate::keys`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramIdSource {
+    /// The first source that has one
+    Auto,
+    AnchorToml,
+    Keypair,
+    DeclareId,
+    /// A fixed Base58 ID
+    Fixed(String),
+}
+
+/// Output paths of the IDL and clients, relative to the project directory.
+#[derive(Default)]
+pub struct Clients {
+    pub idl: Option<PathBuf>,
+    pub ts: Option<PathBuf>,
+    pub rust: Option<PathBuf>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest {
+            dir: None,
+            entry: None,
+            target: "anchor".to_string(),
+            anchor_version: None,
+            arithmetic: Arithmetic::default(),
+            invariant_checks: false,
+            program_id: ProgramIdSource::Auto,
+            clients: Clients::default(),
+            lints: BTreeMap::new(),
+        }
+    }
+}
+
+/// Read `solx.toml` from the project at `path`, or the defaults if there is
+/// none.
+pub fn load(path: &Path) -> Result<Manifest> {
+    let file = path.join(MANIFEST_FILE);
+    if !file.exists() {
+        return Ok(Manifest::default());
+    }
+    let source =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    parse(&source).with_context(|| format!("Invalid {}", file.display()))
+}
+
+pub fn parse(source: &str) -> Result<Manifest> {
+    let doc = source.parse::<DocumentMut>()?;
+    let mut manifest = Manifest::default();
+
+    check_keys(
+        doc.as_table(),
+        "",
+        &["program", "anchor", "build", "clients", "lints"],
+    )?;
+
+    if let Some(program) = table(&doc, "program")? {
+        check_keys(program, "[program] ", &["dir", "entry", "target"])?;
+        manifest.dir = string(program, "program", "dir")?.map(PathBuf::from);
+        manifest.entry = string(program, "program", "entry")?.map(PathBuf::from);
+        if let Some(target) = string(program, "program", "target")? {
+            let registry = BackendRegistry::default();
+            if registry.get(&target).is_none() {
+                let names: Vec<&str> = registry.backends().map(|b| b.name()).collect();
+                anyhow::bail!(
+                    "[program] target must be one of {} (found \"{}\")",
+                    names.join(", "),
+                    target
+                );
+            }
+            manifest.target = target;
+        }
+    }
+
+    if let Some(anchor) = table(&doc, "anchor")? {
+        check_keys(anchor, "[anchor] ", &["version"])?;
+        if let Some(version) = string(anchor, "anchor", "version")? {
+            let valid = version.split('.').count() == 3
+                && version.split('.').all(|n| n.parse::<u32>().is_ok());
+            if !valid {
+                anyhow::bail!(
+                    "[anchor] version must be a version like \"{}\" (found \"{}\")",
+                    ANCHOR_VERSION,
+                    version
+                );
+            }
+            manifest.anchor_version = Some(version);
+        }
+    }
+
+    if let Some(build) = table(&doc, "build")? {
+        check_keys(
+            build,
+            "[build] ",
+            &["arithmetic", "program-id", "invariant-checks"],
+        )?;
+        if let Some(arithmetic) = string(build, "build", "arithmetic")? {
+            manifest.arithmetic = Arithmetic::from_name(&arithmetic).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "[build] arithmetic must be one of {} (found \"{}\")",
+                    Arithmetic::NAMES.join(", "),
+                    arithmetic
+                )
+            })?;
+        }
+        if let Some(source) = string(build, "build", "program-id")? {
+            manifest.program_id = match source.as_str() {
+                "auto" => ProgramIdSource::Auto,
+                "anchor-toml" => ProgramIdSource::AnchorToml,
+                "keypair" => ProgramIdSource::Keypair,
+                "declare-id" => ProgramIdSource::DeclareId,
+                id if is_pubkey(id) => ProgramIdSource::Fixed(id.to_string()),
+                _ => anyhow::bail!(
+                    "[build] program-id must be auto, anchor-toml, keypair, declare-id \
+                     or a Base58 public key (found \"{}\")",
+                    source
+                ),
+            };
+        }
+        if let Some(checks) = boolean(build, "build", "invariant-checks")? {
+            manifest.invariant_checks = checks;
+        }
+    }
+
+    if let Some(clients) = table(&doc, "clients")? {
+        check_keys(clients, "[clients] ", &["idl", "ts", "rust"])?;
+        manifest.clients = Clients {
+            idl: string(clients, "clients", "idl")?.map(PathBuf::from),
+            ts: string(clients, "clients", "ts")?.map(PathBuf::from),
+            rust: string(clients, "clients", "rust")?.map(PathBuf::from),
+        };
+    }
+
+    if let Some(lints) = table(&doc, "lints")? {
+        for (name, _) in lints.iter() {
+            if lints::find(name).is_none() {
+                let known: Vec<&str> = lints::LINTS.iter().map(|l| l.name).collect();
+                anyhow::bail!(
+                    "unknown lint [lints] {} (expected one of {})",
+                    name,
+                    known.join(", ")
+                );
+            }
+            let value = string(lints, "lints", name)?;
+            let level = value.as_deref().and_then(Level::from_name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "[lints] {} must be allow, warn or deny (found \"{}\")",
+                    name,
+                    value.unwrap_or_default()
+                )
+            })?;
+            manifest.lints.insert(name.to_string(), level);
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// A manifest for a new project whose program crate is `dir`, listing the
+/// defaults.
+pub fn template(program: &str, dir: &str) -> String {
+    format!(
+        r#"[program]
+dir = "{}"
+entry = "{}/src/program.solx"
+target = "anchor"
+
+[anchor]
+version = "{}"
+
+[build]
+arithmetic = "checked"
+program-id = "auto"
+invariant-checks = false
+
+[clients]
+idl = "target/idl/{}.json"
+ts = "clients/ts/{}.ts"
+
+[lints]
+"#,
+        dir, dir, ANCHOR_VERSION, program, program
+    )
+}
+
+impl Manifest {
+    /// The program crate: `[program] dir`, or the project directory. This is
+    /// where `src/generated.rs` and `src/lib.rs` are written.
+    pub fn crate_dir(&self, path: &Path) -> PathBuf {
+        match &self.dir {
+            Some(dir) => path.join(dir),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// The root source file: `[program] entry`, or `program.solx` in the
+    /// program crate's `src/` or at its root.
+    pub fn entry_file(&self, path: &Path) -> Result<PathBuf> {
+        if let Some(entry) = &self.entry {
+            let file = path.join(entry);
+            if !file.exists() {
+                anyhow::bail!(
+                    "{}: [program] entry {} does not exist",
+                    MANIFEST_FILE,
+                    file.display()
+                );
+            }
+            return Ok(file);
+        }
+
+        let crate_dir = self.crate_dir(path);
+        let src_dir = crate_dir.join("src");
+        let solx_in_src = src_dir.join("program.solx");
+        let solx_in_root = crate_dir.join("program.solx");
+        if solx_in_src.exists() {
+            Ok(solx_in_src)
+        } else if solx_in_root.exists() {
+            Ok(solx_in_root)
+        } else {
+            anyhow::bail!(
+                "No program.solx found in {} or {}; set [program] entry in {}",
+                src_dir.display(),
+                crate_dir.display(),
+                MANIFEST_FILE
+            );
+        }
+    }
+}
+
+fn table<'a>(doc: &'a DocumentMut, name: &str) -> Result<Option<&'a Table>> {
+    match doc.get(name) {
+        None => Ok(None),
+        Some(Item::Table(table)) => Ok(Some(table)),
+        Some(_) => anyhow::bail!("{} must be a table, e.g. [{}]", name, name),
+    }
+}
+
+fn string(table: &Table, section: &str, key: &str) -> Result<Option<String>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(item) => match item.as_str() {
+            Some(value) => Ok(Some(value.to_string())),
+            None => anyhow::bail!("[{}] {} must be a string", section, key),
+        },
+    }
+}
+
+fn boolean(table: &Table, section: &str, key: &str) -> Result<Option<bool>> {
+    match table.get(key) {
+        None => Ok(None),
+        Some(item) => match item.as_bool() {
+            Some(value) => Ok(Some(value)),
+            None => anyhow::bail!("[{}] {} must be true or false", section, key),
+        },
+    }
+}
+
+fn check_keys(table: &Table, section: &str, known: &[&str]) -> Result<()> {
+    for (key, _) in table.iter() {
+        if !known.contains(&key) {
+            anyhow::bail!(
+                "unknown key {}{} (expected one of {})",
+                section,
+                key,
+                known.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn is_pubkey(id: &str) -> bool {
+    bs58::decode(id)
+        .into_vec()
+        .is_ok_and(|bytes| bytes.len() == 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse(&template("counter", "programs/counter")).unwrap();
+        assert_eq!(
+            manifest.crate_dir(Path::new(".")),
+            Path::new("./programs/counter")
+        );
+        assert_eq!(
+            manifest.entry,
+            Some(PathBuf::from("programs/counter/src/program.solx"))
+        );
+        assert_eq!(manifest.arithmetic, Arithmetic::Checked);
+        assert_eq!(manifest.program_id, ProgramIdSource::Auto);
+        assert!(!manifest.invariant_checks);
+        assert_eq!(
+            manifest.clients.ts,
+            Some(PathBuf::from("clients/ts/counter.ts"))
+        );
+
+        let manifest = parse(
+            "[build]\nprogram-id = \"11111111111111111111111111111111\"\n\n[lints]\nmissing-close = \"deny\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.program_id,
+            ProgramIdSource::Fixed("11111111111111111111111111111111".to_string())
+        );
+        assert_eq!(manifest.lints["missing-close"], Level::Deny);
+
+        let err = |source: &str| parse(source).err().unwrap().to_string();
+        assert_eq!(
+            err("[build]\narithmetic = \"saturating\"\n"),
+            "[build] arithmetic must be one of panic, checked, wrapping (found \"saturating\")"
+        );
+        assert_eq!(
+            err("[program]\nentry = \"a.solx\"\nname = \"x\"\n"),
+            "unknown key [program] name (expected one of dir, entry, target)"
+        );
+        assert_eq!(
+            err("[build]\ninvariant-checks = \"yes\"\n"),
+            "[build] invariant-checks must be true or false"
+        );
+        assert!(err("[lints]\nunused-param = \"deny\"\n")
+            .starts_with("unknown lint [lints] unused-param (expected one of missing-signer, "));
+        assert_eq!(
+            err("[lints]\nmissing-close = \"forbid\"\n"),
+            "[lints] missing-close must be allow, warn or deny (found \"forbid\")"
+        );
+        assert!(err("[program]\ntarget = \"evm\"\n").starts_with("[program] target must be one of"));
+    }
+}
@@ -0,0 +1,309 @@
+//! Project templates for `solx new` and `solx init`.
+//!
+//! A new project is a complete Anchor workspace: `Anchor.toml`, a Cargo
+//! workspace over `programs/*`, the program crate in `programs/<name>` with
+//! its SOL-X source, and a TypeScript test run by `anchor test`. Template
+//! sources live in `templates/`, with `{{Program}}` and `{{program}}`
+//! standing for the PascalCase and snake_case program name.
+
+use clap::ValueEnum;
+use std::path::PathBuf;
+
+use solx_hir::naming::{to_pascal_case, to_snake_case};
+
+use crate::keys::DEFAULT_PROGRAM_ID;
+use crate::manifest::{self, ANCHOR_VERSION, MANIFEST_FILE};
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Template {
+    /// A counter with an authority
+    Counter,
+    /// An escrow offer between a maker and a taker
+    Escrow,
+    /// A per-mint PDA vault recording token deposits
+    TokenVault,
+    /// A single account and `initialize` instruction
+    Blank,
+}
+
+impl Template {
+    /// The SOL-X source and TypeScript test.
+    fn sources(self) -> (&'static str, &'static str) {
+        match self {
+            Template::Counter => (
+                include_str!("../templates/counter.solx"),
+                include_str!("../templates/counter.ts"),
+            ),
+            Template::Escrow => (
+                include_str!("../templates/escrow.solx"),
+                include_str!("../templates/escrow.ts"),
+            ),
+            Template::TokenVault => (
+                include_str!("../templates/token_vault.solx"),
+                include_str!("../templates/token_vault.ts"),
+            ),
+            Template::Blank => (
+                include_str!("../templates/blank.solx"),
+                include_str!("../templates/blank.ts"),
+            ),
+        }
+    }
+
+    /// Whether the program uses token accounts, and so depends on
+    /// `anchor-spl` and `@solana/spl-token`.
+    pub fn uses_tokens(self) -> bool {
+        self == Template::TokenVault
+    }
+
+    pub fn program(self, name: &str) -> String {
+        fill(self.sources().0, name)
+    }
+
+    pub fn test(self, name: &str) -> String {
+        fill(self.sources().1, name)
+    }
+}
+
+fn fill(source: &str, name: &str) -> String {
+    source
+        .replace("{{Program}}", &to_pascal_case(name))
+        .replace("{{program}}", &to_snake_case(name))
+}
+
+/// `programs/<name>`, the program crate of a workspace.
+pub fn crate_dir(name: &str) -> String {
+    format!("programs/{}", to_snake_case(name))
+}
+
+/// Every file of a new workspace for program `name`, relative to the
+/// workspace directory.
+pub fn workspace(name: &str, template: Template) -> Vec<(PathBuf, String)> {
+    let program = to_snake_case(name);
+    let dir = crate_dir(name);
+    let mut files = vec![
+        (PathBuf::from("Anchor.toml"), anchor_toml(&program)),
+        (
+            PathBuf::from("Cargo.toml"),
+            WORKSPACE_CARGO_TOML.to_string(),
+        ),
+        (PathBuf::from("package.json"), package_json(template)),
+        (PathBuf::from("tsconfig.json"), TSCONFIG_JSON.to_string()),
+        (PathBuf::from(".gitignore"), GITIGNORE.to_string()),
+        (
+            PathBuf::from(MANIFEST_FILE),
+            manifest::template(&program, &dir),
+        ),
+        (
+            PathBuf::from("tests").join(format!("{}.ts", program)),
+            template.test(name),
+        ),
+    ];
+    for (path, contents) in program_crate(name, template) {
+        files.push((PathBuf::from(&dir).join(path), contents));
+    }
+    files
+}
+
+/// The files of the program crate, relative to its directory.
+pub fn program_crate(name: &str, template: Template) -> Vec<(PathBuf, String)> {
+    vec![
+        (
+            PathBuf::from("Cargo.toml"),
+            program_cargo_toml(name, template),
+        ),
+        (PathBuf::from("Xargo.toml"), XARGO_TOML.to_string()),
+        (
+            PathBuf::from("src").join("program.solx"),
+            template.program(name),
+        ),
+    ]
+}
+
+fn anchor_toml(program: &str) -> String {
+    format!(
+        r#"[toolchain]
+
+[features]
+resolution = true
+skip-lint = false
+
+[programs.localnet]
+{} = "{}"
+
+[registry]
+url = "https://api.apr.dev"
+
+[provider]
+cluster = "Localnet"
+wallet = "~/.config/solana/id.json"
+
+[scripts]
+test = "yarn run ts-mocha -p ./tsconfig.json -t 1000000 tests/**/*.ts"
+"#,
+        program, DEFAULT_PROGRAM_ID
+    )
+}
+
+fn program_cargo_toml(name: &str, template: Template) -> String {
+    let program = to_snake_case(name);
+    let (spl_feature, spl_dependency) = if template.uses_tokens() {
+        (
+            ", \"anchor-spl/idl-build\"",
+            format!("anchor-spl = \"{}\"\n", ANCHOR_VERSION),
+        )
+    } else {
+        ("", String::new())
+    };
+    format!(
+        r#"[package]
+name = "{}"
+version = "0.1.0"
+description = "Created with SOL-X"
+edition = "2021"
+
+[lib]
+crate-type = ["cdylib", "lib"]
+name = "{}"
+
+[features]
+default = []
+cpi = ["no-entrypoint"]
+no-entrypoint = []
+no-idl = []
+no-log-ix-name = []
+idl-build = ["anchor-lang/idl-build"{}]
+
+[dependencies]
+anchor-lang = "{}"
+{}"#,
+        program, program, spl_feature, ANCHOR_VERSION, spl_dependency
+    )
+}
+
+fn package_json(template: Template) -> String {
+    let spl = if template.uses_tokens() {
+        ",\n    \"@solana/spl-token\": \"^0.4.8\""
+    } else {
+        ""
+    };
+    format!(
+        r#"{{
+  "license": "ISC",
+  "scripts": {{
+    "test": "anchor test"
+  }},
+  "dependencies": {{
+    "@coral-xyz/anchor": "^{}"{}
+  }},
+  "devDependencies": {{
+    "@types/bn.js": "^5.1.0",
+    "@types/chai": "^4.3.0",
+    "@types/mocha": "^9.0.0",
+    "chai": "^4.3.4",
+    "mocha": "^9.0.3",
+    "ts-mocha": "^10.0.0",
+    "typescript": "^4.3.5"
+  }}
+}}
+"#,
+        ANCHOR_VERSION, spl
+    )
+}
+
+const WORKSPACE_CARGO_TOML: &str = r#"[workspace]
+members = ["programs/*"]
+resolver = "2"
+
+[profile.release]
+overflow-checks = true
+lto = "fat"
+codegen-units = 1
+
+[profile.release.build-override]
+opt-level = 3
+incremental = false
+codegen-units = 1
+"#;
+
+const XARGO_TOML: &str = r#"[target.bpfel-unknown-unknown.dependencies.std]
+features = []
+"#;
+
+const TSCONFIG_JSON: &str = r#"{
+  "compilerOptions": {
+    "types": ["mocha", "chai"],
+    "typeRoots": ["./node_modules/@types"],
+    "lib": ["es2015"],
+    "module": "commonjs",
+    "target": "es6",
+    "esModuleInterop": true
+  }
+}
+"#;
+
+const GITIGNORE: &str = r#".anchor
+.DS_Store
+target
+**/*.rs.bk
+node_modules
+test-ledger
+.yarn
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solx_codegen::{BackendOptions, BackendRegistry};
+
+    #[test]
+    fn test_templates_compile_and_pass_their_tests() {
+        for template in Template::value_variants() {
+            let source = template.program("my-vault");
+            assert!(source.starts_with("program MyVault\n"));
+            let hir = solx_hir::Hir::from_ast(solx_parser::parse(&source).unwrap()).unwrap();
+            let uses_tokens = hir
+                .program
+                .instructions
+                .iter()
+                .flat_map(|i| &i.params)
+                .any(|p| p.ty == solx_ast::Type::TokenAccount);
+            assert_eq!(uses_tokens, template.uses_tokens());
+            for test in &hir.program.tests {
+                solx_interp::runner::run_test(&hir, test)
+                    .unwrap_or_else(|e| panic!("test {}: {:#}", test.name, e));
+            }
+            assert!(template.test("my-vault").contains("Program<MyVault>"));
+
+            // Both backends produce Rust that parses
+            let options = BackendOptions {
+                program_id: DEFAULT_PROGRAM_ID.to_string(),
+            };
+            for backend in BackendRegistry::default().backends() {
+                for file in backend.generate(&hir, &options).unwrap() {
+                    syn::parse_file(&file.contents).unwrap_or_else(|e| {
+                        panic!("{} {}: {}", backend.name(), file.path.display(), e)
+                    });
+                }
+            }
+        }
+
+        let files = workspace("my-vault", Template::Counter);
+        let has = |path: PathBuf| files.iter().any(|(p, _)| *p == path);
+        assert!(has(PathBuf::from("tests/my_vault.ts")));
+        assert!(has(PathBuf::from("programs/my_vault/src/program.solx")));
+    }
+
+    #[test]
+    fn test_templates_pass_the_lints() {
+        for template in Template::value_variants() {
+            let source = template.program("my-vault");
+            // With the arithmetic the template's solx.toml sets
+            let hir = solx_hir::Hir::from_ast(solx_parser::parse(&source).unwrap())
+                .unwrap()
+                .with_arithmetic(solx_hir::Arithmetic::Checked);
+            let diagnostics: Vec<String> =
+                hir.lints().iter().map(|d| d.render(&hir.program)).collect();
+            assert!(diagnostics.is_empty(), "{}", diagnostics.join("\n"));
+        }
+    }
+}
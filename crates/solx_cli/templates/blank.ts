@@ -0,0 +1,21 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { assert } from "chai";
+import { {{Program}} } from "../target/types/{{program}}";
+
+describe("{{program}}", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.{{Program}} as Program<{{Program}}>;
+
+  it("initializes", async () => {
+    const authority = program.provider.publicKey!;
+    const state = anchor.web3.Keypair.generate();
+    await program.methods
+      .initialize()
+      .accountsPartial({ authority, state: state.publicKey })
+      .signers([state])
+      .rpc();
+    const account = await program.account.state.fetch(state.publicKey);
+    assert.ok(account.authority.equals(authority));
+  });
+});
@@ -0,0 +1,29 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { assert } from "chai";
+import { {{Program}} } from "../target/types/{{program}}";
+
+describe("{{program}}", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.{{Program}} as Program<{{Program}}>;
+  const authority = program.provider.publicKey!;
+  const state = anchor.web3.Keypair.generate();
+
+  it("initializes", async () => {
+    await program.methods
+      .initialize()
+      .accountsPartial({ authority, state: state.publicKey })
+      .signers([state])
+      .rpc();
+    const account = await program.account.counterState.fetch(state.publicKey);
+    assert.equal(account.count.toNumber(), 0);
+  });
+
+  it("increments and decrements", async () => {
+    await program.methods.increment().accountsPartial({ authority, state: state.publicKey }).rpc();
+    await program.methods.increment().accountsPartial({ authority, state: state.publicKey }).rpc();
+    await program.methods.decrement().accountsPartial({ authority, state: state.publicKey }).rpc();
+    const account = await program.account.counterState.fetch(state.publicKey);
+    assert.equal(account.count.toNumber(), 1);
+  });
+});
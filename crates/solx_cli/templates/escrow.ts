@@ -0,0 +1,30 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { assert } from "chai";
+import { {{Program}} } from "../target/types/{{program}}";
+
+describe("{{program}}", () => {
+  anchor.setProvider(anchor.AnchorProvider.env());
+  const program = anchor.workspace.{{Program}} as Program<{{Program}}>;
+  const maker = program.provider.publicKey!;
+  const taker = anchor.web3.Keypair.generate();
+  const mint = anchor.web3.Keypair.generate().publicKey;
+  const escrow = anchor.web3.Keypair.generate();
+
+  it("opens an escrow", async () => {
+    await program.methods
+      .initialize(taker.publicKey, new anchor.BN(100), mint)
+      .accountsPartial({ maker, escrow: escrow.publicKey })
+      .signers([escrow])
+      .rpc();
+    const account = await program.account.escrowState.fetch(escrow.publicKey);
+    assert.ok(account.taker.equals(taker.publicKey));
+    assert.isTrue(account.initialized);
+  });
+
+  it("lets the maker cancel", async () => {
+    await program.methods.cancel().accountsPartial({ maker, escrow: escrow.publicKey }).rpc();
+    const account = await program.account.escrowState.fetchNullable(escrow.publicKey);
+    assert.isNull(account);
+  });
+});
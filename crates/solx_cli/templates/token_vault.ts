@@ -0,0 +1,40 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { createMint, getOrCreateAssociatedTokenAccount } from "@solana/spl-token";
+import { assert } from "chai";
+import { {{Program}} } from "../target/types/{{program}}";
+
+describe("{{program}}", () => {
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
+  const program = anchor.workspace.{{Program}} as Program<{{Program}}>;
+  const payer = (provider.wallet as anchor.Wallet).payer;
+  const user = provider.publicKey;
+  let mint: anchor.web3.PublicKey;
+
+  const vaultAddress = () =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("vault"), mint.toBuffer()],
+      program.programId
+    )[0];
+
+  before(async () => {
+    mint = await createMint(provider.connection, payer, user, null, 6);
+  });
+
+  it("initializes a vault for the mint", async () => {
+    await program.methods.initialize().accountsPartial({ authority: user, mint }).rpc();
+    const vault = await program.account.vault.fetch(vaultAddress());
+    assert.ok(vault.mint.equals(mint));
+  });
+
+  it("records deposits", async () => {
+    const userAta = await getOrCreateAssociatedTokenAccount(provider.connection, payer, mint, user);
+    await program.methods
+      .deposit(new anchor.BN(25))
+      .accountsPartial({ user, mint, userAta: userAta.address })
+      .rpc();
+    const vault = await program.account.vault.fetch(vaultAddress());
+    assert.equal(vault.total.toNumber(), 25);
+  });
+});
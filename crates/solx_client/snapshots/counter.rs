@@ -0,0 +1,150 @@
+//! Client for the Counter program. Generated by SOL-X; do not edit.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = solana_program::pubkey!("11111111111111111111111111111111");
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct CounterState {
+    pub authority: Pubkey,
+    pub count: u64,
+}
+
+impl CounterState {
+    pub const DISCRIMINATOR: [u8; 8] = [98, 23, 205, 159, 14, 202, 79, 139];
+
+    /// Decode account data, checking the discriminator. Trailing bytes are ignored.
+    pub fn decode(data: &[u8]) -> std::io::Result<Self> {
+        if data.len() < 8 || data[..8] != Self::DISCRIMINATOR {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "account is not a CounterState",
+            ));
+        }
+        Self::deserialize(&mut &data[8..])
+    }
+}
+
+/// Accounts for the `initialize` instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InitializeAccounts {
+    pub authority: Pubkey,
+    pub state: Pubkey,
+}
+
+impl InitializeAccounts {
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.authority, true),
+            AccountMeta::new(self.state, true),
+            AccountMeta::new_readonly(solana_program::pubkey!("11111111111111111111111111111111"), false),
+        ]
+    }
+}
+
+/// Instruction data for `initialize`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InitializeArgs;
+
+impl InitializeArgs {
+    pub const DISCRIMINATOR: [u8; 8] = [175, 175, 109, 31, 13, 152, 155, 237];
+
+    /// The discriminator followed by the Borsh encoded arguments.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+pub fn initialize(accounts: &InitializeAccounts, args: &InitializeArgs) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(),
+        data: args.data(),
+    }
+}
+
+/// Accounts for the `increment` instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IncrementAccounts {
+    pub authority: Pubkey,
+    pub state: Pubkey,
+}
+
+impl IncrementAccounts {
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.authority, true),
+            AccountMeta::new(self.state, false),
+        ]
+    }
+}
+
+/// Instruction data for `increment`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct IncrementArgs;
+
+impl IncrementArgs {
+    pub const DISCRIMINATOR: [u8; 8] = [11, 18, 104, 9, 104, 174, 59, 33];
+
+    /// The discriminator followed by the Borsh encoded arguments.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+pub fn increment(accounts: &IncrementAccounts, args: &IncrementArgs) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(),
+        data: args.data(),
+    }
+}
+
+/// Accounts for the `decrement` instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecrementAccounts {
+    pub authority: Pubkey,
+    pub state: Pubkey,
+}
+
+impl DecrementAccounts {
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.authority, true),
+            AccountMeta::new(self.state, false),
+        ]
+    }
+}
+
+/// Instruction data for `decrement`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct DecrementArgs;
+
+impl DecrementArgs {
+    pub const DISCRIMINATOR: [u8; 8] = [106, 227, 168, 59, 248, 27, 150, 101];
+
+    /// The discriminator followed by the Borsh encoded arguments.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+pub fn decrement(accounts: &DecrementAccounts, args: &DecrementArgs) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(),
+        data: args.data(),
+    }
+}
+
+/// Custom error codes as `(code, name, message)`.
+pub const ERRORS: &[(u32, &str, &str)] = &[
+];
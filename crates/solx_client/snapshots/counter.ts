@@ -0,0 +1,91 @@
+// Generated by SOL-X from program Counter. Do not edit.
+
+import { AccountMeta, PublicKey, TransactionInstruction } from "@solana/web3.js";
+import * as borsh from "@coral-xyz/borsh";
+import BN from "bn.js";
+
+export const PROGRAM_ID = new PublicKey("11111111111111111111111111111111");
+
+/** Maximum instruction data size that fits in a transaction */
+const MAX_IX_DATA = 1232;
+
+export interface CounterState {
+  authority: PublicKey;
+  count: BN;
+}
+
+export const COUNTER_STATE_DISCRIMINATOR = Buffer.from([98, 23, 205, 159, 14, 202, 79, 139]);
+
+export const counterStateLayout = borsh.struct([borsh.publicKey("authority"), borsh.u64("count")]);
+
+export function decodeCounterState(data: Buffer): CounterState {
+  if (!data.subarray(0, 8).equals(COUNTER_STATE_DISCRIMINATOR)) {
+    throw new Error("Account is not a CounterState");
+  }
+  return counterStateLayout.decode(data.subarray(8)) as CounterState;
+}
+
+export const INITIALIZE_DISCRIMINATOR = Buffer.from([175, 175, 109, 31, 13, 152, 155, 237]);
+
+export interface InitializeAccounts {
+  authority: PublicKey;
+  state: PublicKey;
+}
+
+export function createInitializeInstruction(
+  accounts: InitializeAccounts,
+  programId: PublicKey = PROGRAM_ID,
+): TransactionInstruction {
+  const data = INITIALIZE_DISCRIMINATOR;
+  const keys: AccountMeta[] = [
+    { pubkey: accounts.authority, isSigner: true, isWritable: true },
+    { pubkey: accounts.state, isSigner: true, isWritable: true },
+    { pubkey: new PublicKey("11111111111111111111111111111111"), isSigner: false, isWritable: false },
+  ];
+  return new TransactionInstruction({ keys, programId, data });
+}
+
+export const INCREMENT_DISCRIMINATOR = Buffer.from([11, 18, 104, 9, 104, 174, 59, 33]);
+
+export interface IncrementAccounts {
+  authority: PublicKey;
+  state: PublicKey;
+}
+
+export function createIncrementInstruction(
+  accounts: IncrementAccounts,
+  programId: PublicKey = PROGRAM_ID,
+): TransactionInstruction {
+  const data = INCREMENT_DISCRIMINATOR;
+  const keys: AccountMeta[] = [
+    { pubkey: accounts.authority, isSigner: true, isWritable: false },
+    { pubkey: accounts.state, isSigner: false, isWritable: true },
+  ];
+  return new TransactionInstruction({ keys, programId, data });
+}
+
+export const DECREMENT_DISCRIMINATOR = Buffer.from([106, 227, 168, 59, 248, 27, 150, 101]);
+
+export interface DecrementAccounts {
+  authority: PublicKey;
+  state: PublicKey;
+}
+
+export function createDecrementInstruction(
+  accounts: DecrementAccounts,
+  programId: PublicKey = PROGRAM_ID,
+): TransactionInstruction {
+  const data = DECREMENT_DISCRIMINATOR;
+  const keys: AccountMeta[] = [
+    { pubkey: accounts.authority, isSigner: true, isWritable: false },
+    { pubkey: accounts.state, isSigner: false, isWritable: true },
+  ];
+  return new TransactionInstruction({ keys, programId, data });
+}
+
+export const ERRORS: Record<number, { name: string; msg: string }> = {
+};
+
+export function errorFromCode(code: number): { name: string; msg: string } | undefined {
+  return ERRORS[code];
+}
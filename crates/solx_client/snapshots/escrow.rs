@@ -0,0 +1,157 @@
+//! Client for the Escrow program. Generated by SOL-X; do not edit.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = solana_program::pubkey!("11111111111111111111111111111111");
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct EscrowState {
+    pub maker: Pubkey,
+    pub taker: Pubkey,
+    pub amount: u64,
+    pub mint: Pubkey,
+    pub initialized: bool,
+}
+
+impl EscrowState {
+    pub const DISCRIMINATOR: [u8; 8] = [19, 90, 148, 111, 55, 130, 229, 108];
+
+    /// Decode account data, checking the discriminator. Trailing bytes are ignored.
+    pub fn decode(data: &[u8]) -> std::io::Result<Self> {
+        if data.len() < 8 || data[..8] != Self::DISCRIMINATOR {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "account is not a EscrowState",
+            ));
+        }
+        Self::deserialize(&mut &data[8..])
+    }
+}
+
+/// Accounts for the `initialize` instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InitializeAccounts {
+    pub maker: Pubkey,
+    pub escrow: Pubkey,
+}
+
+impl InitializeAccounts {
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.maker, true),
+            AccountMeta::new(self.escrow, true),
+            AccountMeta::new_readonly(solana_program::pubkey!("11111111111111111111111111111111"), false),
+        ]
+    }
+}
+
+/// Instruction data for `initialize`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InitializeArgs {
+    pub taker: Pubkey,
+    pub amount: u64,
+    pub mint: Pubkey,
+}
+
+impl InitializeArgs {
+    pub const DISCRIMINATOR: [u8; 8] = [175, 175, 109, 31, 13, 152, 155, 237];
+
+    /// The discriminator followed by the Borsh encoded arguments.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+pub fn initialize(accounts: &InitializeAccounts, args: &InitializeArgs) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(),
+        data: args.data(),
+    }
+}
+
+/// Accounts for the `cancel` instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CancelAccounts {
+    pub maker: Pubkey,
+    pub escrow: Pubkey,
+}
+
+impl CancelAccounts {
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.maker, true),
+            AccountMeta::new(self.escrow, false),
+        ]
+    }
+}
+
+/// Instruction data for `cancel`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct CancelArgs;
+
+impl CancelArgs {
+    pub const DISCRIMINATOR: [u8; 8] = [232, 219, 223, 41, 219, 236, 220, 190];
+
+    /// The discriminator followed by the Borsh encoded arguments.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+pub fn cancel(accounts: &CancelAccounts, args: &CancelArgs) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(),
+        data: args.data(),
+    }
+}
+
+/// Accounts for the `complete` instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompleteAccounts {
+    pub taker: Pubkey,
+    pub escrow: Pubkey,
+}
+
+impl CompleteAccounts {
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new_readonly(self.taker, true),
+            AccountMeta::new(self.escrow, false),
+        ]
+    }
+}
+
+/// Instruction data for `complete`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct CompleteArgs;
+
+impl CompleteArgs {
+    pub const DISCRIMINATOR: [u8; 8] = [0, 77, 224, 147, 136, 25, 88, 76];
+
+    /// The discriminator followed by the Borsh encoded arguments.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+pub fn complete(accounts: &CompleteAccounts, args: &CompleteArgs) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(),
+        data: args.data(),
+    }
+}
+
+/// Custom error codes as `(code, name, message)`.
+pub const ERRORS: &[(u32, &str, &str)] = &[
+];
@@ -0,0 +1,105 @@
+// Generated by SOL-X from program Escrow. Do not edit.
+
+import { AccountMeta, PublicKey, TransactionInstruction } from "@solana/web3.js";
+import * as borsh from "@coral-xyz/borsh";
+import BN from "bn.js";
+
+export const PROGRAM_ID = new PublicKey("11111111111111111111111111111111");
+
+/** Maximum instruction data size that fits in a transaction */
+const MAX_IX_DATA = 1232;
+
+export interface EscrowState {
+  maker: PublicKey;
+  taker: PublicKey;
+  amount: BN;
+  mint: PublicKey;
+  initialized: boolean;
+}
+
+export const ESCROW_STATE_DISCRIMINATOR = Buffer.from([19, 90, 148, 111, 55, 130, 229, 108]);
+
+export const escrowStateLayout = borsh.struct([borsh.publicKey("maker"), borsh.publicKey("taker"), borsh.u64("amount"), borsh.publicKey("mint"), borsh.bool("initialized")]);
+
+export function decodeEscrowState(data: Buffer): EscrowState {
+  if (!data.subarray(0, 8).equals(ESCROW_STATE_DISCRIMINATOR)) {
+    throw new Error("Account is not a EscrowState");
+  }
+  return escrowStateLayout.decode(data.subarray(8)) as EscrowState;
+}
+
+export const INITIALIZE_DISCRIMINATOR = Buffer.from([175, 175, 109, 31, 13, 152, 155, 237]);
+
+export interface InitializeAccounts {
+  maker: PublicKey;
+  escrow: PublicKey;
+}
+
+export interface InitializeArgs {
+  taker: PublicKey;
+  amount: BN;
+  mint: PublicKey;
+}
+
+const initializeArgsLayout = borsh.struct([borsh.publicKey("taker"), borsh.u64("amount"), borsh.publicKey("mint")]);
+
+export function createInitializeInstruction(
+  accounts: InitializeAccounts,
+  args: InitializeArgs,
+  programId: PublicKey = PROGRAM_ID,
+): TransactionInstruction {
+  const buffer = Buffer.alloc(MAX_IX_DATA);
+  const len = initializeArgsLayout.encode(args, buffer);
+  const data = Buffer.concat([INITIALIZE_DISCRIMINATOR, buffer.subarray(0, len)]);
+  const keys: AccountMeta[] = [
+    { pubkey: accounts.maker, isSigner: true, isWritable: true },
+    { pubkey: accounts.escrow, isSigner: true, isWritable: true },
+    { pubkey: new PublicKey("11111111111111111111111111111111"), isSigner: false, isWritable: false },
+  ];
+  return new TransactionInstruction({ keys, programId, data });
+}
+
+export const CANCEL_DISCRIMINATOR = Buffer.from([232, 219, 223, 41, 219, 236, 220, 190]);
+
+export interface CancelAccounts {
+  maker: PublicKey;
+  escrow: PublicKey;
+}
+
+export function createCancelInstruction(
+  accounts: CancelAccounts,
+  programId: PublicKey = PROGRAM_ID,
+): TransactionInstruction {
+  const data = CANCEL_DISCRIMINATOR;
+  const keys: AccountMeta[] = [
+    { pubkey: accounts.maker, isSigner: true, isWritable: false },
+    { pubkey: accounts.escrow, isSigner: false, isWritable: true },
+  ];
+  return new TransactionInstruction({ keys, programId, data });
+}
+
+export const COMPLETE_DISCRIMINATOR = Buffer.from([0, 77, 224, 147, 136, 25, 88, 76]);
+
+export interface CompleteAccounts {
+  taker: PublicKey;
+  escrow: PublicKey;
+}
+
+export function createCompleteInstruction(
+  accounts: CompleteAccounts,
+  programId: PublicKey = PROGRAM_ID,
+): TransactionInstruction {
+  const data = COMPLETE_DISCRIMINATOR;
+  const keys: AccountMeta[] = [
+    { pubkey: accounts.taker, isSigner: true, isWritable: false },
+    { pubkey: accounts.escrow, isSigner: false, isWritable: true },
+  ];
+  return new TransactionInstruction({ keys, programId, data });
+}
+
+export const ERRORS: Record<number, { name: string; msg: string }> = {
+};
+
+export function errorFromCode(code: number): { name: string; msg: string } | undefined {
+  return ERRORS[code];
+}
@@ -0,0 +1,83 @@
+//! Client for the Types program. Generated by SOL-X; do not edit.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+
+pub const ID: Pubkey = solana_program::pubkey!("11111111111111111111111111111111");
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct Limit {
+    pub amount: u64,
+    pub until: Option<i64>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct Schedule {
+    pub authority: Pubkey,
+    pub payouts: Vec<Option<u64>>,
+    pub limits: Vec<Limit>,
+    pub memo: Option<Vec<u8>>,
+}
+
+impl Schedule {
+    pub const DISCRIMINATOR: [u8; 8] = [217, 243, 116, 56, 73, 82, 207, 51];
+
+    /// Decode account data, checking the discriminator. Trailing bytes are ignored.
+    pub fn decode(data: &[u8]) -> std::io::Result<Self> {
+        if data.len() < 8 || data[..8] != Self::DISCRIMINATOR {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "account is not a Schedule",
+            ));
+        }
+        Self::deserialize(&mut &data[8..])
+    }
+}
+
+/// Accounts for the `configure` instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConfigureAccounts {
+    pub authority: Pubkey,
+    pub schedule: Pubkey,
+}
+
+impl ConfigureAccounts {
+    pub fn to_account_metas(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.authority, true),
+            AccountMeta::new(self.schedule, true),
+            AccountMeta::new_readonly(solana_program::pubkey!("11111111111111111111111111111111"), false),
+        ]
+    }
+}
+
+/// Instruction data for `configure`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ConfigureArgs {
+    pub payouts: Vec<Option<u64>>,
+    pub limit: Option<Limit>,
+}
+
+impl ConfigureArgs {
+    pub const DISCRIMINATOR: [u8; 8] = [245, 7, 108, 117, 95, 196, 54, 217];
+
+    /// The discriminator followed by the Borsh encoded arguments.
+    pub fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        self.serialize(&mut data).unwrap();
+        data
+    }
+}
+
+pub fn configure(accounts: &ConfigureAccounts, args: &ConfigureArgs) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: accounts.to_account_metas(),
+        data: args.data(),
+    }
+}
+
+/// Custom error codes as `(code, name, message)`.
+pub const ERRORS: &[(u32, &str, &str)] = &[
+];
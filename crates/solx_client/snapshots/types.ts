@@ -0,0 +1,73 @@
+// Generated by SOL-X from program Types. Do not edit.
+
+import { AccountMeta, PublicKey, TransactionInstruction } from "@solana/web3.js";
+import * as borsh from "@coral-xyz/borsh";
+import BN from "bn.js";
+
+export const PROGRAM_ID = new PublicKey("11111111111111111111111111111111");
+
+/** Maximum instruction data size that fits in a transaction */
+const MAX_IX_DATA = 1232;
+
+export interface Limit {
+  amount: BN;
+  until: BN | null;
+}
+
+export const limitLayout = (property?: string) =>
+  borsh.struct([borsh.u64("amount"), borsh.option(borsh.i64(), "until")], property);
+
+export interface Schedule {
+  authority: PublicKey;
+  payouts: (BN | null)[];
+  limits: Limit[];
+  memo: number[] | null;
+}
+
+export const SCHEDULE_DISCRIMINATOR = Buffer.from([217, 243, 116, 56, 73, 82, 207, 51]);
+
+export const scheduleLayout = borsh.struct([borsh.publicKey("authority"), borsh.vec(borsh.option(borsh.u64()), "payouts"), borsh.vec(limitLayout(), "limits"), borsh.option(borsh.vec(borsh.u8()), "memo")]);
+
+export function decodeSchedule(data: Buffer): Schedule {
+  if (!data.subarray(0, 8).equals(SCHEDULE_DISCRIMINATOR)) {
+    throw new Error("Account is not a Schedule");
+  }
+  return scheduleLayout.decode(data.subarray(8)) as Schedule;
+}
+
+export const CONFIGURE_DISCRIMINATOR = Buffer.from([245, 7, 108, 117, 95, 196, 54, 217]);
+
+export interface ConfigureAccounts {
+  authority: PublicKey;
+  schedule: PublicKey;
+}
+
+export interface ConfigureArgs {
+  payouts: (BN | null)[];
+  limit: Limit | null;
+}
+
+const configureArgsLayout = borsh.struct([borsh.vec(borsh.option(borsh.u64()), "payouts"), borsh.option(limitLayout(), "limit")]);
+
+export function createConfigureInstruction(
+  accounts: ConfigureAccounts,
+  args: ConfigureArgs,
+  programId: PublicKey = PROGRAM_ID,
+): TransactionInstruction {
+  const buffer = Buffer.alloc(MAX_IX_DATA);
+  const len = configureArgsLayout.encode(args, buffer);
+  const data = Buffer.concat([CONFIGURE_DISCRIMINATOR, buffer.subarray(0, len)]);
+  const keys: AccountMeta[] = [
+    { pubkey: accounts.authority, isSigner: true, isWritable: true },
+    { pubkey: accounts.schedule, isSigner: true, isWritable: true },
+    { pubkey: new PublicKey("11111111111111111111111111111111"), isSigner: false, isWritable: false },
+  ];
+  return new TransactionInstruction({ keys, programId, data });
+}
+
+export const ERRORS: Record<number, { name: string; msg: string }> = {
+};
+
+export function errorFromCode(code: number): { name: string; msg: string } | undefined {
+  return ERRORS[code];
+}
@@ -0,0 +1,113 @@
+//! Client SDK generation for SOL-X programs.
+//!
+//! TypeScript modules and Rust crates are supported. Clients are generated from the HIR, so account layouts, discriminators and
+//! instruction account lists match the on-chain program without an IDL.
+
+pub mod rust;
+pub mod ts;
+
+pub use rust::{generate_rust_client, RustClient};
+pub use ts::generate_ts_client;
+
+use solx_ast::{ParamAnnotation, Program, Seed};
+use solx_hir::accounts::seeds;
+
+/// A `@seeds` account that gets an address derivation helper.
+pub(crate) struct PdaAccount<'a> {
+    /// snake_case helper name: the parameter name, prefixed with the
+    /// instruction name when another instruction uses different seeds for a
+    /// parameter of the same name.
+    pub name: String,
+    pub seeds: &'a [Seed],
+}
+
+pub(crate) fn pda_accounts(program: &Program) -> Vec<PdaAccount<'_>> {
+    let mut pdas: Vec<PdaAccount> = Vec::new();
+    for instruction in &program.instructions {
+        for param in &instruction.params {
+            let Some(seeds) = seeds(param) else {
+                continue;
+            };
+            let mut name = param.name.clone();
+            match pdas.iter().find(|p| p.name == name) {
+                Some(existing) if existing.seeds == seeds => continue,
+                Some(_) => name = format!("{}_{}", instruction.name, param.name),
+                None => {}
+            }
+            pdas.push(PdaAccount { name, seeds });
+        }
+    }
+    pdas
+}
+
+pub(crate) fn uses_associated_token(program: &Program) -> bool {
+    program.instructions.iter().any(|i| {
+        i.params.iter().any(|p| {
+            p.annotations
+                .iter()
+                .any(|a| matches!(a, ParamAnnotation::AssociatedToken { .. }))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solx_hir::Hir;
+    use std::path::Path;
+
+    const PROGRAM_ID: &str = "11111111111111111111111111111111";
+
+    /// Compare `actual` with the snapshot file, or rewrite it when
+    /// `SOLX_UPDATE_SNAPSHOTS` is set.
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("snapshots")
+            .join(name);
+        if std::env::var_os("SOLX_UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|_| panic!("missing snapshot {}", path.display()));
+        assert_eq!(
+            expected, actual,
+            "snapshot {} is out of date; rerun with SOLX_UPDATE_SNAPSHOTS=1",
+            name
+        );
+    }
+
+    /// An example program, or a fixture covering cases the examples do not.
+    fn example(name: &str) -> Hir {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let fixture = root.join("fixtures").join(format!("{}.solx", name));
+        let path = if fixture.exists() {
+            fixture
+        } else {
+            root.join("../../examples").join(name).join("program.solx")
+        };
+        let source = std::fs::read_to_string(path).unwrap();
+        Hir::from_ast(solx_parser::parse(&source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_ts_client_snapshots() {
+        for name in ["counter", "escrow", "types"] {
+            let client = generate_ts_client(&example(name), PROGRAM_ID);
+            assert_snapshot(&format!("{}.ts", name), &client);
+        }
+    }
+
+    #[test]
+    fn test_rust_client_snapshots() {
+        for name in ["counter", "escrow", "types"] {
+            let client = generate_rust_client(&example(name), PROGRAM_ID);
+            assert_snapshot(&format!("{}.rs", name), &client.lib_rs);
+            assert!(client.cargo_toml.contains(&format!(
+                "borsh = \"{}\"\nsolana-program = \"{}\"\n",
+                solx_codegen::backend::BORSH_VERSION,
+                solx_codegen::backend::SOLANA_PROGRAM_VERSION
+            )));
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! Rust client crate generation.
+//!
+//! The generated crate depends only on `solana-program` and `borsh`, so
+//! off-chain services can build instructions and read accounts without
+//! pulling in `anchor-lang`. It exposes:
+//! - an args struct, an accounts struct and an instruction builder per instruction
+//! - a Borsh struct with a discriminator-checked decoder per account
+//! - PDA derivation helpers for `@seeds` accounts and associated token accounts
+//! - the custom error codes
+//!
+//! Names reach it through the HIR, which rejects Rust keywords, and the
+//! crate versions are the ones the native backend is written against.
+
+use solx_ast::*;
+use solx_codegen::backend::{BORSH_VERSION, SOLANA_PROGRAM_VERSION};
+use solx_hir::accounts::{ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use solx_hir::idl::sighash;
+use solx_hir::naming::{to_pascal_case, to_snake_case};
+use solx_hir::Hir;
+
+use crate::{pda_accounts, uses_associated_token};
+
+/// The files of a generated Rust client crate.
+pub struct RustClient {
+    pub cargo_toml: String,
+    pub lib_rs: String,
+}
+
+pub fn generate_rust_client(hir: &Hir, program_id: &str) -> RustClient {
+    RustClient {
+        cargo_toml: generate_cargo_toml(&hir.program),
+        lib_rs: generate_lib_rs(hir, program_id),
+    }
+}
+
+fn generate_cargo_toml(program: &Program) -> String {
+    let mut output = String::new();
+    output.push_str("# Generated by SOL-X. Do not edit.\n\n");
+    output.push_str("[package]\n");
+    output.push_str(&format!(
+        "name = \"{}-client\"\n",
+        to_snake_case(&program.name).replace('_', "-")
+    ));
+    output.push_str("version = \"0.1.0\"\n");
+    output.push_str("edition = \"2021\"\n\n");
+    output.push_str("[dependencies]\n");
+    output.push_str(&format!("borsh = \"{}\"\n", BORSH_VERSION));
+    output.push_str(&format!(
+        "solana-program = \"{}\"\n",
+        SOLANA_PROGRAM_VERSION
+    ));
+    output
+}
+
+fn generate_lib_rs(hir: &Hir, program_id: &str) -> String {
+    let program = &hir.program;
+    let mut output = String::new();
+
+    output.push_str(&format!(
+        "//! Client for the {} program. Generated by SOL-X; do not edit.\n\n",
+        program.name
+    ));
+    output.push_str("use borsh::{BorshDeserialize, BorshSerialize};\n");
+    output.push_str("use solana_program::instruction::{AccountMeta, Instruction};\n");
+    output.push_str("use solana_program::pubkey::Pubkey;\n\n");
+    output.push_str(&format!(
+        "pub const ID: Pubkey = solana_program::pubkey!(\"{}\");\n\n",
+        program_id
+    ));
+
+    // Structs
+    for def in &program.structs {
+        output.push_str(&generate_struct(&def.name, &def.docs, &def.fields));
+    }
+
+    // Accounts
+    for account in &program.accounts {
+        output.push_str(&generate_account(account));
+    }
+
+    // Instructions
+    for instruction in &program.instructions {
+        output.push_str(&generate_instruction(hir, instruction));
+    }
+
+    // PDA helpers
+    output.push_str(&generate_pda_helpers(program));
+
+    // Errors
+    output.push_str("/// Custom error codes as `(code, name, message)`.\n");
+    output.push_str("pub const ERRORS: &[(u32, &str, &str)] = &[\n");
+    for error in &hir.errors {
+        output.push_str(&format!(
+            "    ({}, {:?}, {:?}),\n",
+            error.code, error.name, error.msg
+        ));
+    }
+    output.push_str("];\n");
+
+    output
+}
+
+fn generate_struct(name: &str, docs: &[String], fields: &[Field]) -> String {
+    let mut output = String::new();
+
+    output.push_str(&doc_comments(docs, ""));
+    output.push_str("#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]\n");
+    output.push_str(&format!("pub struct {} {{\n", name));
+    for field in fields {
+        output.push_str(&doc_comments(&field.docs, "    "));
+        output.push_str(&format!(
+            "    pub {}: {},\n",
+            field.name,
+            field.ty.to_rust_type()
+        ));
+    }
+    output.push_str("}\n\n");
+
+    output
+}
+
+fn generate_account(account: &AccountDef) -> String {
+    let mut output = generate_struct(&account.name, &account.docs, &account.fields);
+
+    output.push_str(&format!("impl {} {{\n", account.name));
+    output.push_str(&format!(
+        "    pub const DISCRIMINATOR: [u8; 8] = [{}];\n\n",
+        bytes(&sighash("account", &account.name))
+    ));
+    output.push_str(
+        "    /// Decode account data, checking the discriminator. Trailing bytes are ignored.\n",
+    );
+    output.push_str("    pub fn decode(data: &[u8]) -> std::io::Result<Self> {\n");
+    output.push_str("        if data.len() < 8 || data[..8] != Self::DISCRIMINATOR {\n");
+    output.push_str("            return Err(std::io::Error::new(\n");
+    output.push_str("                std::io::ErrorKind::InvalidData,\n");
+    output.push_str(&format!(
+        "                \"account is not a {}\",\n",
+        account.name
+    ));
+    output.push_str("            ));\n        }\n");
+    output.push_str("        Self::deserialize(&mut &data[8..])\n");
+    output.push_str("    }\n}\n\n");
+
+    output
+}
+
+fn generate_instruction(hir: &Hir, instruction: &Instruction) -> String {
+    let mut output = String::new();
+    let pascal = to_pascal_case(&instruction.name);
+    let accounts = hir.instruction_accounts(instruction);
+    let args: Vec<(&str, &Type)> = instruction
+        .params
+        .iter()
+        .filter(|p| !p.ty.is_account())
+        .map(|p| (p.name.as_str(), &p.ty))
+        .collect();
+
+    // Accounts struct; program accounts have fixed addresses
+    output.push_str(&format!(
+        "/// Accounts for the `{}` instruction.\n",
+        instruction.name
+    ));
+    output.push_str("#[derive(Clone, Copy, Debug, PartialEq)]\n");
+    output.push_str(&format!("pub struct {}Accounts {{\n", pascal));
+    for account in accounts.iter().filter(|a| a.address.is_none()) {
+        output.push_str(&format!("    pub {}: Pubkey,\n", account.name));
+    }
+    output.push_str("}\n\n");
+
+    output.push_str(&format!("impl {}Accounts {{\n", pascal));
+    output.push_str("    pub fn to_account_metas(&self) -> Vec<AccountMeta> {\n");
+    output.push_str("        vec![\n");
+    for account in &accounts {
+        let pubkey = match &account.address {
+            Some(address) => format!("solana_program::pubkey!(\"{}\")", address),
+            None => format!("self.{}", account.name),
+        };
+        let constructor = if account.writable {
+            "new"
+        } else {
+            "new_readonly"
+        };
+        output.push_str(&format!(
+            "            AccountMeta::{}({}, {}),\n",
+            constructor, pubkey, account.signer
+        ));
+    }
+    output.push_str("        ]\n    }\n}\n\n");
+
+    // Instruction data
+    output.push_str(&format!(
+        "/// Instruction data for `{}`.\n",
+        instruction.name
+    ));
+    output.push_str("#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]\n");
+    if args.is_empty() {
+        output.push_str(&format!("pub struct {}Args;\n\n", pascal));
+    } else {
+        output.push_str(&format!("pub struct {}Args {{\n", pascal));
+        for (name, ty) in &args {
+            output.push_str(&format!("    pub {}: {},\n", name, ty.to_rust_type()));
+        }
+        output.push_str("}\n\n");
+    }
+
+    output.push_str(&format!("impl {}Args {{\n", pascal));
+    output.push_str(&format!(
+        "    pub const DISCRIMINATOR: [u8; 8] = [{}];\n\n",
+        bytes(&sighash("global", &instruction.name))
+    ));
+    output.push_str("    /// The discriminator followed by the Borsh encoded arguments.\n");
+    output.push_str("    pub fn data(&self) -> Vec<u8> {\n");
+    output.push_str("        let mut data = Self::DISCRIMINATOR.to_vec();\n");
+    output.push_str("        self.serialize(&mut data).unwrap();\n");
+    output.push_str("        data\n    }\n}\n\n");
+
+    // Builder
+    output.push_str(&doc_comments(&instruction.docs, ""));
+    output.push_str(&format!(
+        "pub fn {}(accounts: &{}Accounts, args: &{}Args) -> Instruction {{\n",
+        instruction.name, pascal, pascal
+    ));
+    output.push_str("    Instruction {\n");
+    output.push_str("        program_id: ID,\n");
+    output.push_str("        accounts: accounts.to_account_metas(),\n");
+    output.push_str("        data: args.data(),\n");
+    output.push_str("    }\n}\n\n");
+
+    output
+}
+
+fn generate_pda_helpers(program: &Program) -> String {
+    let mut output = String::new();
+
+    for pda in pda_accounts(program) {
+        let mut inputs: Vec<&str> = Vec::new();
+        let mut seeds: Vec<String> = Vec::new();
+        for seed in pda.seeds {
+            match seed {
+                Seed::Const(s) => seeds.push(format!("{:?}.as_bytes()", s)),
+                Seed::Account(account) => {
+                    seeds.push(format!("{}.as_ref()", account));
+                    if !inputs.contains(&account.as_str()) {
+                        inputs.push(account);
+                    }
+                }
+            }
+        }
+
+        let params: Vec<String> = inputs.iter().map(|i| format!("{}: &Pubkey", i)).collect();
+        output.push_str(&format!(
+            "pub fn find_{}_address({}) -> (Pubkey, u8) {{\n",
+            pda.name,
+            params.join(", ")
+        ));
+        output.push_str(&format!(
+            "    Pubkey::find_program_address(&[{}], &ID)\n}}\n\n",
+            seeds.join(", ")
+        ));
+    }
+
+    if uses_associated_token(program) {
+        output.push_str(&format!(
+            "pub const TOKEN_PROGRAM_ID: Pubkey = solana_program::pubkey!(\"{}\");\n",
+            TOKEN_PROGRAM_ID
+        ));
+        output.push_str(&format!(
+            "pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey = solana_program::pubkey!(\"{}\");\n\n",
+            ASSOCIATED_TOKEN_PROGRAM_ID
+        ));
+        output.push_str(
+            "pub fn find_associated_token_address(owner: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {\n",
+        );
+        output.push_str("    Pubkey::find_program_address(\n");
+        output.push_str("        &[owner.as_ref(), TOKEN_PROGRAM_ID.as_ref(), mint.as_ref()],\n");
+        output.push_str("        &ASSOCIATED_TOKEN_PROGRAM_ID,\n    )\n}\n\n");
+    }
+
+    output
+}
+
+fn doc_comments(docs: &[String], indent: &str) -> String {
+    docs.iter()
+        .map(|line| format!("{}/// {}\n", indent, line))
+        .collect()
+}
+
+fn bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| b.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,365 @@
+//! TypeScript client generation.
+//!
+//! The generated module depends on `@solana/web3.js`, `@coral-xyz/borsh` and
+//! `bn.js`, and exposes:
+//! - an instruction builder per instruction
+//! - a Borsh layout and decoder per account
+//! - PDA derivation helpers for `@seeds` accounts and associated token accounts
+//! - a map from custom error codes to names and messages
+
+use solx_ast::*;
+use solx_hir::accounts::{ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID};
+use solx_hir::idl::sighash;
+use solx_hir::naming::{to_camel_case, to_pascal_case, to_screaming_snake_case};
+use solx_hir::Hir;
+
+use crate::{pda_accounts, uses_associated_token};
+
+pub fn generate_ts_client(hir: &Hir, program_id: &str) -> String {
+    let program = &hir.program;
+    let mut output = String::new();
+
+    output.push_str(&format!(
+        "// Generated by SOL-X from program {}. Do not edit.\n\n",
+        program.name
+    ));
+    output.push_str(
+        "import { AccountMeta, PublicKey, TransactionInstruction } from \"@solana/web3.js\";\n",
+    );
+    output.push_str("import * as borsh from \"@coral-xyz/borsh\";\n");
+    output.push_str("import BN from \"bn.js\";\n\n");
+    output.push_str(&format!(
+        "export const PROGRAM_ID = new PublicKey({});\n\n",
+        quote(program_id)
+    ));
+    output.push_str("/** Maximum instruction data size that fits in a transaction */\n");
+    output.push_str("const MAX_IX_DATA = 1232;\n\n");
+
+    // Structs, before the layouts that use them
+    for def in &program.structs {
+        output.push_str(&generate_struct(def));
+    }
+
+    // Accounts
+    for account in &program.accounts {
+        output.push_str(&generate_account(account));
+    }
+
+    // Instructions
+    for instruction in &program.instructions {
+        output.push_str(&generate_instruction(hir, instruction));
+    }
+
+    // PDA helpers
+    output.push_str(&generate_pda_helpers(program));
+
+    // Errors
+    output.push_str("export const ERRORS: Record<number, { name: string; msg: string }> = {\n");
+    for error in &hir.errors {
+        output.push_str(&format!(
+            "  {}: {{ name: {}, msg: {} }},\n",
+            error.code,
+            quote(&error.name),
+            quote(&error.msg)
+        ));
+    }
+    output.push_str("};\n\n");
+    output.push_str(
+        "export function errorFromCode(code: number): { name: string; msg: string } | undefined {\n",
+    );
+    output.push_str("  return ERRORS[code];\n}\n");
+
+    output
+}
+
+fn generate_struct(def: &StructDef) -> String {
+    let mut output = String::new();
+
+    output.push_str(&doc_comment(&def.docs, ""));
+    output.push_str(&format!("export interface {} {{\n", def.name));
+    output.push_str(&interface_fields(&def.fields));
+    output.push_str("}\n\n");
+
+    output.push_str(&format!(
+        "export const {}Layout = (property?: string) =>\n  borsh.struct([{}], property);\n\n",
+        to_camel_case(&def.name),
+        field_layouts(&def.fields)
+    ));
+
+    output
+}
+
+fn generate_account(account: &AccountDef) -> String {
+    let mut output = String::new();
+    let layout_name = format!("{}Layout", to_camel_case(&account.name));
+    let discriminator_name = format!("{}_DISCRIMINATOR", to_screaming_snake_case(&account.name));
+
+    output.push_str(&doc_comment(&account.docs, ""));
+    output.push_str(&format!("export interface {} {{\n", account.name));
+    output.push_str(&interface_fields(&account.fields));
+    output.push_str("}\n\n");
+
+    output.push_str(&format!(
+        "export const {} = Buffer.from([{}]);\n\n",
+        discriminator_name,
+        bytes(&sighash("account", &account.name))
+    ));
+    output.push_str(&format!(
+        "export const {} = {};\n\n",
+        layout_name,
+        struct_layout(&account.fields)
+    ));
+    output.push_str(&format!(
+        "export function decode{}(data: Buffer): {} {{\n",
+        account.name, account.name
+    ));
+    output.push_str(&format!(
+        "  if (!data.subarray(0, 8).equals({})) {{\n",
+        discriminator_name
+    ));
+    output.push_str(&format!(
+        "    throw new Error(\"Account is not a {}\");\n  }}\n",
+        account.name
+    ));
+    output.push_str(&format!(
+        "  return {}.decode(data.subarray(8)) as {};\n}}\n\n",
+        layout_name, account.name
+    ));
+
+    output
+}
+
+fn generate_instruction(hir: &Hir, instruction: &Instruction) -> String {
+    let mut output = String::new();
+    let pascal = to_pascal_case(&instruction.name);
+    let discriminator_name = format!("{}_DISCRIMINATOR", instruction.name.to_uppercase());
+    let accounts = hir.instruction_accounts(instruction);
+    let args: Vec<Field> = instruction
+        .params
+        .iter()
+        .filter(|p| !p.ty.is_account())
+        .map(|p| Field {
+            docs: Vec::new(),
+            name: p.name.clone(),
+            ty: p.ty.clone(),
+        })
+        .collect();
+
+    output.push_str(&format!(
+        "export const {} = Buffer.from([{}]);\n\n",
+        discriminator_name,
+        bytes(&sighash("global", &instruction.name))
+    ));
+
+    output.push_str(&format!("export interface {}Accounts {{\n", pascal));
+    for account in accounts.iter().filter(|a| a.address.is_none()) {
+        output.push_str(&format!("  {}: PublicKey;\n", to_camel_case(&account.name)));
+    }
+    output.push_str("}\n\n");
+
+    if !args.is_empty() {
+        output.push_str(&format!("export interface {}Args {{\n", pascal));
+        for arg in &args {
+            output.push_str(&format!(
+                "  {}: {};\n",
+                to_camel_case(&arg.name),
+                ts_type(&arg.ty)
+            ));
+        }
+        output.push_str("}\n\n");
+        output.push_str(&format!(
+            "const {}ArgsLayout = {};\n\n",
+            to_camel_case(&instruction.name),
+            struct_layout(&args)
+        ));
+    }
+
+    output.push_str(&doc_comment(&instruction.docs, ""));
+    output.push_str(&format!("export function create{}Instruction(\n", pascal));
+    output.push_str(&format!("  accounts: {}Accounts,\n", pascal));
+    if !args.is_empty() {
+        output.push_str(&format!("  args: {}Args,\n", pascal));
+    }
+    output.push_str("  programId: PublicKey = PROGRAM_ID,\n");
+    output.push_str("): TransactionInstruction {\n");
+
+    if args.is_empty() {
+        output.push_str(&format!("  const data = {};\n", discriminator_name));
+    } else {
+        output.push_str("  const buffer = Buffer.alloc(MAX_IX_DATA);\n");
+        output.push_str(&format!(
+            "  const len = {}ArgsLayout.encode(args, buffer);\n",
+            to_camel_case(&instruction.name)
+        ));
+        output.push_str(&format!(
+            "  const data = Buffer.concat([{}, buffer.subarray(0, len)]);\n",
+            discriminator_name
+        ));
+    }
+
+    output.push_str("  const keys: AccountMeta[] = [\n");
+    for account in &accounts {
+        let pubkey = match &account.address {
+            Some(address) => format!("new PublicKey({})", quote(address)),
+            None => format!("accounts.{}", to_camel_case(&account.name)),
+        };
+        output.push_str(&format!(
+            "    {{ pubkey: {}, isSigner: {}, isWritable: {} }},\n",
+            pubkey, account.signer, account.writable
+        ));
+    }
+    output.push_str("  ];\n");
+    output.push_str("  return new TransactionInstruction({ keys, programId, data });\n}\n\n");
+
+    output
+}
+
+fn generate_pda_helpers(program: &Program) -> String {
+    let mut output = String::new();
+
+    for pda in pda_accounts(program) {
+        let mut inputs: Vec<String> = Vec::new();
+        let mut buffers: Vec<String> = Vec::new();
+        for seed in pda.seeds {
+            match seed {
+                Seed::Const(s) => buffers.push(format!("Buffer.from({})", quote(s))),
+                Seed::Account(account) => {
+                    let account = to_camel_case(account);
+                    buffers.push(format!("{}.toBuffer()", account));
+                    if !inputs.contains(&account) {
+                        inputs.push(account);
+                    }
+                }
+            }
+        }
+
+        output.push_str(&format!(
+            "export function find{}Address(\n",
+            to_pascal_case(&pda.name)
+        ));
+        for input in &inputs {
+            output.push_str(&format!("  {}: PublicKey,\n", input));
+        }
+        output.push_str("  programId: PublicKey = PROGRAM_ID,\n");
+        output.push_str("): [PublicKey, number] {\n");
+        output.push_str(&format!(
+            "  return PublicKey.findProgramAddressSync([{}], programId);\n}}\n\n",
+            buffers.join(", ")
+        ));
+    }
+
+    if uses_associated_token(program) {
+        output.push_str(&format!(
+            "export const TOKEN_PROGRAM_ID = new PublicKey({});\n",
+            quote(TOKEN_PROGRAM_ID)
+        ));
+        output.push_str(&format!(
+            "export const ASSOCIATED_TOKEN_PROGRAM_ID = new PublicKey({});\n\n",
+            quote(ASSOCIATED_TOKEN_PROGRAM_ID)
+        ));
+        output.push_str(
+            "export function findAssociatedTokenAddress(owner: PublicKey, mint: PublicKey): [PublicKey, number] {\n",
+        );
+        output.push_str("  return PublicKey.findProgramAddressSync(\n");
+        output.push_str("    [owner.toBuffer(), TOKEN_PROGRAM_ID.toBuffer(), mint.toBuffer()],\n");
+        output.push_str("    ASSOCIATED_TOKEN_PROGRAM_ID,\n  );\n}\n\n");
+    }
+
+    output
+}
+
+fn interface_fields(fields: &[Field]) -> String {
+    let mut output = String::new();
+    for field in fields {
+        output.push_str(&doc_comment(&field.docs, "  "));
+        output.push_str(&format!(
+            "  {}: {};\n",
+            to_camel_case(&field.name),
+            ts_type(&field.ty)
+        ));
+    }
+    output
+}
+
+fn struct_layout(fields: &[Field]) -> String {
+    format!("borsh.struct([{}])", field_layouts(fields))
+}
+
+fn field_layouts(fields: &[Field]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|f| layout(&f.ty, Some(&to_camel_case(&f.name))))
+        .collect();
+    fields.join(", ")
+}
+
+/// Borsh layout for `ty`; `name` is omitted for nested element layouts.
+fn layout(ty: &Type, name: Option<&str>) -> String {
+    let property = name.map(quote).unwrap_or_default();
+    // The property name is only passed for named fields
+    let with_property = |inner: String| match name {
+        Some(_) => format!("{}, {}", inner, property),
+        None => inner,
+    };
+    match ty {
+        Type::Vec(inner) => format!("borsh.vec({})", with_property(layout(inner, None))),
+        Type::Option(inner) => format!("borsh.option({})", with_property(layout(inner, None))),
+        Type::Struct(name) => format!("{}Layout({})", to_camel_case(name), property),
+        other => {
+            let func = match other {
+                Type::Pubkey => "publicKey",
+                Type::U8 => "u8",
+                Type::U16 => "u16",
+                Type::U32 => "u32",
+                Type::U64 => "u64",
+                Type::I8 => "i8",
+                Type::I16 => "i16",
+                Type::I32 => "i32",
+                Type::I64 => "i64",
+                Type::Bool => "bool",
+                Type::String => "str",
+                _ => unreachable!("{} is not a primitive data type", other),
+            };
+            format!("borsh.{}({})", func, property)
+        }
+    }
+}
+
+fn ts_type(ty: &Type) -> String {
+    match ty {
+        Type::Pubkey => "PublicKey".to_string(),
+        Type::U8 | Type::U16 | Type::U32 | Type::I8 | Type::I16 | Type::I32 => "number".to_string(),
+        Type::U64 | Type::I64 => "BN".to_string(),
+        Type::Bool => "boolean".to_string(),
+        Type::String => "string".to_string(),
+        Type::Vec(inner) => match inner.as_ref() {
+            Type::Option(_) => format!("({})[]", ts_type(inner)),
+            _ => format!("{}[]", ts_type(inner)),
+        },
+        Type::Option(inner) => format!("{} | null", ts_type(inner)),
+        Type::Struct(name) => name.clone(),
+        Type::Signer | Type::Account(_) | Type::TokenAccount | Type::Mint | Type::Named(_) => {
+            unreachable!("{} is not a data type", ty)
+        }
+    }
+}
+
+fn doc_comment(docs: &[String], indent: &str) -> String {
+    if docs.is_empty() {
+        return String::new();
+    }
+    format!("{}/** {} */\n", indent, docs.join(" "))
+}
+
+fn bytes(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| b.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A TypeScript string literal; JSON string syntax is valid TypeScript.
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).unwrap()
+}
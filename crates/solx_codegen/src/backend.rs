@@ -0,0 +1,462 @@
+//! Code generation backends.
+//!
+//! A backend turns the type checked HIR into a set of files relative to the
+//! program crate directory. The CLI looks backends up by name in a
+//! [`BackendRegistry`]; other tools can register their own implementations
+//! (e.g. pinocchio) next to the builtin ones.
+//!
+//! The builtin backends write the program to `src/generated.rs`, which is
+//! regenerated on every build, and create `src/lib.rs` only once: it
+//! `include!`s the generated code and holds hand-written helpers.
+
+use anyhow::Result;
+use quote::quote;
+use solx_ast::{ParamAnnotation, Type};
+use solx_hir::{Hir, HELPERS_MODULE};
+use std::path::PathBuf;
+
+use crate::{
+    doc_attrs, extern_fn_signature, generate_anchor_code, generate_native_code, ident, pretty,
+    EXTERN_FNS_TRAIT, HELPERS_TYPE,
+};
+
+/// Options shared by all backends.
+#[derive(Debug, Clone)]
+pub struct BackendOptions {
+    /// Base58 program ID to embed in the generated program.
+    pub program_id: String,
+}
+
+/// A file produced by a backend. `path` is relative to the program crate
+/// directory, e.g. `programs/<name>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    pub contents: String,
+    /// When false the file is a scaffold for hand-written code, only
+    /// written if it does not exist yet.
+    pub overwrite: bool,
+}
+
+/// A crate the generated code uses, e.g. `borsh = "0.10"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    pub version: String,
+    /// Features of the crate to enable, e.g. `init-if-needed`
+    pub features: Vec<String>,
+    /// Features of the program crate that enable the same feature of this
+    /// crate, e.g. `idl-build = ["anchor-lang/idl-build"]`
+    pub forwarded_features: Vec<String>,
+}
+
+impl Dependency {
+    pub fn new(name: &str, version: &str) -> Self {
+        Dependency {
+            name: name.to_string(),
+            version: version.to_string(),
+            features: Vec::new(),
+            forwarded_features: Vec::new(),
+        }
+    }
+
+    pub fn feature(mut self, feature: &str) -> Self {
+        self.features.push(feature.to_string());
+        self
+    }
+
+    pub fn forward_feature(mut self, feature: &str) -> Self {
+        self.forwarded_features.push(feature.to_string());
+        self
+    }
+}
+
+pub trait Backend {
+    /// Name used to select the backend, e.g. `solx build --target <name>`.
+    fn name(&self) -> &str;
+
+    /// One-line description for help output.
+    fn description(&self) -> &str;
+
+    fn generate(&self, hir: &Hir, options: &BackendOptions) -> Result<Vec<GeneratedFile>>;
+
+    /// Crates the generated code needs in the program crate's `Cargo.toml`.
+    /// `solx build` adds the missing ones and keeps existing versions.
+    fn dependencies(&self, _hir: &Hir) -> Vec<Dependency> {
+        Vec::new()
+    }
+
+    /// Crates of other backends this one does not use, removed from the
+    /// program crate's `Cargo.toml` with the features that refer to them.
+    fn replaces(&self) -> &[&str] {
+        &[]
+    }
+}
+
+/// Anchor program in `src/generated.rs`, `include!`d by `src/lib.rs`.
+pub struct AnchorBackend;
+
+impl Backend for AnchorBackend {
+    fn name(&self) -> &str {
+        "anchor"
+    }
+
+    fn description(&self) -> &str {
+        "An Anchor program"
+    }
+
+    fn generate(&self, hir: &Hir, options: &BackendOptions) -> Result<Vec<GeneratedFile>> {
+        let code = generate_anchor_code(hir)?;
+        Ok(vec![
+            generated_rs(format!(
+                "declare_id!(\"{}\");\n\n{}",
+                options.program_id, code
+            )),
+            lib_rs(hir)?,
+        ])
+    }
+
+    fn dependencies(&self, hir: &Hir) -> Vec<Dependency> {
+        let params = || hir.program.instructions.iter().flat_map(|i| &i.params);
+        let mut anchor_lang =
+            Dependency::new("anchor-lang", ANCHOR_VERSION).forward_feature("idl-build");
+        if params().any(|p| {
+            p.annotations
+                .iter()
+                .any(|a| matches!(a, ParamAnnotation::InitIfNeeded { .. }))
+        }) {
+            anchor_lang = anchor_lang.feature("init-if-needed");
+        }
+        let mut dependencies = vec![anchor_lang];
+        if params().any(|p| matches!(p.ty, Type::TokenAccount | Type::Mint)) {
+            dependencies
+                .push(Dependency::new("anchor-spl", ANCHOR_VERSION).forward_feature("idl-build"));
+        }
+        dependencies
+    }
+
+    fn replaces(&self) -> &[&str] {
+        &["solana-program", "borsh"]
+    }
+}
+
+/// Plain `solana_program` entrypoint in `src/generated.rs`, `include!`d by
+/// `src/lib.rs`.
+pub struct NativeBackend;
+
+impl Backend for NativeBackend {
+    fn name(&self) -> &str {
+        "native"
+    }
+
+    fn description(&self) -> &str {
+        "A plain solana_program entrypoint, without Anchor"
+    }
+
+    fn generate(&self, hir: &Hir, options: &BackendOptions) -> Result<Vec<GeneratedFile>> {
+        let code = generate_native_code(hir)?;
+        Ok(vec![
+            generated_rs(format!(
+                "solana_program::declare_id!(\"{}\");\n\n{}",
+                options.program_id, code
+            )),
+            lib_rs(hir)?,
+        ])
+    }
+
+    fn dependencies(&self, _hir: &Hir) -> Vec<Dependency> {
+        vec![
+            Dependency::new("solana-program", SOLANA_PROGRAM_VERSION),
+            Dependency::new("borsh", BORSH_VERSION),
+        ]
+    }
+
+    fn replaces(&self) -> &[&str] {
+        &["anchor-lang", "anchor-spl"]
+    }
+}
+
+/// Anchor version the Anchor backend's output is written against.
+pub const ANCHOR_VERSION: &str = "0.30.1";
+
+/// Versions the native backend's output is written against, matching the
+/// ones Anchor 0.30 builds on.
+pub const SOLANA_PROGRAM_VERSION: &str = "1.18";
+pub const BORSH_VERSION: &str = "0.10";
+
+/// Header of `src/generated.rs`. Plain comments, since `include!`d files
+/// cannot have inner attributes or inner doc comments.
+pub const GENERATED_HEADER: &str = "\
+// Generated by SOL-X from program.solx. Do not edit: `solx build`
+// overwrites this file. Hand-written code belongs in lib.rs.
+
+";
+
+fn generated_rs(code: String) -> GeneratedFile {
+    GeneratedFile {
+        path: PathBuf::from("src/generated.rs"),
+        contents: format!("{}{}", GENERATED_HEADER, code),
+        overwrite: true,
+    }
+}
+
+/// The crate root: includes the generated program and scaffolds the
+/// implementation of the `extern fn` helpers declared so far. Each body is a
+/// `compile_error!` until written, so a missing helper cannot reach the chain.
+fn lib_rs(hir: &Hir) -> Result<GeneratedFile> {
+    let module = ident(HELPERS_MODULE);
+    let implementation = if hir.program.externs.is_empty() {
+        quote!()
+    } else {
+        let helpers = ident(HELPERS_TYPE);
+        let extern_fns = ident(EXTERN_FNS_TRAIT);
+        let fns = hir.program.externs.iter().map(|def| {
+            let docs = doc_attrs(&def.docs);
+            let signature = extern_fn_signature(def);
+            let todo = format!("implement extern fn {} in src/lib.rs", def.name);
+            quote! {
+                #docs
+                #signature {
+                    compile_error!(#todo)
+                }
+            }
+        });
+        quote! {
+            pub struct #helpers;
+
+            impl #extern_fns for #helpers {
+                #(#fns)*
+            }
+        }
+    };
+    let code = pretty(quote! {
+        include!("generated.rs");
+
+        /// Hand-written functions declared in program.solx with `extern fn`,
+        /// called from instructions as `helpers.name(...)`.
+        pub mod #module {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #implementation
+        }
+    })?;
+    Ok(GeneratedFile {
+        path: PathBuf::from("src/lib.rs"),
+        contents: format!(
+            "//! The {} program. SOL-X creates this file once; edit it freely.\n\n{}",
+            hir.program.name, code
+        ),
+        overwrite: false,
+    })
+}
+
+/// The signatures of the `extern fn`s a hand-written lib.rs does not
+/// implement, e.g. ones declared after it was created. Checked before cargo
+/// runs, so the error names the helpers instead of a missing trait item.
+pub fn missing_helpers(hir: &Hir, lib_rs: &str) -> Vec<String> {
+    let implements = lib_rs.contains(&format!("impl {} for {}", EXTERN_FNS_TRAIT, HELPERS_TYPE));
+    hir.program
+        .externs
+        .iter()
+        .filter(|def| !implements || !defines_fn(lib_rs, &def.name))
+        .map(|def| {
+            let params: Vec<String> = def
+                .params
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, ty.to_rust_type()))
+                .collect();
+            let ret = def
+                .ret
+                .as_ref()
+                .map(|ty| format!(" -> {}", ty.to_rust_type()))
+                .unwrap_or_default();
+            format!("fn {}({}){}", def.name, params.join(", "), ret)
+        })
+        .collect()
+}
+
+/// Whether `source` has `fn name(`, allowing whitespace before the paren.
+fn defines_fn(source: &str, name: &str) -> bool {
+    let prefix = format!("fn {}", name);
+    source
+        .match_indices(&prefix)
+        .any(|(i, _)| source[i + prefix.len()..].trim_start().starts_with('('))
+}
+
+/// Backends available by name.
+pub struct BackendRegistry {
+    backends: Vec<Box<dyn Backend>>,
+}
+
+impl BackendRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        BackendRegistry {
+            backends: Vec::new(),
+        }
+    }
+
+    /// Add a backend, replacing any registered backend with the same name.
+    pub fn register(&mut self, backend: Box<dyn Backend>) {
+        self.backends.retain(|b| b.name() != backend.name());
+        self.backends.push(backend);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Backend> {
+        self.backends
+            .iter()
+            .find(|b| b.name() == name)
+            .map(|b| b.as_ref())
+    }
+
+    pub fn backends(&self) -> impl Iterator<Item = &dyn Backend> {
+        self.backends.iter().map(|b| b.as_ref())
+    }
+
+    /// Look up `name`, listing the available backends if it is unknown.
+    pub fn resolve(&self, name: &str) -> Result<&dyn Backend> {
+        self.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.backends().map(|b| b.name()).collect();
+            anyhow::anyhow!(
+                "Unknown target {}; available targets: {}",
+                name,
+                names.join(", ")
+            )
+        })
+    }
+}
+
+/// The builtin `anchor` and `native` backends.
+impl Default for BackendRegistry {
+    fn default() -> Self {
+        let mut registry = BackendRegistry::new();
+        registry.register(Box::new(AnchorBackend));
+        registry.register(Box::new(NativeBackend));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Stub;
+
+    impl Backend for Stub {
+        fn name(&self) -> &str {
+            "anchor"
+        }
+
+        fn description(&self) -> &str {
+            "stub"
+        }
+
+        fn generate(&self, _hir: &Hir, _options: &BackendOptions) -> Result<Vec<GeneratedFile>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_generated_file_and_lib_rs_scaffold() {
+        let source = r#"
+program Vault
+
+extern fn fee(amount: u64) -> u64
+
+instruction deposit(user: Signer, amount: u64) {
+  require amount > helpers.fee(amount)
+}
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let options = BackendOptions {
+            program_id: "11111111111111111111111111111111".to_string(),
+        };
+        let files = AnchorBackend.generate(&hir, &options).unwrap();
+
+        assert_eq!(files[0].path, PathBuf::from("src/generated.rs"));
+        assert!(files[0].overwrite);
+        assert!(files[0].contents.starts_with(GENERATED_HEADER));
+        assert_eq!(
+            files[0]
+                .contents
+                .matches("use anchor_lang::prelude::*;")
+                .count(),
+            1
+        );
+        assert!(files[0]
+            .contents
+            .contains("pub trait ExternFns {\n    fn fee(amount: u64) -> u64;\n}"));
+        assert!(files[0]
+            .contents
+            .contains("amount > helpers::Helpers::fee(amount),"));
+
+        assert_eq!(files[1].path, PathBuf::from("src/lib.rs"));
+        assert!(!files[1].overwrite);
+        assert!(files[1].contents.contains("include!(\"generated.rs\");"));
+        assert!(files[1].contents.contains(
+            "impl ExternFns for Helpers {\n        fn fee(amount: u64) -> u64 {\n            \
+             compile_error!(\"implement extern fn fee in src/lib.rs\")"
+        ));
+        assert!(missing_helpers(&hir, &files[1].contents).is_empty());
+
+        // A helper declared after lib.rs was created
+        let source = format!("{}\nextern fn log_deposit(user: Pubkey)\n", source);
+        let hir = Hir::from_ast(solx_parser::parse(&source).unwrap()).unwrap();
+        assert_eq!(
+            missing_helpers(&hir, &files[1].contents),
+            vec!["fn log_deposit(user: Pubkey)"]
+        );
+        assert_eq!(
+            missing_helpers(&hir, "include!(\"generated.rs\");").len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_anchor_dependencies() {
+        let hir = |params: &str| {
+            let source = format!("program Vault\n\ninstruction deposit({}) {{\n}}\n", params);
+            Hir::from_ast(solx_parser::parse(&source).unwrap()).unwrap()
+        };
+        let names = |dependencies: Vec<Dependency>| -> Vec<(String, Vec<String>)> {
+            dependencies
+                .into_iter()
+                .map(|d| (d.name, d.features))
+                .collect()
+        };
+
+        assert_eq!(
+            names(AnchorBackend.dependencies(&hir("user: Signer"))),
+            vec![("anchor-lang".to_string(), vec![])]
+        );
+        let tokens = "user: Signer, mint: Mint, user_ata: TokenAccount \
+                      @associated_token(mint = mint, authority = user) @init_if_needed(payer = user)";
+        assert_eq!(
+            names(AnchorBackend.dependencies(&hir(tokens))),
+            vec![
+                (
+                    "anchor-lang".to_string(),
+                    vec!["init-if-needed".to_string()]
+                ),
+                ("anchor-spl".to_string(), vec![]),
+            ]
+        );
+        assert_eq!(AnchorBackend.replaces(), ["solana-program", "borsh"]);
+    }
+
+    #[test]
+    fn test_registry_lookup_and_override() {
+        let mut registry = BackendRegistry::default();
+        assert_eq!(registry.resolve("native").unwrap().name(), "native");
+        let err = registry.resolve("pinocchio").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Unknown target pinocchio; available targets: anchor, native"
+        );
+
+        registry.register(Box::new(Stub));
+        assert_eq!(registry.get("anchor").unwrap().description(), "stub");
+        assert_eq!(registry.backends().count(), 2);
+    }
+}
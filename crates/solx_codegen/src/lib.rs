@@ -1,219 +1,1207 @@
-use anyhow::Result;
+pub mod backend;
+pub mod native;
+
+pub use backend::{
+    missing_helpers, Backend, BackendOptions, BackendRegistry, Dependency, GeneratedFile,
+};
+pub use native::generate_native_code;
+
+use anyhow::{Context as _, Result};
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::{format_ident, quote};
 use solx_ast::*;
-use solx_hir::Hir;
+use solx_hir::accounts::{account_space, calls_program, close_recipient, seeds};
+use solx_hir::errors::ARITHMETIC_OVERFLOW;
+use solx_hir::naming::{context_name, to_snake_case};
+use solx_hir::properties::{property, Property};
+use solx_hir::sysvar::Sysvar;
+use solx_hir::{Arithmetic, Hir, ImportedProgram, HELPERS_MODULE};
 
 pub fn generate_anchor_code(hir: &Hir) -> Result<String> {
     let program = &hir.program;
-    let mut output = String::new();
 
     // Generate use statements
-    output.push_str("use anchor_lang::prelude::*;\n\n");
+    let mut uses = vec![quote!(
+        use anchor_lang::prelude::*;
+    )];
+    let params = || program.instructions.iter().flat_map(|i| i.params.iter());
+    if params().any(|p| matches!(p.ty, Type::TokenAccount | Type::Mint)) {
+        uses.push(quote!(
+            use anchor_spl::token::{Mint, Token, TokenAccount};
+        ));
+    }
+    if params().any(is_associated_token) {
+        uses.push(quote!(
+            use anchor_spl::associated_token::AssociatedToken;
+        ));
+    }
+
+    // Generate CPI modules for imported programs
+    let cpi_modules = hir.imports.iter().map(generate_cpi_module);
+
+    // Generate user structs
+    let structs = program.structs.iter().map(|def| {
+        let docs = doc_attrs(&def.docs);
+        let name = ident(&def.name);
+        let fields = struct_fields(&def.fields);
+        quote! {
+            #docs
+            #[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+            pub struct #name { #(#fields),* }
+        }
+    });
 
     // Generate account structs
-    for account in &program.accounts {
-        output.push_str("#[account]\n");
-        output.push_str(&format!("pub struct {} {{\n", account.name));
-        for field in &account.fields {
-            output.push_str(&format!(
-                "    pub {}: {},\n",
-                field.name,
-                field.ty.to_rust_type()
-            ));
+    let accounts = program.accounts.iter().map(|account| {
+        let docs = doc_attrs(&account.docs);
+        let name = ident(&account.name);
+        let fields = struct_fields(&account.fields);
+        quote! {
+            #docs
+            #[account]
+            pub struct #name { #(#fields),* }
         }
-        output.push_str("}\n\n");
-    }
+    });
+
+    // Generate event structs
+    let events = program.events.iter().map(|event| {
+        let docs = doc_attrs(&event.docs);
+        let name = ident(&event.name);
+        let fields = struct_fields(&event.fields);
+        quote! {
+            #docs
+            #[event]
+            pub struct #name { #(#fields),* }
+        }
+    });
 
-    // Generate program module
-    output.push_str("#[program]\n");
-    output.push_str(&format!("pub mod {} {{\n", program.name.to_lowercase()));
-    output.push_str("    use super::*;\n\n");
+    let extern_fns = extern_fns_trait(hir);
 
-    // Generate instruction handlers
-    for instruction in &program.instructions {
-        output.push_str(&format!("    pub fn {}(\n", instruction.name));
-        output.push_str("        ctx: Context<");
-        output.push_str(instruction.name.as_str());
-        output.push_str("Context>,\n");
+    // Generate program module with instruction handlers
+    let module = ident(&to_snake_case(&program.name));
+    let handlers = program
+        .instructions
+        .iter()
+        .map(|instruction| generate_handler(hir, instruction));
 
-        // Generate parameters
-        for param in &instruction.params {
-            if matches!(param.ty, ParamType::Signer | ParamType::Account(_)) {
-                continue; // These are in the context
-            }
-            output.push_str(&format!("        {}: {},\n", param.name, param.ty.to_rust_type()));
+    // Generate custom errors from require messages
+    let errors = if hir.errors.is_empty() {
+        quote!()
+    } else {
+        let variants = hir.errors.iter().map(|error| {
+            let name = ident(&error.name);
+            let msg = &error.msg;
+            quote!(#[msg(#msg)] #name)
+        });
+        quote! {
+            #[error_code]
+            pub enum ErrorCode { #(#variants),* }
         }
-        output.push_str("    ) -> Result<()> {\n");
+    };
 
-        // Generate context struct name
-        let context_name = format!("{}Context", instruction.name);
+    // Generate context structs
+    let contexts = program
+        .instructions
+        .iter()
+        .map(|instruction| generate_context(hir, instruction));
 
-        // Generate body
-        for stmt in &instruction.body {
-            output.push_str(&generate_statement(stmt, &context_name));
+    pretty(quote! {
+        #(#uses)*
+        #(#cpi_modules)*
+        #(#structs)*
+        #(#accounts)*
+        #(#events)*
+        #extern_fns
+        #[program]
+        pub mod #module {
+            use super::*;
+            #(#handlers)*
         }
+        #errors
+        #(#contexts)*
+    })
+}
 
-        output.push_str("        Ok(())\n");
-        output.push_str("    }\n\n");
+fn generate_handler(hir: &Hir, instruction: &Instruction) -> TokenStream {
+    let docs = doc_attrs(&instruction.docs);
+    let name = ident(&instruction.name);
+    let context = context_ident(instruction);
+    // Accounts are in the context, other parameters are handler arguments
+    let args = instruction
+        .params
+        .iter()
+        .filter(|p| !p.ty.is_account())
+        .map(|p| {
+            let name = ident(&p.name);
+            let ty = rust_type(&p.ty.to_rust_type());
+            quote!(#name: #ty)
+        });
+    // The body, then the invariants of the accounts it wrote
+    let checks = hir.invariant_checks(instruction);
+    let body = instruction
+        .body
+        .iter()
+        .chain(&checks)
+        .map(|stmt| generate_statement(stmt, hir, instruction));
+
+    quote! {
+        #docs
+        pub fn #name(ctx: Context<#context>, #(#args),*) -> Result<()> {
+            #(#body)*
+            Ok(())
+        }
     }
+}
 
-    output.push_str("}\n\n");
+fn generate_context(hir: &Hir, instruction: &Instruction) -> TokenStream {
+    let program = &hir.program;
+    let name = context_ident(instruction);
 
-    // Generate context structs
-    for instruction in &program.instructions {
-        output.push_str("#[derive(Accounts)]\n");
-        output.push_str(&format!("pub struct {}Context<'info> {{\n", instruction.name));
+    // Find init account statements to determine which accounts need init
+    let init_payer = |param: &str| {
+        instruction.body.iter().find_map(|s| match s {
+            Statement::InitAccount {
+                var_name, payer, ..
+            } if var_name == param => Some(payer.as_str()),
+            _ => None,
+        })
+    };
 
-        // Find init account statements to determine which accounts need init
-        let init_accounts: Vec<(&str, &str, Option<&str>)> = instruction
-            .body
-            .iter()
-            .filter_map(|s| {
-                if let Statement::InitAccount {
-                    var_name,
-                    account_name: _,
-                    payer,
-                    signer,
-                } = s
-                {
-                    // Find the parameter that matches the variable name
-                    instruction
-                        .params
-                        .iter()
-                        .find(|p| p.name == *var_name)
-                        .map(|p| (p.name.as_str(), payer.as_str(), signer.as_deref()))
-                } else {
-                    None
+    // Generate accounts from parameters
+    let mut fields = Vec::new();
+    for param in &instruction.params {
+        let field = ident(&param.name);
+        match &param.ty {
+            Type::Signer if hir.is_writable(instruction, param) => {
+                fields.push(quote!(#[account(mut)] pub #field: Signer<'info>));
+            }
+            Type::Signer => {
+                fields.push(quote!(pub #field: Signer<'info>));
+            }
+            Type::Account(account_name) => {
+                let mut constraints = match init_payer(&param.name) {
+                    Some(payer) => {
+                        let size = program
+                            .accounts
+                            .iter()
+                            .find(|a| a.name == *account_name)
+                            .map_or(8, |a| account_space(program, a));
+                        let payer = ident(payer);
+                        let size = Literal::u64_unsuffixed(size);
+                        vec![quote!(init), quote!(payer = #payer), quote!(space = #size)]
+                    }
+                    None if hir.is_writable(instruction, param) => vec![quote!(mut)],
+                    None => Vec::new(),
+                };
+                if let Some(recipient) = close_recipient(instruction, &param.name) {
+                    let recipient = ident(recipient);
+                    constraints.push(quote!(close = #recipient));
                 }
-            })
-            .collect();
+                constraints.extend(seeds_constraints(param));
+                let ty = ident(account_name);
+                fields.push(account_field(
+                    &field,
+                    &constraints,
+                    quote!(Account<'info, #ty>),
+                ));
+            }
+            Type::TokenAccount => {
+                let constraints =
+                    token_account_constraints(param, hir.is_writable(instruction, param));
+                fields.push(account_field(
+                    &field,
+                    &constraints,
+                    quote!(Account<'info, TokenAccount>),
+                ));
+            }
+            Type::Mint => {
+                let constraints = if hir.is_writable(instruction, param) {
+                    vec![quote!(mut)]
+                } else {
+                    Vec::new()
+                };
+                fields.push(account_field(
+                    &field,
+                    &constraints,
+                    quote!(Account<'info, Mint>),
+                ));
+            }
+            _ => {}
+        }
+    }
 
-        // Generate accounts from parameters
-        for param in &instruction.params {
-            match &param.ty {
-                ParamType::Signer => {
-                    output.push_str(&format!(
-                        "    #[account(mut)]\n    pub {}: Signer<'info>,\n",
-                        param.name
-                    ));
-                }
-                ParamType::Account(acc_name) => {
-                    // Check if this account needs to be initialized
-                    let init_info = init_accounts
-                        .iter()
-                        .find(|(param_name, _, _)| param_name == &param.name);
-
-                    if let Some((_, payer, _signer)) = init_info {
-                        // Calculate account size: 8 (discriminator) + sum of field sizes
-                        let mut size = 8u64;
-                        if let Some(account_def) = program.accounts.iter().find(|a| a.name == *acc_name) {
-                            for field in &account_def.fields {
-                                size += calculate_type_size(&field.ty);
-                            }
-                        }
-                        output.push_str(&format!(
-                            "    #[account(\n        init,\n        payer = {},\n        space = {}\n    )]\n",
-                            payer, size
-                        ));
-                    } else {
-                        output.push_str("    #[account(mut)]\n");
+    // Programs required by the constraints above
+    let needs_system = instruction
+        .body
+        .iter()
+        .any(|s| matches!(s, Statement::InitAccount { .. }))
+        || instruction.params.iter().any(|p| {
+            p.annotations
+                .iter()
+                .any(|a| matches!(a, ParamAnnotation::InitIfNeeded { .. }))
+        });
+    if needs_system {
+        fields.push(quote!(pub system_program: Program<'info, System>));
+    }
+    if instruction
+        .params
+        .iter()
+        .any(|p| p.ty == Type::TokenAccount)
+    {
+        fields.push(quote!(pub token_program: Program<'info, Token>));
+    }
+    if instruction.params.iter().any(is_associated_token) {
+        fields.push(quote!(pub associated_token_program: Program<'info, AssociatedToken>));
+    }
+    for import in &hir.imports {
+        if calls_program(instruction, &import.module) {
+            let field = format_ident!("{}_program", import.module);
+            let module = ident(&import.module);
+            let ty = ident(&import.name);
+            fields.push(quote!(pub #field: Program<'info, #module::#ty>));
+        }
+    }
+
+    quote! {
+        #[derive(Accounts)]
+        pub struct #name<'info> { #(#fields),* }
+    }
+}
+
+/// A context struct field, with an `#[account(...)]` attribute only when
+/// there are constraints.
+fn account_field(field: &Ident, constraints: &[TokenStream], ty: TokenStream) -> TokenStream {
+    if constraints.is_empty() {
+        quote!(pub #field: #ty)
+    } else {
+        quote! {
+            #[account(#(#constraints),*)]
+            pub #field: #ty
+        }
+    }
+}
+
+fn context_ident(instruction: &Instruction) -> Ident {
+    ident(&context_name(&instruction.name))
+}
+
+/// Parse and pretty print generated tokens.
+pub(crate) fn pretty(tokens: TokenStream) -> Result<String> {
+    let file: syn::File = syn::parse2(tokens).context("Code generation produced invalid Rust")?;
+    Ok(unparse_items(file.items))
+}
+
+/// `prettyplease` does not separate items, so print them one at a time with
+/// a blank line between everything but consecutive `use` declarations.
+fn unparse_items(items: Vec<syn::Item>) -> String {
+    let mut output = String::new();
+    let mut previous_use = false;
+    for item in items {
+        let is_use = matches!(item, syn::Item::Use(_));
+        let grouped = is_use && previous_use;
+        if !output.is_empty() && !grouped {
+            output.push('\n');
+        }
+        previous_use = is_use;
+        match item {
+            syn::Item::Mod(mut module) if module.content.is_some() => {
+                let (brace, items) = module.content.take().unwrap();
+                module.content = Some((brace, Vec::new()));
+                let header = unparse_item(syn::Item::Mod(module));
+                output.push_str(header.trim_end().trim_end_matches("{}"));
+                output.push_str("{\n");
+                for line in unparse_items(items).lines() {
+                    if !line.is_empty() {
+                        output.push_str("    ");
                     }
-                    output.push_str(&format!(
-                        "    pub {}: Account<'info, {}>,\n",
-                        param.name, acc_name
-                    ));
+                    output.push_str(line);
+                    output.push('\n');
                 }
-                _ => {}
+                output.push_str("}\n");
             }
+            item => output.push_str(&unparse_item(item)),
         }
-
-        output.push_str("}\n\n");
     }
+    output
+}
+
+fn unparse_item(item: syn::Item) -> String {
+    prettyplease::unparse(&syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items: vec![item],
+    })
+}
+
+pub(crate) fn ident(name: &str) -> Ident {
+    format_ident!("{}", name)
+}
+
+pub(crate) fn rust_type(ty: &str) -> syn::Type {
+    syn::parse_str(ty).expect("valid Rust type")
+}
+
+pub(crate) fn doc_attrs(docs: &[String]) -> TokenStream {
+    let docs = docs.iter().map(|line| format!(" {}", line));
+    quote!(#(#[doc = #docs])*)
+}
+
+pub(crate) fn struct_fields(fields: &[Field]) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .map(|field| {
+            let docs = doc_attrs(&field.docs);
+            let name = ident(&field.name);
+            let ty = rust_type(&field.ty.to_rust_type());
+            quote!(#docs pub #name: #ty)
+        })
+        .collect()
+}
 
-    Ok(output)
+pub(crate) fn bytes(bytes: &[u8]) -> TokenStream {
+    let bytes = bytes.iter().map(|b| Literal::u8_unsuffixed(*b));
+    quote!(#(#bytes),*)
 }
 
-fn calculate_type_size(ty: &Type) -> u64 {
-    match ty {
-        Type::Pubkey => 32,
-        Type::U8 | Type::I8 => 1,
-        Type::U16 | Type::I16 => 2,
-        Type::U32 | Type::I32 => 4,
-        Type::U64 | Type::I64 => 8,
-        Type::Bool => 1,
-        Type::String => 4 + 4, // length prefix + data (variable, but we'll use a default)
-        Type::Vec(inner) => 4 + 4 + calculate_type_size(inner), // length + capacity + element size
-        Type::Option(inner) => 1 + calculate_type_size(inner), // discriminant + inner
+/// `seeds = [...]` and `bump` constraints for a PDA account parameter.
+fn seeds_constraints(param: &Param) -> Vec<TokenStream> {
+    let Some(seeds) = seeds(param) else {
+        return Vec::new();
+    };
+    let seeds = seed_exprs(seeds, |name| {
+        let name = ident(name);
+        quote!(#name.key().as_ref())
+    });
+    vec![quote!(seeds = [#(#seeds),*]), quote!(bump)]
+}
+
+/// The byte slices of PDA seeds, with `account` giving the address bytes of
+/// an account seed.
+fn seed_exprs(seeds: &[Seed], account: impl Fn(&str) -> TokenStream) -> Vec<TokenStream> {
+    seeds
+        .iter()
+        .map(|seed| match seed {
+            Seed::Const(s) => {
+                let s = Literal::byte_string(s.as_bytes());
+                quote!(#s)
+            }
+            Seed::Account(name) => account(name),
+        })
+        .collect()
+}
+
+pub(crate) fn is_associated_token(param: &Param) -> bool {
+    param
+        .annotations
+        .iter()
+        .any(|a| matches!(a, ParamAnnotation::AssociatedToken { .. }))
+}
+
+/// Constraints for a token account parameter, combining `init_if_needed`,
+/// `mut` and `associated_token::*`.
+fn token_account_constraints(param: &Param, writable: bool) -> Vec<TokenStream> {
+    let mut constraints = Vec::new();
+    for annotation in &param.annotations {
+        if let ParamAnnotation::InitIfNeeded { payer } = annotation {
+            let payer = ident(payer);
+            constraints.push(quote!(init_if_needed));
+            constraints.push(quote!(payer = #payer));
+        }
     }
+    if constraints.is_empty() && writable {
+        constraints.push(quote!(mut));
+    }
+    for annotation in &param.annotations {
+        if let ParamAnnotation::AssociatedToken { mint, authority } = annotation {
+            let mint = ident(mint);
+            let authority = ident(authority);
+            constraints.push(quote!(associated_token::mint = #mint));
+            constraints.push(quote!(associated_token::authority = #authority));
+        }
+    }
+    constraints
 }
 
-fn generate_statement(stmt: &Statement, context_name: &str) -> String {
+/// Generate a module with the program ID, an `Id` marker type for
+/// `Program<'info, _>`, and one invoke helper per IDL instruction. The
+/// helpers take the seeds of the PDAs the program signs for.
+fn generate_cpi_module(import: &ImportedProgram) -> TokenStream {
+    let doc = format!(" CPI helpers for the `{}` program.", import.name);
+    let module = ident(&import.module);
+    let name = ident(&import.name);
+    let address = &import.idl.address;
+
+    let functions = import.idl.instructions.iter().map(|ix| {
+        let function = ident(&ix.name);
+        let accounts: Vec<Ident> = ix.accounts.iter().map(|a| ident(&a.name)).collect();
+        let arg_names: Vec<Ident> = ix.args.iter().map(|(name, _)| ident(name)).collect();
+        let arg_types = ix.args.iter().map(|(_, ty)| rust_type(&ty.to_rust_type()));
+        let discriminator = bytes(&ix.discriminator);
+        let data = if ix.args.is_empty() {
+            quote!(let data = vec![#discriminator];)
+        } else {
+            quote!(let mut data = vec![#discriminator];)
+        };
+        let metas = ix.accounts.iter().map(|account| {
+            let name = ident(&account.name);
+            let ctor = if account.writable {
+                quote!(new)
+            } else {
+                quote!(new_readonly)
+            };
+            let signer = account.signer;
+            quote!(AccountMeta::#ctor(#name.key(), #signer))
+        });
+
+        quote! {
+            pub fn #function<'info>(
+                program: AccountInfo<'info>,
+                #(#accounts: AccountInfo<'info>,)*
+                #(#arg_names: #arg_types,)*
+                signer_seeds: &[&[&[u8]]],
+            ) -> Result<()> {
+                #data
+                #(
+                    AnchorSerialize::serialize(&#arg_names, &mut data)
+                        .map_err(|_| error!(anchor_lang::error::ErrorCode::InstructionDidNotSerialize))?;
+                )*
+                let ix = Instruction {
+                    program_id: ID,
+                    accounts: vec![#(#metas),*],
+                    data,
+                };
+                invoke_signed(&ix, &[#(#accounts,)* program], signer_seeds)?;
+                Ok(())
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        pub mod #module {
+            use super::*;
+            use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+            use anchor_lang::solana_program::program::invoke_signed;
+
+            declare_id!(#address);
+
+            #[derive(Clone)]
+            pub struct #name;
+
+            impl anchor_lang::Id for #name {
+                fn id() -> Pubkey {
+                    ID
+                }
+            }
+
+            #(#functions)*
+        }
+    }
+}
+
+fn generate_statement(stmt: &Statement, hir: &Hir, instruction: &Instruction) -> TokenStream {
     match stmt {
         Statement::InitAccount { .. } => {
             // Init is handled in the context struct via #[account(init)]
-            // No code needed here
-            String::new()
+            quote!()
+        }
+        Statement::Close { .. } => {
+            // Anchor closes the account after the body via #[account(close)]
+            quote!()
         }
         Statement::Require { condition, message } => {
-            let cond_str = generate_expr(condition, context_name);
-            if let Some(msg) = message {
-                format!("        require!({}, {});\n", cond_str, msg)
-            } else {
-                format!("        require!({});\n", cond_str)
-            }
+            let condition = generate_value(condition, hir, instruction);
+            let error = match message.as_deref().and_then(|msg| hir.error_for(msg)) {
+                Some(error) => {
+                    let name = ident(&error.name);
+                    quote!(ErrorCode::#name)
+                }
+                None => quote!(anchor_lang::error::ErrorCode::RequireViolated),
+            };
+            quote!(require!(#condition, #error);)
+        }
+        Statement::Emit { event, fields } => {
+            let event = ident(event);
+            let fields = event_fields(fields, |value| generate_value(value, hir, instruction));
+            quote!(emit!(#event { #(#fields),* });)
         }
         Statement::Assign { target, value } => {
-            let target_str = generate_expr(target, context_name);
-            let value_str = generate_expr(value, context_name);
-            format!("        {} = {};\n", target_str, value_str)
+            let target = generate_expr(target, hir, instruction);
+            let value = generate_value(value, hir, instruction);
+            quote!(#target = #value;)
+        }
+        Statement::Expr(Expr::MethodCall {
+            object,
+            method,
+            args,
+        }) if matches!(object.as_ref(), Expr::Ident(name) if hir.import(name).is_some()) => {
+            let module = match object.as_ref() {
+                Expr::Ident(name) => name,
+                _ => unreachable!(),
+            };
+            let program = format_ident!("{}_program", module);
+            let signers = hir
+                .cpi_pda_signers(instruction, object, method, args)
+                .into_iter()
+                .map(|param| {
+                    let seeds = seed_exprs(seeds(param).unwrap_or_default(), |name| {
+                        let name = ident(name);
+                        quote!(ctx.accounts.#name.key().as_ref())
+                    });
+                    let name = ident(&param.name);
+                    quote!(&[#(#seeds,)* &[ctx.bumps.#name]])
+                });
+            let args = args.iter().map(|arg| match arg {
+                Expr::Ident(name) if is_account_param(instruction, name) => {
+                    let name = ident(name);
+                    quote!(ctx.accounts.#name.to_account_info())
+                }
+                _ => generate_value(arg, hir, instruction),
+            });
+            let module = ident(module);
+            let method = ident(method);
+            quote! {
+                #module::#method(
+                    ctx.accounts.#program.to_account_info(),
+                    #(#args,)*
+                    &[#(#signers),*],
+                )?;
+            }
         }
         Statement::Expr(expr) => {
-            format!("        {};\n", generate_expr(expr, context_name))
+            let expr = generate_value(expr, hir, instruction);
+            quote!(#expr;)
         }
     }
 }
 
-fn generate_expr(expr: &Expr, _context_name: &str) -> String {
+/// Event struct fields, using shorthand when the value is a variable of the
+/// same name.
+pub(crate) fn event_fields(
+    fields: &[(String, Expr)],
+    value: impl Fn(&Expr) -> TokenStream,
+) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .map(|(name, expr)| {
+            let field = ident(name);
+            let value = value(expr);
+            if value.to_string() == *name {
+                quote!(#field)
+            } else {
+                quote!(#field: #value)
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn binary_op(op: &BinOp) -> TokenStream {
+    match op {
+        BinOp::Add => quote!(+),
+        BinOp::Sub => quote!(-),
+        BinOp::Mul => quote!(*),
+        BinOp::Div => quote!(/),
+        BinOp::Mod => quote!(%),
+        BinOp::Eq => quote!(==),
+        BinOp::Ne => quote!(!=),
+        BinOp::Lt => quote!(<),
+        BinOp::Le => quote!(<=),
+        BinOp::Gt => quote!(>),
+        BinOp::Ge => quote!(>=),
+        BinOp::And => quote!(&&),
+        BinOp::Or => quote!(||),
+    }
+}
+
+pub(crate) fn unary_op(op: &UnOp) -> TokenStream {
+    match op {
+        UnOp::Not => quote!(!),
+        UnOp::Neg => quote!(-),
+    }
+}
+
+pub(crate) fn literal(lit: &solx_ast::Literal) -> TokenStream {
+    match lit {
+        solx_ast::Literal::Int(i) if *i < 0 => {
+            let abs = Literal::u64_unsuffixed(i.unsigned_abs());
+            quote!(-#abs)
+        }
+        solx_ast::Literal::Int(i) => {
+            let i = Literal::i64_unsuffixed(*i);
+            quote!(#i)
+        }
+        solx_ast::Literal::UInt(u) => {
+            let u = Literal::u64_unsuffixed(*u);
+            quote!(#u)
+        }
+        solx_ast::Literal::Bool(b) => quote!(#b),
+        solx_ast::Literal::String(s) => quote!(#s),
+    }
+}
+
+/// An arithmetic operation under checked or wrapping arithmetic, as a call
+/// such as `u64::checked_add(a, b).ok_or(ErrorCode::ArithmeticOverflow)?`;
+/// `None` for plain operators. `value` generates the operands.
+pub(crate) fn arithmetic_call(
+    hir: &Hir,
+    instruction: &Instruction,
+    expr: &Expr,
+    value: impl Fn(&Expr) -> TokenStream,
+) -> Option<TokenStream> {
+    let Expr::BinaryOp { op, left, right } = expr else {
+        return None;
+    };
+    let name = match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::Div => "div",
+        BinOp::Mod => "rem",
+        _ => return None,
+    };
+    let prefix = match hir.arithmetic {
+        Arithmetic::Panic => return None,
+        Arithmetic::Checked => "checked",
+        Arithmetic::Wrapping => "wrapping",
+    };
+    let ty = hir
+        .type_of(instruction, expr)
+        .expect("arithmetic is type checked");
+    let ty = rust_type(&ty.to_rust_type());
+    let method = format_ident!("{}_{}", prefix, name);
+    let (left, right) = (value(left), value(right));
+    let call = quote!(#ty::#method(#left, #right));
+    if hir.arithmetic == Arithmetic::Wrapping {
+        return Some(call);
+    }
+    let error = hir
+        .error_for(ARITHMETIC_OVERFLOW)
+        .expect("checked arithmetic has an overflow error");
+    let error = ident(&error.name);
+    Some(quote!(#call.ok_or(ErrorCode::#error)?))
+}
+
+/// The Rust expression that fetches a sysvar inside a handler.
+pub(crate) fn sysvar_getter(sysvar: Sysvar) -> TokenStream {
+    sysvar.getter().parse().expect("valid sysvar getter")
+}
+
+/// Whether `object` names the hand-written `helpers` module, unless a
+/// parameter shadows it.
+pub(crate) fn is_helpers(object: &Expr, instruction: &Instruction) -> bool {
+    match object {
+        Expr::Ident(name) => {
+            name == HELPERS_MODULE && !instruction.params.iter().any(|p| p.name == *name)
+        }
+        _ => false,
+    }
+}
+
+/// Trait in `generated.rs` declaring the program's `extern fn`s, and the type
+/// in the hand-written helpers module that implements it.
+pub const EXTERN_FNS_TRAIT: &str = "ExternFns";
+pub const HELPERS_TYPE: &str = "Helpers";
+
+/// The `extern fn` declarations as a trait for lib.rs to implement, so a
+/// declaration without an implementation fails to compile rather than
+/// panicking on-chain. Empty for programs without `extern fn`s.
+pub(crate) fn extern_fns_trait(hir: &Hir) -> TokenStream {
+    if hir.program.externs.is_empty() {
+        return quote!();
+    }
+    let name = ident(EXTERN_FNS_TRAIT);
+    let fns = hir.program.externs.iter().map(|def| {
+        let docs = doc_attrs(&def.docs);
+        let signature = extern_fn_signature(def);
+        quote!(#docs #signature;)
+    });
+    let doc = format!(
+        " The `extern fn`s declared in program.solx, implemented by hand for `{}::{}` in lib.rs.",
+        HELPERS_MODULE, HELPERS_TYPE
+    );
+    quote! {
+        #[doc = #doc]
+        pub trait #name {
+            #(#fns)*
+        }
+    }
+}
+
+/// `fn name(param: Type, ...) -> Ret` of an `extern fn`.
+pub(crate) fn extern_fn_signature(def: &ExternFn) -> TokenStream {
+    let name = ident(&def.name);
+    let params = def.params.iter().map(|(param, ty)| {
+        let param = ident(param);
+        let ty = rust_type(&ty.to_rust_type());
+        quote!(#param: #ty)
+    });
+    let ret = def.ret.as_ref().map(|ty| {
+        let ty = rust_type(&ty.to_rust_type());
+        quote!(-> #ty)
+    });
+    quote!(fn #name(#(#params),*) #ret)
+}
+
+/// A call of the `extern fn` `method` through its implementation in lib.rs.
+/// Handlers are generated where the trait is in scope.
+pub(crate) fn helper_call(method: &str, args: impl Iterator<Item = TokenStream>) -> TokenStream {
+    let module = ident(HELPERS_MODULE);
+    let helpers = ident(HELPERS_TYPE);
+    let method = ident(method);
+    quote!(#module::#helpers::#method(#(#args),*))
+}
+
+/// Whether `expr` is a variable or field of a type Rust moves, such as
+/// `String` or a struct, which is cloned where it is used as a value.
+pub(crate) fn is_moved(hir: &Hir, instruction: &Instruction, expr: &Expr) -> bool {
+    matches!(expr, Expr::Ident(_) | Expr::FieldAccess { .. })
+        && hir.type_of(instruction, expr).is_ok_and(|ty| !ty.is_copy())
+}
+
+pub(crate) fn is_account_param(instruction: &Instruction, name: &str) -> bool {
+    instruction
+        .params
+        .iter()
+        .any(|p| p.name == name && p.ty.is_account())
+}
+
+/// The sysvar referenced by `expr`, unless a parameter shadows its name.
+pub(crate) fn sysvar_of(expr: &Expr, instruction: &Instruction) -> Option<Sysvar> {
+    match expr {
+        Expr::Ident(name) if !instruction.params.iter().any(|p| p.name == *name) => {
+            Sysvar::from_name(name)
+        }
+        _ => None,
+    }
+}
+
+/// A builtin property of an account in the context. Anchor exposes the
+/// address as `key()` and the rest on the `AccountInfo`.
+fn property_access(property: Property, account: TokenStream) -> TokenStream {
+    match property {
+        Property::Key => quote!(#account.key()),
+        Property::Lamports => quote!(#account.to_account_info().lamports()),
+        Property::Owner => quote!(*#account.to_account_info().owner),
+        Property::DataLen => quote!((#account.to_account_info().data_len() as u64)),
+        Property::IsSigner => quote!(#account.to_account_info().is_signer),
+        Property::IsWritable => quote!(#account.to_account_info().is_writable),
+    }
+}
+
+/// An expression in a position that needs no parentheses, such as the right
+/// hand side of an assignment or a macro argument.
+fn generate_value(expr: &Expr, hir: &Hir, instruction: &Instruction) -> TokenStream {
+    let value = |e: &Expr| generate_value(e, hir, instruction);
+    if let Some(call) = arithmetic_call(hir, instruction, expr, value) {
+        return call;
+    }
+    match expr {
+        Expr::BinaryOp { op, left, right } => {
+            let left = generate_expr(left, hir, instruction);
+            let op = binary_op(op);
+            let right = generate_expr(right, hir, instruction);
+            quote!(#left #op #right)
+        }
+        _ if is_moved(hir, instruction, expr) => {
+            let value = generate_expr(expr, hir, instruction);
+            quote!(#value.clone())
+        }
+        _ => generate_expr(expr, hir, instruction),
+    }
+}
+
+/// An expression as an operand; binary operations are parenthesized.
+fn generate_expr(expr: &Expr, hir: &Hir, instruction: &Instruction) -> TokenStream {
     match expr {
         Expr::Ident(name) => {
-            // Check if it's a context field
-            format!("ctx.accounts.{}", name)
+            // Accounts live in the context, other parameters are handler arguments
+            let ident = ident(name);
+            if is_account_param(instruction, name) {
+                quote!(ctx.accounts.#ident)
+            } else {
+                quote!(#ident)
+            }
         }
         Expr::FieldAccess { object, field } => {
-            let obj_str = generate_expr(object, _context_name);
-            format!("{}.{}", obj_str, field)
-        }
-        Expr::Literal(lit) => match lit {
-            Literal::Int(i) => i.to_string(),
-            Literal::UInt(u) => u.to_string(),
-            Literal::Bool(b) => b.to_string(),
-            Literal::String(s) => format!("\"{}\"", s),
-        },
-        Expr::BinaryOp { op, left, right } => {
-            let left_str = generate_expr(left, _context_name);
-            let right_str = generate_expr(right, _context_name);
-            let op_str = match op {
-                BinOp::Add => "+",
-                BinOp::Sub => "-",
-                BinOp::Mul => "*",
-                BinOp::Div => "/",
-                BinOp::Mod => "%",
-                BinOp::Eq => "==",
-                BinOp::Ne => "!=",
-                BinOp::Lt => "<",
-                BinOp::Le => "<=",
-                BinOp::Gt => ">",
-                BinOp::Ge => ">=",
-                BinOp::And => "&&",
-                BinOp::Or => "||",
-            };
-            format!("({} {} {})", left_str, op_str, right_str)
+            if let Some(property) = property(&hir.program, instruction, object, field) {
+                return property_access(property, generate_expr(object, hir, instruction));
+            }
+            let field = ident(field);
+            match sysvar_of(object, instruction) {
+                Some(sysvar) => {
+                    let getter = sysvar_getter(sysvar);
+                    quote!(#getter.#field)
+                }
+                None => {
+                    let object = generate_expr(object, hir, instruction);
+                    quote!(#object.#field)
+                }
+            }
+        }
+        Expr::Literal(lit) => literal(lit),
+        Expr::BinaryOp { op, .. } if hir.arithmetic != Arithmetic::Panic && op.is_arithmetic() => {
+            generate_value(expr, hir, instruction)
+        }
+        Expr::BinaryOp { .. } => {
+            let value = generate_value(expr, hir, instruction);
+            quote!((#value))
         }
         Expr::UnaryOp { op, operand } => {
-            let op_str = match op {
-                UnOp::Not => "!",
-                UnOp::Neg => "-",
-            };
-            format!("{}{}", op_str, generate_expr(operand, _context_name))
+            let op = unary_op(op);
+            let operand = generate_expr(operand, hir, instruction);
+            quote!(#op #operand)
+        }
+        Expr::MethodCall {
+            object,
+            method,
+            args,
+        } => {
+            if is_helpers(object, instruction) {
+                let args = args.iter().map(|a| generate_value(a, hir, instruction));
+                return helper_call(method, args);
+            }
+            let method = ident(method);
+            match sysvar_of(object, instruction) {
+                // Sysvar methods take a single integer (e.g. `minimum_balance(data_len)`)
+                Some(sysvar) => {
+                    let getter = sysvar_getter(sysvar);
+                    let args = args.iter().map(|a| generate_expr(a, hir, instruction));
+                    quote!(#getter.#method(#(#args as usize),*))
+                }
+                None => {
+                    let object = generate_expr(object, hir, instruction);
+                    let args = args.iter().map(|a| generate_value(a, hir, instruction));
+                    quote!(#object.#method(#(#args),*))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anchor_formatting() {
+        let source = r#"
+program Counter
+
+account CounterState {
+  authority: Pubkey
+  count: u64
+}
+
+instruction increment_by(authority: Signer, state: CounterState, amount: u64) {
+  require state.count + amount > state.count
+  state.count = state.count + amount * 2
+}
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let code = generate_anchor_code(&hir).unwrap();
+
+        assert!(code.contains("pub fn increment_by(ctx: Context<IncrementByContext>, amount: u64)"));
+        assert!(code.contains("pub struct IncrementByContext<'info> {"));
+        assert!(code.contains("(ctx.accounts.state.count + amount) > ctx.accounts.state.count,"));
+        assert!(
+            code.contains("ctx.accounts.state.count = ctx.accounts.state.count + (amount * 2);")
+        );
+
+        let code = generate_anchor_code(&hir.with_arithmetic(Arithmetic::Checked)).unwrap();
+        assert!(code.contains("u64::checked_mul(amount, 2).ok_or(ErrorCode::ArithmeticOverflow)?"));
+        assert!(code.contains("#[msg(\"Arithmetic overflow\")]"));
+    }
+
+    #[test]
+    fn test_invariant_checks_follow_the_body() {
+        let source = r#"
+program Vault
+
+account Vault {
+  total_shares: u64
+  total_deposits: u64
+  invariant total_shares <= total_deposits, "Shares exceed deposits"
+}
+
+instruction deposit(user: Signer, vault: Vault, amount: u64) {
+  vault.total_deposits += amount
+  vault.total_shares += amount
+}
+
+instruction audit(vault: Vault) {
+  require vault.total_deposits > 0
+}
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let code = generate_anchor_code(&hir).unwrap();
+        assert!(!code.contains("SharesExceedDeposits"));
+
+        let code = generate_anchor_code(&hir.with_invariant_checks(true)).unwrap();
+        let check = "require!(\n            ctx.accounts.vault.total_shares <= ctx.accounts.vault.total_deposits,\n            ErrorCode::SharesExceedDeposits\n        );";
+        assert_eq!(code.matches(check).count(), 1, "{}", code);
+        assert!(code.find("vault.total_shares = ").unwrap() < code.find(check).unwrap());
+        assert!(code.contains("#[msg(\"Shares exceed deposits\")]"));
+    }
+
+    #[test]
+    fn test_checked_arithmetic_in_invariant_checks() {
+        let source = r#"
+program Pool
+
+account Pool {
+  a: u64
+  b: u64
+  invariant a + b >= a, "Sum wraps"
+}
+
+instruction set(pool: Pool, a: u64) {
+  pool.a = a
+}
+"#;
+        // The invariant is the only arithmetic, in either builder order
+        let hir = || Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        for hir in [
+            hir()
+                .with_arithmetic(Arithmetic::Checked)
+                .with_invariant_checks(true),
+            hir()
+                .with_invariant_checks(true)
+                .with_arithmetic(Arithmetic::Checked),
+        ] {
+            let code = generate_anchor_code(&hir).unwrap();
+            assert!(code.contains("checked_add"), "{}", code);
+            assert!(code.contains("ErrorCode::ArithmeticOverflow"));
         }
     }
+
+    #[test]
+    fn test_cpi_signed_by_pdas() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("vault.json"),
+            r#"{
+              "address": "Vau1t11111111111111111111111111111111111111",
+              "instructions": [{
+                "name": "deposit",
+                "discriminator": [242, 35, 198, 137, 82, 225, 242, 182],
+                "accounts": [
+                  { "name": "owner", "signer": true },
+                  { "name": "payer", "writable": true, "signer": true }
+                ],
+                "args": [{ "name": "amount", "type": "u64" }]
+              }]
+            }"#,
+        )
+        .unwrap();
+        let source = r#"
+program Router
+
+import program Vault from "vault.json"
+
+account RouterState {
+  total: u64
+}
+
+instruction route(user: Signer, state: RouterState @seeds("router", user), amount: u64) {
+  vault.deposit(state, user, amount)
+}
+
+instruction forward(user: Signer, amount: u64) {
+  vault.deposit(user, user, amount)
+}
+"#;
+        let hir = Hir::from_ast_in(solx_parser::parse(source).unwrap(), dir.path()).unwrap();
+
+        let code = generate_anchor_code(&hir).unwrap();
+        assert!(code.contains("invoke_signed(&ix, &[owner, payer, program], signer_seeds)?;"));
+        assert!(
+            code.contains(
+                "&[&[b\"router\", ctx.accounts.user.key().as_ref(), &[ctx.bumps.state]]],"
+            ),
+            "{}",
+            code
+        );
+        assert!(
+            code.contains("amount,\n            &[],\n        )?;"),
+            "{}",
+            code
+        );
+
+        let code = generate_native_code(&hir).unwrap();
+        assert!(code.contains("let (state_address, state_bump) ="));
+        assert!(
+            code.contains("&[&[b\"router\", user.key.as_ref(), &[state_bump]]]"),
+            "{}",
+            code
+        );
+
+        // Other accounts cannot sign
+        let source = source.replace(" @seeds(\"router\", user)", "");
+        let err = Hir::from_ast_in(solx_parser::parse(&source).unwrap(), dir.path())
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .ends_with("account owner of vault.deposit must be a Signer or an @seeds account"));
+    }
+
+    #[test]
+    fn test_mut_only_for_written_accounts() {
+        let source = r#"
+program Vault
+
+account Vault {
+  authority: Pubkey
+  total: u64
+}
+
+instruction open(payer: Signer, authority: Signer, vault: Vault) {
+  init account vault: Vault payer payer
+  vault.authority = authority.key
+}
+
+instruction deposit(authority: Signer, vault: Vault, amount: u64) {
+  require vault.authority == authority.key
+  vault.total += amount
+}
+
+instruction audit(authority: Signer, vault: Vault, log: Vault @mut) {
+  require vault.total > 0
+}
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let code = generate_anchor_code(&hir).unwrap();
+        let context = |name: &str| {
+            let start = code.find(&format!("pub struct {}Context", name)).unwrap();
+            code[start..start + code[start..].find('}').unwrap()].to_string()
+        };
+        assert!(context("Open").contains("#[account(mut)]\n    pub payer: Signer<'info>"));
+        assert!(context("Open").contains("    pub authority: Signer<'info>"));
+        assert!(!context("Open").contains("#[account(mut)]\n    pub authority"));
+        assert!(context("Deposit")
+            .contains("    pub authority: Signer<'info>,\n    #[account(mut)]\n    pub vault"));
+        assert!(context("Audit").contains(
+            "    pub vault: Account<'info, Vault>,\n    #[account(mut)]\n    pub log: Account<'info, Vault>"
+        ));
+
+        let audit = &hir.program.instructions[2];
+        let writable: Vec<bool> = hir
+            .instruction_accounts(audit)
+            .iter()
+            .map(|a| a.writable)
+            .collect();
+        assert_eq!(writable, vec![false, false, true]);
+    }
+
+    #[test]
+    fn test_close_accounts() {
+        let source = r#"
+program Escrow
+
+account Offer {
+  maker: Pubkey
+  amount: u64
+}
+
+instruction cancel(maker: Signer, offer: Offer) {
+  require offer.maker == maker.key
+  offer.amount = 0
+  close offer to maker
+}
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let code = generate_anchor_code(&hir).unwrap();
+        assert!(
+            code.contains("#[account(mut)]\n    pub maker: Signer<'info>,\n    #[account(mut, close = maker)]\n    pub offer: Account<'info, Offer>"),
+            "{}",
+            code
+        );
+
+        let code = generate_native_code(&hir).unwrap();
+        // The closed account's data is not written back
+        assert!(!code.contains("offer_data.store(offer)"), "{}", code);
+        assert!(
+            code.contains("**offer.try_borrow_mut_lamports()? = 0;"),
+            "{}",
+            code
+        );
+        assert!(code.contains("offer.realloc(0, false)?;"), "{}", code);
+    }
+
+    #[test]
+    fn test_account_properties() {
+        let source = r#"
+program Registry
+
+account Entry {
+  owner: Pubkey
+  last: Pubkey
+}
+
+instruction touch(user: Signer, entry: Entry) {
+  require user.lamports >= rent.minimum_balance(entry.data_len)
+  require entry.owner == user.key && user.is_signer && entry.is_writable
+  entry.last = entry.key
+}
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        // Macro arguments are printed as written, so compare without spaces
+        let flat = |code: String| code.split_whitespace().collect::<String>();
+
+        let code = flat(generate_anchor_code(&hir).unwrap());
+        assert!(code.contains(
+            "ctx.accounts.user.to_account_info().lamports()>=Rent::get()?\
+             .minimum_balance((ctx.accounts.entry.to_account_info().data_len()asu64)asusize)"
+        ));
+        assert!(code.contains(
+            "((ctx.accounts.entry.owner==ctx.accounts.user.key())\
+             &&ctx.accounts.user.to_account_info().is_signer)\
+             &&ctx.accounts.entry.to_account_info().is_writable"
+        ));
+        assert!(code.contains("ctx.accounts.entry.last=ctx.accounts.entry.key();"));
+
+        let code = flat(native::generate_native_code(&hir).unwrap());
+        assert!(code.contains(
+            "user.lamports()>=Rent::get()?.minimum_balance((entry.data_len()asu64)asusize)"
+        ));
+        assert!(code.contains("((entry_data.owner==*user.key)&&user.is_signer)&&entry.is_writable"));
+        assert!(code.contains("entry_data.last=*entry.key;"));
+    }
+
+    #[test]
+    fn test_structs_and_data_params() {
+        let source = r#"
+program Config
+
+struct Fees {
+  rate: u16
+  recipient: Pubkey
+}
+
+account Settings {
+  fees: Fees
+}
+
+instruction update(authority: Signer, settings: Settings, fees: Fees, memo: Vec<u8>) {
+  settings.fees = fees
+  settings.fees.rate = fees.rate
+}
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let flat = |code: String| code.split_whitespace().collect::<String>();
+
+        let code = flat(generate_anchor_code(&hir).unwrap());
+        assert!(code.contains(
+            "#[derive(AnchorSerialize,AnchorDeserialize,Clone,Debug,Default,PartialEq)]\
+             pubstructFees{pubrate:u16,pubrecipient:Pubkey,}"
+        ));
+        assert!(code.contains("fees:Fees,memo:Vec<u8>)"));
+        // Values that are not Copy are cloned rather than moved
+        assert!(code.contains("ctx.accounts.settings.fees=fees.clone();"));
+        assert!(code.contains("ctx.accounts.settings.fees.rate=fees.rate;"));
+
+        let code = flat(native::generate_native_code(&hir).unwrap());
+        assert!(code.contains("letmemo:Vec<u8>=BorshDeserialize::deserialize(&mutdata)"));
+        assert!(code.contains("settings_data.fees=fees.clone();"));
+    }
 }
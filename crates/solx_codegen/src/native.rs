@@ -0,0 +1,940 @@
+//! Native backend: a plain `solana_program` entrypoint without Anchor.
+//!
+//! The generated program keeps Anchor's wire format (8 byte sighash
+//! discriminators, Borsh data, custom errors from 6000) so the IDL and
+//! generated clients work with either backend. The checks Anchor derives
+//! from `#[account(...)]` constraints are written out explicitly.
+
+use anyhow::Result;
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote};
+use solx_ast::*;
+use solx_hir::accounts::{
+    account_space, close_recipient, seeds, ASSOCIATED_TOKEN_PROGRAM_ID, TOKEN_PROGRAM_ID,
+};
+use solx_hir::idl::sighash;
+use solx_hir::invariants::root;
+use solx_hir::properties::{property, Property};
+use solx_hir::sysvar::Sysvar;
+use solx_hir::{Arithmetic, Hir, ImportedProgram};
+
+use crate::{
+    arithmetic_call, binary_op, bytes, doc_attrs, event_fields, extern_fns_trait, helper_call,
+    ident, is_account_param, is_helpers, is_moved, literal, pretty, rust_type, struct_fields,
+    sysvar_getter, sysvar_of, unary_op,
+};
+
+/// Anchor's `RequireViolated`, used for `require` without a message.
+const REQUIRE_VIOLATED: u32 = 2500;
+
+pub fn generate_native_code(hir: &Hir) -> Result<String> {
+    let program = &hir.program;
+
+    // Generate use statements
+    let mut uses = vec![
+        quote!(
+            use borsh::{BorshDeserialize, BorshSerialize};
+        ),
+        quote!(
+            use solana_program::account_info::{next_account_info, AccountInfo};
+        ),
+        quote!(
+            use solana_program::entrypoint::ProgramResult;
+        ),
+    ];
+    if !hir.imports.is_empty() || program.instructions.iter().any(needs_ata_creation) {
+        uses.push(quote!(
+            use solana_program::instruction::{AccountMeta, Instruction};
+        ));
+    }
+    let inits = || {
+        program
+            .instructions
+            .iter()
+            .flat_map(|i| i.params.iter().map(move |p| (i, p)))
+            .filter(|(i, p)| init_payer(i, &p.name).is_some())
+    };
+    let uses_invoke = program.instructions.iter().any(needs_ata_creation)
+        || inits().any(|(_, p)| seeds(p).is_none());
+    let uses_invoke_signed = !hir.imports.is_empty() || inits().any(|(_, p)| seeds(p).is_some());
+    match (uses_invoke, uses_invoke_signed) {
+        (true, true) => uses.push(quote!(
+            use solana_program::program::{invoke, invoke_signed};
+        )),
+        (true, false) => uses.push(quote!(
+            use solana_program::program::invoke;
+        )),
+        (false, true) => uses.push(quote!(
+            use solana_program::program::invoke_signed;
+        )),
+        (false, false) => {}
+    }
+    uses.push(quote!(
+        use solana_program::program_error::ProgramError;
+    ));
+    uses.push(quote!(
+        use solana_program::pubkey::Pubkey;
+    ));
+    let uses_clock = uses_sysvar(program, Sysvar::Clock);
+    let uses_rent = uses_sysvar(program, Sysvar::Rent) || inits().next().is_some();
+    if uses_clock {
+        uses.push(quote!(
+            use solana_program::clock::Clock;
+        ));
+    }
+    if uses_rent {
+        uses.push(quote!(
+            use solana_program::rent::Rent;
+        ));
+    }
+    if uses_clock || uses_rent {
+        uses.push(quote!(
+            use solana_program::sysvar::Sysvar;
+        ));
+    }
+
+    // Generate CPI modules for imported programs
+    let cpi_modules = hir.imports.iter().map(generate_cpi_module);
+
+    // Generate structs, accounts and events
+    let structs = program.structs.iter().map(generate_struct);
+    let accounts = program
+        .accounts
+        .iter()
+        .map(|account| generate_account(program, account));
+    let events = program.events.iter().map(generate_event);
+    let extern_fns = extern_fns_trait(hir);
+
+    // Generate custom errors from require messages
+    let require_violated = Literal::u32_unsuffixed(REQUIRE_VIOLATED);
+    let errors = if hir.errors.is_empty() {
+        quote!()
+    } else {
+        let variants = hir.errors.iter().map(|error| {
+            let doc = format!(" {}", error.msg);
+            let name = ident(&error.name);
+            let code = Literal::u32_unsuffixed(error.code);
+            quote!(#[doc = #doc] #name = #code)
+        });
+        quote! {
+            #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+            pub enum ErrorCode { #(#variants),* }
+
+            impl From<ErrorCode> for ProgramError {
+                fn from(e: ErrorCode) -> Self {
+                    ProgramError::Custom(e as u32)
+                }
+            }
+        }
+    };
+
+    // Generate entrypoint and dispatch
+    let arms = program.instructions.iter().map(|instruction| {
+        let discriminator = bytes(&sighash("global", &instruction.name));
+        let handler = format_ident!("process_{}", instruction.name);
+        quote!([#discriminator] => #handler(program_id, accounts, data))
+    });
+
+    // Generate instruction handlers
+    let handlers = program
+        .instructions
+        .iter()
+        .map(|instruction| generate_handler(hir, instruction));
+
+    pretty(quote! {
+        #(#uses)*
+        #(#cpi_modules)*
+        #(#structs)*
+        #(#accounts)*
+        #(#events)*
+        #extern_fns
+
+        /// Error code of a `require` without a message, as in Anchor.
+        pub const REQUIRE_VIOLATED: u32 = #require_violated;
+
+        #errors
+
+        #[cfg(not(feature = "no-entrypoint"))]
+        solana_program::entrypoint!(process_instruction);
+
+        pub fn process_instruction(
+            program_id: &Pubkey,
+            accounts: &[AccountInfo],
+            instruction_data: &[u8],
+        ) -> ProgramResult {
+            if instruction_data.len() < 8 {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            let (discriminator, data) = instruction_data.split_at(8);
+            match discriminator {
+                #(#arms,)*
+                _ => Err(ProgramError::InvalidInstructionData),
+            }
+        }
+
+        #(#handlers)*
+    })
+}
+
+fn generate_struct(def: &StructDef) -> TokenStream {
+    let docs = doc_attrs(&def.docs);
+    let name = ident(&def.name);
+    let fields = struct_fields(&def.fields);
+
+    quote! {
+        #docs
+        #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+        pub struct #name { #(#fields),* }
+    }
+}
+
+fn generate_account(program: &Program, account: &AccountDef) -> TokenStream {
+    let docs = doc_attrs(&account.docs);
+    let name = ident(&account.name);
+    let fields = struct_fields(&account.fields);
+    let discriminator = bytes(&sighash("account", &account.name));
+    let space = Literal::u64_unsuffixed(account_space(program, account));
+
+    quote! {
+        #docs
+        #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+        pub struct #name { #(#fields),* }
+
+        impl #name {
+            pub const DISCRIMINATOR: [u8; 8] = [#discriminator];
+            /// Bytes allocated by `init`, including the discriminator.
+            pub const SPACE: usize = #space;
+
+            pub fn load(account: &AccountInfo) -> Result<Self, ProgramError> {
+                let data = account.try_borrow_data()?;
+                if data.len() < 8 || data[..8] != Self::DISCRIMINATOR {
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                Self::deserialize(&mut &data[8..]).map_err(|_| ProgramError::InvalidAccountData)
+            }
+
+            pub fn store(&self, account: &AccountInfo) -> ProgramResult {
+                let mut buf = Self::DISCRIMINATOR.to_vec();
+                self.serialize(&mut buf).map_err(|_| ProgramError::InvalidAccountData)?;
+                let mut data = account.try_borrow_mut_data()?;
+                if buf.len() > data.len() {
+                    return Err(ProgramError::AccountDataTooSmall);
+                }
+                data[..buf.len()].copy_from_slice(&buf);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Events are logged like Anchor's `emit!`: discriminator plus Borsh data
+/// through `sol_log_data`.
+fn generate_event(event: &EventDef) -> TokenStream {
+    let docs = doc_attrs(&event.docs);
+    let name = ident(&event.name);
+    let fields = struct_fields(&event.fields);
+    let discriminator = bytes(&sighash("event", &event.name));
+
+    quote! {
+        #docs
+        #[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+        pub struct #name { #(#fields),* }
+
+        impl #name {
+            pub const DISCRIMINATOR: [u8; 8] = [#discriminator];
+
+            pub fn emit(&self) -> ProgramResult {
+                let mut data = Self::DISCRIMINATOR.to_vec();
+                self.serialize(&mut data).map_err(|_| ProgramError::InvalidAccountData)?;
+                solana_program::log::sol_log_data(&[&data]);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn generate_handler(hir: &Hir, instruction: &Instruction) -> TokenStream {
+    let accounts = hir.instruction_accounts(instruction);
+    let args: Vec<(&str, &Type)> = instruction
+        .params
+        .iter()
+        .filter(|p| !p.ty.is_account())
+        .map(|p| (p.name.as_str(), &p.ty))
+        .collect();
+    let uses_program_id = instruction
+        .params
+        .iter()
+        .any(|p| matches!(p.ty, Type::Account(_)));
+
+    let docs = doc_attrs(&instruction.docs);
+    let name = format_ident!("process_{}", instruction.name);
+    let program_id_param = if uses_program_id {
+        quote!(program_id)
+    } else {
+        quote!(_program_id)
+    };
+    let accounts_param = if accounts.is_empty() {
+        quote!(_accounts)
+    } else {
+        quote!(accounts)
+    };
+    let data_param = if args.is_empty() {
+        quote!(_data)
+    } else {
+        quote!(data)
+    };
+    let mut body = Vec::new();
+
+    // Arguments, in declaration order
+    if !args.is_empty() {
+        body.push(quote!(let mut data = data;));
+        for (name, ty) in &args {
+            let name = ident(name);
+            let ty = rust_type(&ty.to_rust_type());
+            body.push(quote! {
+                let #name: #ty = BorshDeserialize::deserialize(&mut data)
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+            });
+        }
+    }
+
+    // Accounts, in the same order as the Anchor context struct
+    if !accounts.is_empty() {
+        body.push(quote!(let accounts_iter = &mut accounts.iter();));
+        for account in &accounts {
+            let name = ident(&account.name);
+            body.push(quote!(let #name = next_account_info(accounts_iter)?;));
+        }
+    }
+
+    // Signer, writable and program address checks
+    for account in &accounts {
+        let name = ident(&account.name);
+        if account.signer {
+            body.push(check(
+                quote!(!#name.is_signer),
+                quote!(ProgramError::MissingRequiredSignature),
+            ));
+        }
+        if account.writable {
+            body.push(check(
+                quote!(!#name.is_writable),
+                quote!(ProgramError::InvalidArgument),
+            ));
+        }
+        if let Some(address) = &account.address {
+            let address = pubkey(address);
+            body.push(check(
+                quote!(#name.key != &#address),
+                quote!(ProgramError::IncorrectProgramId),
+            ));
+        }
+    }
+
+    // Constraints on account parameters
+    let token_program_id = pubkey(TOKEN_PROGRAM_ID);
+    for param in &instruction.params {
+        let name = ident(&param.name);
+        let address = format_ident!("{}_address", param.name);
+        match &param.ty {
+            Type::Account(_) => {
+                if let Some(seeds) = seeds(param) {
+                    // The bump is only needed to sign for `init` or a CPI
+                    let signs = init_payer(instruction, &param.name).is_some()
+                        || hir.signs_cpi(instruction, param);
+                    let bump = if signs {
+                        let bump = format_ident!("{}_bump", param.name);
+                        quote!(#bump)
+                    } else {
+                        quote!(_)
+                    };
+                    let seeds = seed_exprs(seeds);
+                    body.push(quote! {
+                        let (#address, #bump) =
+                            Pubkey::find_program_address(&[#(#seeds),*], program_id);
+                    });
+                    body.push(check(
+                        quote!(#name.key != &#address),
+                        quote!(ProgramError::InvalidSeeds),
+                    ));
+                }
+                if init_payer(instruction, &param.name).is_none() {
+                    body.push(check(
+                        quote!(#name.owner != program_id),
+                        quote!(ProgramError::IllegalOwner),
+                    ));
+                }
+            }
+            Type::TokenAccount => {
+                let mut annotations = param.annotations.iter();
+                if let Some(ParamAnnotation::AssociatedToken { mint, authority }) = annotations
+                    .clone()
+                    .find(|a| matches!(a, ParamAnnotation::AssociatedToken { .. }))
+                {
+                    let mint_ident = ident(mint);
+                    let authority_ident = ident(authority);
+                    let ata_program_id = pubkey(ASSOCIATED_TOKEN_PROGRAM_ID);
+                    body.push(quote! {
+                        let (#address, _) = Pubkey::find_program_address(
+                            &[
+                                #authority_ident.key.as_ref(),
+                                #token_program_id.as_ref(),
+                                #mint_ident.key.as_ref(),
+                            ],
+                            &#ata_program_id,
+                        );
+                    });
+                    body.push(check(
+                        quote!(#name.key != &#address),
+                        quote!(ProgramError::InvalidSeeds),
+                    ));
+                    if let Some(ParamAnnotation::InitIfNeeded { payer }) =
+                        annotations.find(|a| matches!(a, ParamAnnotation::InitIfNeeded { .. }))
+                    {
+                        body.push(create_associated_token_account(
+                            &param.name,
+                            payer,
+                            authority,
+                            mint,
+                        ));
+                    }
+                }
+                body.push(check(
+                    quote!(#name.owner != &#token_program_id),
+                    quote!(ProgramError::IllegalOwner),
+                ));
+            }
+            Type::Mint => {
+                body.push(check(
+                    quote!(#name.owner != &#token_program_id),
+                    quote!(ProgramError::IllegalOwner),
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    // Create `init` accounts and load the others
+    for param in &instruction.params {
+        let Type::Account(account_name) = &param.ty else {
+            continue;
+        };
+        let name = ident(&param.name);
+        let ty = ident(account_name);
+        let data = format_ident!("{}_data", param.name);
+        let mutable =
+            init_payer(instruction, &param.name).is_some() || assigns_to(instruction, &param.name);
+        let binding = if mutable {
+            quote!(mut #data)
+        } else {
+            quote!(#data)
+        };
+        match init_payer(instruction, &param.name) {
+            Some(payer) => {
+                let payer = ident(payer);
+                let lamports = format_ident!("{}_lamports", param.name);
+                body.push(quote! {
+                    let #lamports = Rent::get()?.minimum_balance(#ty::SPACE);
+                });
+                let create = quote! {
+                    &solana_program::system_instruction::create_account(
+                        #payer.key,
+                        #name.key,
+                        #lamports,
+                        #ty::SPACE as u64,
+                        program_id,
+                    ),
+                    &[#payer.clone(), #name.clone(), system_program.clone()]
+                };
+                match seeds(param) {
+                    Some(seeds) => {
+                        let seeds = seed_exprs(seeds);
+                        let bump = format_ident!("{}_bump", param.name);
+                        body.push(quote! {
+                            invoke_signed(#create, &[&[#(#seeds,)* &[#bump]]])?;
+                        });
+                    }
+                    None => body.push(quote!(invoke(#create)?;)),
+                }
+                body.push(quote!(let #binding = #ty::default();));
+            }
+            None if mutable || reads_data(hir, instruction, &param.name) => {
+                body.push(quote!(let #binding = #ty::load(#name)?;));
+            }
+            // Still checks the discriminator
+            None => body.push(quote!(#ty::load(#name)?;)),
+        }
+    }
+
+    // Generate body, then check the invariants of the accounts it wrote
+    for stmt in instruction
+        .body
+        .iter()
+        .chain(&hir.invariant_checks(instruction))
+    {
+        body.push(generate_statement(stmt, hir, instruction));
+    }
+
+    // Write back modified account data
+    for param in &instruction.params {
+        if matches!(param.ty, Type::Account(_))
+            && close_recipient(instruction, &param.name).is_none()
+            && (init_payer(instruction, &param.name).is_some()
+                || assigns_to(instruction, &param.name))
+        {
+            let name = ident(&param.name);
+            let data = format_ident!("{}_data", param.name);
+            body.push(quote!(#data.store(#name)?;));
+        }
+    }
+
+    // Close accounts like Anchor: move the lamports, hand the account back
+    // to the system program and free its data
+    for param in &instruction.params {
+        if let Some(recipient) = close_recipient(instruction, &param.name) {
+            let name = ident(&param.name);
+            let recipient = ident(recipient);
+            body.push(quote! {
+                **#recipient.try_borrow_mut_lamports()? = #recipient
+                    .lamports()
+                    .checked_add(#name.lamports())
+                    .ok_or(ProgramError::ArithmeticOverflow)?;
+                **#name.try_borrow_mut_lamports()? = 0;
+                #name.assign(&solana_program::system_program::ID);
+                #name.realloc(0, false)?;
+            });
+        }
+    }
+
+    quote! {
+        #docs
+        fn #name(
+            #program_id_param: &Pubkey,
+            #accounts_param: &[AccountInfo],
+            #data_param: &[u8],
+        ) -> ProgramResult {
+            #(#body)*
+            Ok(())
+        }
+    }
+}
+
+fn check(condition: TokenStream, error: TokenStream) -> TokenStream {
+    quote! {
+        if #condition {
+            return Err(#error);
+        }
+    }
+}
+
+fn pubkey(address: &str) -> TokenStream {
+    quote!(solana_program::pubkey!(#address))
+}
+
+fn seed_exprs(seeds: &[Seed]) -> Vec<TokenStream> {
+    seeds
+        .iter()
+        .map(|seed| match seed {
+            Seed::Const(s) => {
+                let s = Literal::byte_string(s.as_bytes());
+                quote!(#s)
+            }
+            Seed::Account(name) => {
+                let name = ident(name);
+                quote!(#name.key.as_ref())
+            }
+        })
+        .collect()
+}
+
+/// `CreateIdempotent` on the associated token program when the account does
+/// not exist yet.
+fn create_associated_token_account(
+    name: &str,
+    payer: &str,
+    authority: &str,
+    mint: &str,
+) -> TokenStream {
+    let name = ident(name);
+    let payer = ident(payer);
+    let authority = ident(authority);
+    let mint = ident(mint);
+    quote! {
+        if #name.data_is_empty() {
+            invoke(
+                &Instruction {
+                    program_id: *associated_token_program.key,
+                    accounts: vec![
+                        AccountMeta::new(*#payer.key, true),
+                        AccountMeta::new(*#name.key, false),
+                        AccountMeta::new_readonly(*#authority.key, false),
+                        AccountMeta::new_readonly(*#mint.key, false),
+                        AccountMeta::new_readonly(*system_program.key, false),
+                        AccountMeta::new_readonly(*token_program.key, false),
+                    ],
+                    data: vec![1],
+                },
+                &[
+                    #payer.clone(),
+                    #name.clone(),
+                    #authority.clone(),
+                    #mint.clone(),
+                    system_program.clone(),
+                    token_program.clone(),
+                    associated_token_program.clone(),
+                ],
+            )?;
+        }
+    }
+}
+
+/// Generate a module with the program ID and one invoke helper per IDL
+/// instruction, taking the seeds of the PDAs the program signs for.
+fn generate_cpi_module(import: &ImportedProgram) -> TokenStream {
+    let doc = format!(" CPI helpers for the `{}` program.", import.name);
+    let module = ident(&import.module);
+    let address = pubkey(&import.idl.address);
+
+    let functions = import.idl.instructions.iter().map(|ix| {
+        let function = ident(&ix.name);
+        let accounts: Vec<_> = ix.accounts.iter().map(|a| ident(&a.name)).collect();
+        let arg_names: Vec<_> = ix.args.iter().map(|(name, _)| ident(name)).collect();
+        let arg_types = ix.args.iter().map(|(_, ty)| rust_type(&ty.to_rust_type()));
+        let discriminator = bytes(&ix.discriminator);
+        let data = if ix.args.is_empty() {
+            quote!(let data = vec![#discriminator];)
+        } else {
+            quote!(let mut data = vec![#discriminator];)
+        };
+        let metas = ix.accounts.iter().map(|account| {
+            let name = ident(&account.name);
+            let ctor = if account.writable {
+                quote!(new)
+            } else {
+                quote!(new_readonly)
+            };
+            let signer = account.signer;
+            quote!(AccountMeta::#ctor(*#name.key, #signer))
+        });
+
+        quote! {
+            pub fn #function<'a>(
+                program: &AccountInfo<'a>,
+                #(#accounts: &AccountInfo<'a>,)*
+                #(#arg_names: #arg_types,)*
+                signer_seeds: &[&[&[u8]]],
+            ) -> ProgramResult {
+                #data
+                #(
+                    BorshSerialize::serialize(&#arg_names, &mut data)
+                        .map_err(|_| ProgramError::InvalidInstructionData)?;
+                )*
+                let ix = Instruction {
+                    program_id: ID,
+                    accounts: vec![#(#metas),*],
+                    data,
+                };
+                invoke_signed(&ix, &[#(#accounts.clone(),)* program.clone()], signer_seeds)
+            }
+        }
+    });
+
+    quote! {
+        #[doc = #doc]
+        pub mod #module {
+            use super::*;
+
+            pub const ID: Pubkey = #address;
+
+            #(#functions)*
+        }
+    }
+}
+
+fn generate_statement(stmt: &Statement, hir: &Hir, instruction: &Instruction) -> TokenStream {
+    match stmt {
+        Statement::InitAccount { .. } => {
+            // The account is created before the body runs
+            quote!()
+        }
+        Statement::Close { .. } => {
+            // The account is closed after the body runs
+            quote!()
+        }
+        Statement::Require { condition, message } => {
+            let condition = generate_value(condition, hir, instruction);
+            let fail = match message.as_deref().and_then(|msg| hir.error_for(msg)) {
+                Some(error) => {
+                    let msg = &error.msg;
+                    let name = ident(&error.name);
+                    quote! {
+                        solana_program::msg!(#msg);
+                        return Err(ErrorCode::#name.into());
+                    }
+                }
+                None => quote!(return Err(ProgramError::Custom(REQUIRE_VIOLATED));),
+            };
+            quote! {
+                if !(#condition) {
+                    #fail
+                }
+            }
+        }
+        Statement::Emit { event, fields } => {
+            let event = ident(event);
+            let fields = event_fields(fields, |value| generate_value(value, hir, instruction));
+            quote!(#event { #(#fields),* }.emit()?;)
+        }
+        Statement::Assign { target, value } => {
+            let target = generate_expr(target, hir, instruction);
+            let value = generate_value(value, hir, instruction);
+            quote!(#target = #value;)
+        }
+        Statement::Expr(Expr::MethodCall {
+            object,
+            method,
+            args,
+        }) if matches!(object.as_ref(), Expr::Ident(name) if hir.import(name).is_some()) => {
+            let module = match object.as_ref() {
+                Expr::Ident(name) => name,
+                _ => unreachable!(),
+            };
+            let program = format_ident!("{}_program", module);
+            let signers = hir
+                .cpi_pda_signers(instruction, object, method, args)
+                .into_iter()
+                .map(|param| {
+                    let seeds = seed_exprs(seeds(param).unwrap_or_default());
+                    let bump = format_ident!("{}_bump", param.name);
+                    quote!(&[#(#seeds,)* &[#bump]])
+                });
+            let args = args.iter().map(|arg| generate_value(arg, hir, instruction));
+            let module = ident(module);
+            let method = ident(method);
+            quote!(#module::#method(#program, #(#args,)* &[#(#signers),*])?;)
+        }
+        Statement::Expr(expr) => {
+            let expr = generate_value(expr, hir, instruction);
+            quote!(#expr;)
+        }
+    }
+}
+
+/// An expression in a position that needs no parentheses, such as the right
+/// hand side of an assignment or a call argument.
+fn generate_value(expr: &Expr, hir: &Hir, instruction: &Instruction) -> TokenStream {
+    let value = |e: &Expr| generate_value(e, hir, instruction);
+    if let Some(call) = arithmetic_call(hir, instruction, expr, value) {
+        return call;
+    }
+    match expr {
+        Expr::BinaryOp { op, left, right } => {
+            let left = generate_expr(left, hir, instruction);
+            let op = binary_op(op);
+            let right = generate_expr(right, hir, instruction);
+            quote!(#left #op #right)
+        }
+        _ if is_moved(hir, instruction, expr) => {
+            let value = generate_expr(expr, hir, instruction);
+            quote!(#value.clone())
+        }
+        _ => generate_expr(expr, hir, instruction),
+    }
+}
+
+/// An expression as an operand; binary operations are parenthesized.
+fn generate_expr(expr: &Expr, hir: &Hir, instruction: &Instruction) -> TokenStream {
+    match expr {
+        // Accounts are `AccountInfo`s, other parameters are deserialized arguments
+        Expr::Ident(name) => {
+            let name = ident(name);
+            quote!(#name)
+        }
+        Expr::FieldAccess { object, field } => {
+            let field_ident = ident(field);
+            if let Some(sysvar) = sysvar_of(object, instruction) {
+                let getter = sysvar_getter(sysvar);
+                return quote!(#getter.#field_ident);
+            }
+            if let Some(property) = property(&hir.program, instruction, object, field) {
+                return property_access(property, generate_expr(object, hir, instruction));
+            }
+            match object.as_ref() {
+                Expr::Ident(name) if is_account_param(instruction, name) => {
+                    let data = format_ident!("{}_data", name);
+                    quote!(#data.#field_ident)
+                }
+                _ => {
+                    let object = generate_expr(object, hir, instruction);
+                    quote!(#object.#field_ident)
+                }
+            }
+        }
+        Expr::Literal(lit) => literal(lit),
+        Expr::BinaryOp { op, .. } if hir.arithmetic != Arithmetic::Panic && op.is_arithmetic() => {
+            generate_value(expr, hir, instruction)
+        }
+        Expr::BinaryOp { .. } => {
+            let value = generate_value(expr, hir, instruction);
+            quote!((#value))
+        }
+        Expr::UnaryOp { op, operand } => {
+            let op = unary_op(op);
+            let operand = generate_expr(operand, hir, instruction);
+            quote!(#op #operand)
+        }
+        Expr::MethodCall {
+            object,
+            method,
+            args,
+        } => {
+            if is_helpers(object, instruction) {
+                let args = args.iter().map(|a| generate_value(a, hir, instruction));
+                return helper_call(method, args);
+            }
+            let method = ident(method);
+            match sysvar_of(object, instruction) {
+                // Sysvar methods take a single integer (e.g. `minimum_balance(data_len)`)
+                Some(sysvar) => {
+                    let getter = sysvar_getter(sysvar);
+                    let args = args.iter().map(|a| generate_expr(a, hir, instruction));
+                    quote!(#getter.#method(#(#args as usize),*))
+                }
+                None => {
+                    let object = generate_expr(object, hir, instruction);
+                    let args = args.iter().map(|a| generate_value(a, hir, instruction));
+                    quote!(#object.#method(#(#args),*))
+                }
+            }
+        }
+    }
+}
+
+/// A builtin property of an `AccountInfo`.
+fn property_access(property: Property, account: TokenStream) -> TokenStream {
+    match property {
+        Property::Key => quote!(*#account.key),
+        Property::Lamports => quote!(#account.lamports()),
+        Property::Owner => quote!(*#account.owner),
+        Property::DataLen => quote!((#account.data_len() as u64)),
+        Property::IsSigner => quote!(#account.is_signer),
+        Property::IsWritable => quote!(#account.is_writable),
+    }
+}
+
+/// Whether `field` is a data field of the program account parameter `name`,
+/// as opposed to an `AccountInfo` field such as `key`.
+fn has_data_field(program: &Program, instruction: &Instruction, name: &str, field: &str) -> bool {
+    let Some(Type::Account(account_name)) = instruction
+        .params
+        .iter()
+        .find(|p| p.name == name)
+        .map(|p| &p.ty)
+    else {
+        return false;
+    };
+    program
+        .accounts
+        .iter()
+        .find(|a| a.name == *account_name)
+        .is_some_and(|a| a.fields.iter().any(|f| f.name == field))
+}
+
+/// Whether the body reads a data field of the account parameter `name`.
+fn reads_data(hir: &Hir, instruction: &Instruction, name: &str) -> bool {
+    let mut found = false;
+    for stmt in &instruction.body {
+        for expr in stmt.exprs() {
+            expr.visit(&mut |e| {
+                if let Expr::FieldAccess { object, field } = e {
+                    found |= matches!(object.as_ref(), Expr::Ident(n) if n == name)
+                        && has_data_field(&hir.program, instruction, name, field);
+                }
+            });
+        }
+    }
+    found
+}
+
+/// The payer of an `init` statement for `name`, if any.
+fn init_payer<'a>(instruction: &'a Instruction, name: &str) -> Option<&'a str> {
+    instruction.body.iter().find_map(|s| match s {
+        Statement::InitAccount {
+            var_name, payer, ..
+        } if var_name == name => Some(payer.as_str()),
+        _ => None,
+    })
+}
+
+fn needs_ata_creation(instruction: &Instruction) -> bool {
+    instruction.params.iter().any(|p| {
+        p.annotations
+            .iter()
+            .any(|a| matches!(a, ParamAnnotation::InitIfNeeded { .. }))
+    })
+}
+
+/// Whether the body assigns to a field of the account parameter `name`.
+fn assigns_to(instruction: &Instruction, name: &str) -> bool {
+    instruction.body.iter().any(|s| match s {
+        Statement::Assign {
+            target: target @ Expr::FieldAccess { .. },
+            ..
+        } => root(target) == Some(name),
+        _ => false,
+    })
+}
+
+fn uses_sysvar(program: &Program, sysvar: Sysvar) -> bool {
+    program.instructions.iter().any(|instruction| {
+        let mut found = false;
+        for stmt in &instruction.body {
+            for expr in stmt.exprs() {
+                expr.visit(&mut |e| {
+                    found |= sysvar_of(e, instruction) == Some(sysvar);
+                });
+            }
+        }
+        found
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_counter() {
+        let source = r#"
+program Counter
+
+account CounterState {
+  authority: Pubkey
+  count: u64
+}
+
+instruction initialize(authority: Signer, state: CounterState @seeds("counter", authority)) {
+  init account state: CounterState payer authority
+  state.authority = authority.key
+}
+
+instruction increment(authority: Signer, state: CounterState @seeds("counter", authority)) {
+  require state.authority == authority.key, "Unauthorized"
+  state.count += 1
+}
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let code = generate_native_code(&hir).unwrap();
+
+        assert!(code.contains(
+            "[175, 175, 109, 31, 13, 152, 155, 237] => {\n            process_initialize("
+        ));
+        assert!(code.contains("return Err(ProgramError::MissingRequiredSignature);"));
+        assert!(code.contains("&[&[b\"counter\", authority.key.as_ref(), &[state_bump]]]"));
+        assert!(code.contains("if state.owner != program_id {"));
+        assert!(code.contains("let mut state_data = CounterState::load(state)?;"));
+        assert!(code.contains("return Err(ErrorCode::Unauthorized.into());"));
+        assert!(code.contains("state_data.count = state_data.count + 1;"));
+        assert!(code.contains("state_data.store(state)?;"));
+    }
+}
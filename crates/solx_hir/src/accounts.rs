@@ -0,0 +1,261 @@
+//! The full account list of an instruction as it appears on the wire: the
+//! account parameters followed by the programs their constraints require.
+
+use solx_ast::*;
+
+use crate::invariants::root;
+use crate::Hir;
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionAccount {
+    pub name: String,
+    pub writable: bool,
+    pub signer: bool,
+    /// Fixed address, for program accounts
+    pub address: Option<String>,
+}
+
+impl Hir {
+    pub fn instruction_accounts(&self, instruction: &Instruction) -> Vec<InstructionAccount> {
+        let init_targets: Vec<&str> = instruction
+            .body
+            .iter()
+            .filter_map(|s| match s {
+                Statement::InitAccount { var_name, .. } => Some(var_name.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        let mut accounts = Vec::new();
+        for param in &instruction.params {
+            let signer = match &param.ty {
+                Type::Signer => true,
+                // Keypair accounts created with `init` sign for their own
+                // creation; PDAs are signed for by the program
+                Type::Account(_) => {
+                    init_targets.contains(&param.name.as_str()) && seeds(param).is_none()
+                }
+                Type::TokenAccount | Type::Mint => false,
+                _ => continue,
+            };
+            let writable = self.is_writable(instruction, param);
+            accounts.push(InstructionAccount {
+                name: param.name.clone(),
+                writable,
+                signer,
+                address: None,
+            });
+        }
+
+        let program = |name: &str, address: &str| InstructionAccount {
+            name: name.to_string(),
+            writable: false,
+            signer: false,
+            address: Some(address.to_string()),
+        };
+        let annotated = |pred: fn(&ParamAnnotation) -> bool| {
+            instruction
+                .params
+                .iter()
+                .any(|p| p.annotations.iter().any(pred))
+        };
+
+        if !init_targets.is_empty()
+            || annotated(|a| matches!(a, ParamAnnotation::InitIfNeeded { .. }))
+        {
+            accounts.push(program("system_program", SYSTEM_PROGRAM_ID));
+        }
+        if instruction
+            .params
+            .iter()
+            .any(|p| p.ty == Type::TokenAccount)
+        {
+            accounts.push(program("token_program", TOKEN_PROGRAM_ID));
+        }
+        if annotated(|a| matches!(a, ParamAnnotation::AssociatedToken { .. })) {
+            accounts.push(program(
+                "associated_token_program",
+                ASSOCIATED_TOKEN_PROGRAM_ID,
+            ));
+        }
+        for import in &self.imports {
+            if calls_program(instruction, &import.module) {
+                accounts.push(program(
+                    &format!("{}_program", import.module),
+                    &import.idl.address,
+                ));
+            }
+        }
+
+        accounts
+    }
+
+    /// Whether an account parameter is writable: annotated `@mut`, created
+    /// with `init` or `@init_if_needed`, paying for a creation, assigned a
+    /// field of, closed or receiving the lamports of a closed account, or
+    /// passed where a CPI writes it.
+    pub fn is_writable(&self, instruction: &Instruction, param: &Param) -> bool {
+        if !param.ty.is_account() {
+            return false;
+        }
+        let annotated = param.annotations.iter().any(|a| {
+            matches!(
+                a,
+                ParamAnnotation::Mut | ParamAnnotation::InitIfNeeded { .. }
+            )
+        });
+        let pays = instruction.params.iter().any(|p| {
+            p.annotations.iter().any(
+                |a| matches!(a, ParamAnnotation::InitIfNeeded { payer } if *payer == param.name),
+            )
+        });
+        annotated
+            || pays
+            || instruction.body.iter().any(|stmt| match stmt {
+                Statement::InitAccount {
+                    var_name, payer, ..
+                } => *var_name == param.name || *payer == param.name,
+                Statement::Assign { target, .. } => root(target) == Some(param.name.as_str()),
+                Statement::Close { account, recipient } => {
+                    *account == param.name || *recipient == param.name
+                }
+                Statement::Expr(Expr::MethodCall {
+                    object,
+                    method,
+                    args,
+                }) => self.cpi_writes(object, method, args, &param.name),
+                _ => false,
+            })
+    }
+
+    /// Whether the CPI call `object.method(args)` passes the account `name`
+    /// where the callee's IDL marks the account writable.
+    fn cpi_writes(&self, object: &Expr, method: &str, args: &[Expr], name: &str) -> bool {
+        let Expr::Ident(module) = object else {
+            return false;
+        };
+        let Some(ix) = self.import(module).and_then(|i| i.idl.instruction(method)) else {
+            return false;
+        };
+        args.iter()
+            .zip(&ix.accounts)
+            .any(|(arg, meta)| meta.writable && *arg == Expr::Ident(name.to_string()))
+    }
+
+    /// The `@seeds` accounts the CPI call `object.method(args)` passes where
+    /// the callee's IDL expects a signer. The program signs for them with
+    /// their seeds and bump.
+    pub fn cpi_pda_signers<'a>(
+        &self,
+        instruction: &'a Instruction,
+        object: &Expr,
+        method: &str,
+        args: &[Expr],
+    ) -> Vec<&'a Param> {
+        let Expr::Ident(module) = object else {
+            return Vec::new();
+        };
+        let Some(ix) = self.import(module).and_then(|i| i.idl.instruction(method)) else {
+            return Vec::new();
+        };
+        args.iter()
+            .zip(&ix.accounts)
+            .filter(|(_, meta)| meta.signer)
+            .filter_map(|(arg, _)| match arg {
+                Expr::Ident(name) => instruction.params.iter().find(|p| p.name == *name),
+                _ => None,
+            })
+            .filter(|param| seeds(param).is_some())
+            .collect()
+    }
+
+    /// Whether the program signs for the `@seeds` account `param` in a CPI
+    /// call of the instruction.
+    pub fn signs_cpi(&self, instruction: &Instruction, param: &Param) -> bool {
+        instruction.body.iter().any(|stmt| match stmt {
+            Statement::Expr(Expr::MethodCall {
+                object,
+                method,
+                args,
+            }) => self
+                .cpi_pda_signers(instruction, object, method, args)
+                .iter()
+                .any(|p| p.name == param.name),
+            _ => false,
+        })
+    }
+}
+
+/// Bytes `init` allocates for an account: 8 bytes of discriminator plus the
+/// sizes of its fields.
+pub fn account_space(program: &Program, account: &AccountDef) -> u64 {
+    8 + account
+        .fields
+        .iter()
+        .map(|f| calculate_type_size(program, &f.ty))
+        .sum::<u64>()
+}
+
+/// Serialized size of a field of type `ty`, a data type of `program`.
+pub fn calculate_type_size(program: &Program, ty: &Type) -> u64 {
+    match ty {
+        Type::Pubkey => 32,
+        Type::U8 | Type::I8 => 1,
+        Type::U16 | Type::I16 => 2,
+        Type::U32 | Type::I32 => 4,
+        Type::U64 | Type::I64 => 8,
+        Type::Bool => 1,
+        Type::String => 4 + 4, // length prefix + data (variable, but we'll use a default)
+        Type::Vec(inner) => 4 + 4 + calculate_type_size(program, inner), // length + capacity + element size
+        Type::Option(inner) => 1 + calculate_type_size(program, inner),  // discriminant + inner
+        Type::Struct(name) => program
+            .structs
+            .iter()
+            .find(|s| s.name == *name)
+            .map(|s| {
+                s.fields
+                    .iter()
+                    .map(|f| calculate_type_size(program, &f.ty))
+                    .sum()
+            })
+            .unwrap_or(0),
+        Type::Signer | Type::Account(_) | Type::TokenAccount | Type::Mint | Type::Named(_) => {
+            unreachable!("{} is not a data type", ty)
+        }
+    }
+}
+
+/// The PDA seeds of an account parameter, if any.
+pub fn seeds(param: &Param) -> Option<&[Seed]> {
+    param.annotations.iter().find_map(|a| match a {
+        ParamAnnotation::Seeds(seeds) => Some(seeds.as_slice()),
+        _ => None,
+    })
+}
+
+/// The account receiving the lamports of `account` when the instruction
+/// closes it with `close account to recipient`.
+pub fn close_recipient<'a>(instruction: &'a Instruction, account: &str) -> Option<&'a str> {
+    instruction.body.iter().find_map(|stmt| match stmt {
+        Statement::Close {
+            account: closed,
+            recipient,
+        } if closed == account => Some(recipient.as_str()),
+        _ => None,
+    })
+}
+
+/// Whether the instruction body contains a CPI call into `module`.
+pub fn calls_program(instruction: &Instruction, module: &str) -> bool {
+    instruction.body.iter().any(|stmt| {
+        matches!(
+            stmt,
+            Statement::Expr(Expr::MethodCall { object, .. })
+                if matches!(object.as_ref(), Expr::Ident(name) if name == module)
+        )
+    })
+}
@@ -0,0 +1,76 @@
+//! Custom program errors derived from `require condition, "message"`.
+//!
+//! Each distinct message becomes one error variant, numbered from Anchor's
+//! first custom error code in order of appearance.
+
+use solx_ast::{Program, Statement};
+
+/// Anchor reserves codes below 6000 for framework errors.
+pub const FIRST_ERROR_CODE: u32 = 6000;
+
+/// Message of the error returned by `checked` arithmetic.
+pub const ARITHMETIC_OVERFLOW: &str = "Arithmetic overflow";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorDef {
+    pub code: u32,
+    pub name: String,
+    pub msg: String,
+}
+
+pub(crate) fn collect_errors(program: &Program) -> Vec<ErrorDef> {
+    let mut errors: Vec<ErrorDef> = Vec::new();
+    let messages = program
+        .instructions
+        .iter()
+        .flat_map(|i| i.body.iter())
+        .filter_map(|stmt| match stmt {
+            Statement::Require {
+                message: Some(msg), ..
+            } => Some(msg),
+            _ => None,
+        });
+
+    for msg in messages {
+        add_error(&mut errors, msg);
+    }
+
+    errors
+}
+
+/// Add an error for `msg` unless one exists, numbered after the others.
+pub(crate) fn add_error(errors: &mut Vec<ErrorDef>, msg: &str) {
+    if errors.iter().any(|e| e.msg == msg) {
+        return;
+    }
+    let base = error_name(msg);
+    let mut name = base.clone();
+    let mut suffix = 2;
+    while errors.iter().any(|e| e.name == name) {
+        name = format!("{}{}", base, suffix);
+        suffix += 1;
+    }
+    errors.push(ErrorDef {
+        code: FIRST_ERROR_CODE + errors.len() as u32,
+        name,
+        msg: msg.to_string(),
+    });
+}
+
+/// `"Only the authority"` -> `OnlyTheAuthority`
+fn error_name(msg: &str) -> String {
+    let mut name = String::new();
+    for word in msg.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            name.push(first.to_ascii_uppercase());
+            name.extend(chars);
+        }
+    }
+    if name.is_empty() {
+        name.push_str("Error");
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, 'E');
+    }
+    name
+}
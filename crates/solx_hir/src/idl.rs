@@ -0,0 +1,169 @@
+//! Loading of Anchor IDL files for `import program` declarations.
+//!
+//! Both the 0.30 spec format and the legacy (pre-0.30) format are accepted.
+//! Only the parts needed to type check and generate CPI calls are read.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use solx_ast::Type;
+
+use crate::naming::to_snake_case;
+
+/// An instruction exposed by an imported program. Names are normalized to
+/// snake_case, since legacy IDLs use camelCase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdlInstruction {
+    pub name: String,
+    pub discriminator: [u8; 8],
+    pub accounts: Vec<IdlAccountMeta>,
+    pub args: Vec<(String, Type)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdlAccountMeta {
+    pub name: String,
+    pub writable: bool,
+    pub signer: bool,
+}
+
+/// The subset of an Anchor IDL that SOL-X uses for CPI.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Idl {
+    pub address: String,
+    pub instructions: Vec<IdlInstruction>,
+}
+
+impl Idl {
+    pub fn instruction(&self, name: &str) -> Option<&IdlInstruction> {
+        self.instructions.iter().find(|ix| ix.name == name)
+    }
+}
+
+#[derive(Deserialize)]
+struct RawIdl {
+    address: Option<String>,
+    metadata: Option<RawMetadata>,
+    instructions: Vec<RawInstruction>,
+}
+
+#[derive(Deserialize)]
+struct RawMetadata {
+    address: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawInstruction {
+    name: String,
+    discriminator: Option<Vec<u8>>,
+    #[serde(default)]
+    accounts: Vec<serde_json::Value>,
+    #[serde(default)]
+    args: Vec<RawField>,
+}
+
+#[derive(Deserialize)]
+struct RawAccountMeta {
+    name: String,
+    #[serde(default, alias = "isMut")]
+    writable: bool,
+    #[serde(default, alias = "isSigner")]
+    signer: bool,
+}
+
+#[derive(Deserialize)]
+struct RawField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: serde_json::Value,
+}
+
+pub fn parse_idl(json: &str) -> Result<Idl> {
+    let raw: RawIdl = serde_json::from_str(json).context("Invalid IDL JSON")?;
+
+    let address = raw
+        .address
+        .or(raw.metadata.and_then(|m| m.address))
+        .context("IDL has no program address")?;
+
+    let mut instructions = Vec::new();
+    for ix in raw.instructions {
+        let mut accounts = Vec::new();
+        for account in ix.accounts {
+            if account.get("accounts").is_some() {
+                anyhow::bail!(
+                    "IDL instruction {}: nested account groups are not supported",
+                    ix.name
+                );
+            }
+            let meta: RawAccountMeta = serde_json::from_value(account)
+                .with_context(|| format!("IDL instruction {}: invalid account", ix.name))?;
+            accounts.push(IdlAccountMeta {
+                name: to_snake_case(&meta.name),
+                writable: meta.writable,
+                signer: meta.signer,
+            });
+        }
+
+        let mut args = Vec::new();
+        for arg in ix.args {
+            let ty = idl_type(&arg.ty)
+                .with_context(|| format!("IDL instruction {}: argument {}", ix.name, arg.name))?;
+            args.push((to_snake_case(&arg.name), ty));
+        }
+
+        let discriminator = match ix.discriminator {
+            Some(bytes) => bytes.try_into().map_err(|_| {
+                anyhow::anyhow!("IDL instruction {}: discriminator must be 8 bytes", ix.name)
+            })?,
+            None => sighash("global", &to_snake_case(&ix.name)),
+        };
+
+        instructions.push(IdlInstruction {
+            name: to_snake_case(&ix.name),
+            discriminator,
+            accounts,
+            args,
+        });
+    }
+
+    Ok(Idl {
+        address,
+        instructions,
+    })
+}
+
+fn idl_type(value: &serde_json::Value) -> Result<Type> {
+    if let Some(name) = value.as_str() {
+        return Ok(match name {
+            "pubkey" | "publicKey" => Type::Pubkey,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "bool" => Type::Bool,
+            "string" => Type::String,
+            other => anyhow::bail!("unsupported IDL type {}", other),
+        });
+    }
+    if let Some(inner) = value.get("vec") {
+        return Ok(Type::Vec(Box::new(idl_type(inner)?)));
+    }
+    if let Some(inner) = value.get("option") {
+        return Ok(Type::Option(Box::new(idl_type(inner)?)));
+    }
+    anyhow::bail!("unsupported IDL type {}", value)
+}
+
+/// Anchor's discriminator: the first 8 bytes of `sha256("<namespace>:<name>")`,
+/// with namespace `global`, `account` or `event`.
+pub fn sighash(namespace: &str, name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("{}:{}", namespace, name).as_bytes());
+    let mut out = [0u8; 8];
+    out.copy_from_slice(&hash[..8]);
+    out
+}
@@ -0,0 +1,171 @@
+//! `invariant` declarations inside accounts.
+//!
+//! An account invariant is a condition on the account's fields that every
+//! account of the type keeps. The HIR type checks it, and exposes it per
+//! account parameter as a `require` on that parameter, which is how it is
+//! used everywhere:
+//! - as a runtime check appended to every instruction that writes the
+//!   account, when the program is built with invariant checks
+//! - as a property the fuzzer checks after every call
+//! - as an obligation a verifier proves for every instruction that writes
+//!   the account, assuming it of every account the instruction reads
+
+use anyhow::Result;
+use solx_ast::*;
+
+use crate::accounts::close_recipient;
+use crate::typeck::Scope;
+use crate::{at, Hir};
+
+/// Message of the error raised by an invariant declared without one, for
+/// account type `account`.
+pub fn default_message(account: &str) -> String {
+    format!("{} invariant violated", account)
+}
+
+/// `condition` as a condition on the account called `object`: bare field
+/// names become `object.field`.
+pub fn on_account(condition: &Expr, object: &str) -> Expr {
+    match condition {
+        Expr::Ident(field) => Expr::FieldAccess {
+            object: Box::new(Expr::Ident(object.to_string())),
+            field: field.clone(),
+        },
+        Expr::FieldAccess {
+            object: inner,
+            field,
+        } => Expr::FieldAccess {
+            object: Box::new(on_account(inner, object)),
+            field: field.clone(),
+        },
+        Expr::Literal(_) => condition.clone(),
+        Expr::BinaryOp { op, left, right } => Expr::BinaryOp {
+            op: op.clone(),
+            left: Box::new(on_account(left, object)),
+            right: Box::new(on_account(right, object)),
+        },
+        Expr::UnaryOp { op, operand } => Expr::UnaryOp {
+            op: op.clone(),
+            operand: Box::new(on_account(operand, object)),
+        },
+        Expr::MethodCall {
+            object: inner,
+            method,
+            args,
+        } => Expr::MethodCall {
+            object: Box::new(on_account(inner, object)),
+            method: method.clone(),
+            args: args.iter().map(|a| on_account(a, object)).collect(),
+        },
+    }
+}
+
+impl Hir {
+    /// The invariants of an account parameter as `require` statements on
+    /// it. Other parameters have none.
+    pub fn account_invariants(&self, param: &Param) -> Vec<Statement> {
+        let Type::Account(account) = &param.ty else {
+            return Vec::new();
+        };
+        let Some(def) = self.program.accounts.iter().find(|a| a.name == *account) else {
+            return Vec::new();
+        };
+        def.invariants
+            .iter()
+            .map(|invariant| Statement::Require {
+                condition: on_account(&invariant.condition, &param.name),
+                message: Some(
+                    invariant
+                        .message
+                        .clone()
+                        .unwrap_or_else(|| default_message(account)),
+                ),
+            })
+            .collect()
+    }
+
+    /// The program account parameters an instruction writes: those it
+    /// creates with `init`, assigns a field of, closes or sends the
+    /// lamports of a closed account to.
+    pub fn written_accounts<'a>(&self, instruction: &'a Instruction) -> Vec<&'a Param> {
+        instruction
+            .params
+            .iter()
+            .filter(|p| matches!(p.ty, Type::Account(_)))
+            .filter(|p| {
+                instruction.body.iter().any(|stmt| match stmt {
+                    Statement::InitAccount { var_name, .. } => *var_name == p.name,
+                    Statement::Assign { target, .. } => root(target) == Some(p.name.as_str()),
+                    Statement::Close { account, recipient } => {
+                        *account == p.name || *recipient == p.name
+                    }
+                    _ => false,
+                })
+            })
+            .collect()
+    }
+
+    /// The checks run at the end of `instruction`: the invariants of the
+    /// accounts it writes and does not close, when invariant checks are on.
+    pub fn invariant_checks(&self, instruction: &Instruction) -> Vec<Statement> {
+        if !self.invariant_checks {
+            return Vec::new();
+        }
+        self.written_accounts(instruction)
+            .into_iter()
+            .filter(|param| close_recipient(instruction, &param.name).is_none())
+            .flat_map(|param| self.account_invariants(param))
+            .collect()
+    }
+}
+
+/// The variable an assignment target is a field of.
+pub fn root(target: &Expr) -> Option<&str> {
+    match target {
+        Expr::Ident(name) => Some(name),
+        Expr::FieldAccess { object, .. } => root(object),
+        _ => None,
+    }
+}
+
+/// Invariants may only read the account's fields, and must be bool.
+pub(crate) fn validate(program: &Program) -> Result<()> {
+    for account in &program.accounts {
+        // Type check against an instruction taking the account alone
+        let instruction = Instruction {
+            docs: Vec::new(),
+            name: account.name.clone(),
+            span: account.span,
+            params: vec![Param {
+                name: "account".to_string(),
+                span: account.span,
+                ty: Type::Account(account.name.clone()),
+                annotations: Vec::new(),
+            }],
+            body: Vec::new(),
+        };
+        let scope = Scope::for_invariant(program, &instruction);
+        for invariant in &account.invariants {
+            check(&scope, account, &invariant.condition)
+                .map_err(|e| at(program, invariant.span, e))?;
+        }
+    }
+    Ok(())
+}
+
+fn check(scope: &Scope, account: &AccountDef, condition: &Expr) -> Result<()> {
+    let mut unsupported = None;
+    condition.visit(&mut |e| match e {
+        Expr::Ident(name) if !account.fields.iter().any(|f| f.name == *name) => {
+            unsupported.get_or_insert(format!("{} has no field {}", account.name, name));
+        }
+        Expr::MethodCall { method, .. } => {
+            unsupported.get_or_insert(format!("cannot call {}", method));
+        }
+        _ => {}
+    });
+    if let Some(message) = unsupported {
+        anyhow::bail!("In invariant of {}: {}", account.name, message);
+    }
+    scope.check(&on_account(condition, "account"), &Type::Bool)
+}
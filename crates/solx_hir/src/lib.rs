@@ -1,25 +1,1100 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use solx_ast::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub mod accounts;
+pub mod errors;
+pub mod idl;
+pub mod invariants;
+pub mod lints;
+pub mod naming;
+pub mod properties;
+pub mod sysvar;
+mod typeck;
+mod types;
+
+use errors::ErrorDef;
+use idl::Idl;
+use properties::property;
+use typeck::Scope;
+
+/// Module of the generated crate holding the functions declared with
+/// `extern fn`; instructions call them as `helpers.name(...)`.
+pub const HELPERS_MODULE: &str = "helpers";
 
 /// High-level Intermediate Representation
 /// This layer performs type checking and validation
 pub struct Hir {
     pub program: Program,
+    pub imports: Vec<ImportedProgram>,
+    pub errors: Vec<ErrorDef>,
+    pub arithmetic: Arithmetic,
+    /// Whether instructions check the invariants of the accounts they write
+    pub invariant_checks: bool,
+    /// Lint levels overriding the defaults, by lint name
+    pub lint_levels: BTreeMap<String, lints::Level>,
+}
+
+/// What `+ - * / %` do when the result does not fit the operand type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Arithmetic {
+    /// Plain Rust operators: the program panics under `overflow-checks`
+    #[default]
+    Panic,
+    /// The instruction fails with the `ArithmeticOverflow` program error
+    Checked,
+    /// The result wraps around
+    Wrapping,
+}
+
+impl Arithmetic {
+    pub const NAMES: [&'static str; 3] = ["panic", "checked", "wrapping"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "panic" => Some(Arithmetic::Panic),
+            "checked" => Some(Arithmetic::Checked),
+            "wrapping" => Some(Arithmetic::Wrapping),
+            _ => None,
+        }
+    }
+}
+
+/// A program declared with `import program`, resolved against its IDL.
+pub struct ImportedProgram {
+    /// Name as written in the import, e.g. `Vault`
+    pub name: String,
+    /// Name used for calls and the generated CPI module, e.g. `vault`
+    pub module: String,
+    pub idl: Idl,
 }
 
 impl Hir {
     pub fn from_ast(program: Program) -> Result<Self> {
-        // Validate that all account types referenced exist
+        Self::from_ast_in(program, Path::new("."))
+    }
+
+    /// Build the HIR, resolving `import program` IDL paths relative to `root`.
+    pub fn from_ast_in(mut program: Program, root: &Path) -> Result<Self> {
+        let imports = load_imports(&program, root)?;
+
+        naming::check_reserved(&program)?;
+        validate_type_names(&program)?;
+        types::resolve(&mut program)?;
+        validate_externs(&program)?;
+        validate_tests(&program)?;
+        invariants::validate(&program)?;
+        naming::check_collisions(&program)?;
+
         for instruction in &program.instructions {
-            for param in &instruction.params {
-                if let ParamType::Account(ref name) = param.ty {
-                    if !program.accounts.iter().any(|acc| acc.name == *name) {
-                        anyhow::bail!("Unknown account type: {}", name);
+            validate_annotations(instruction)
+                .and_then(|()| check_body(&program, &imports, instruction))
+                .map_err(|e| at(&program, instruction.span, e))?;
+        }
+
+        let errors = errors::collect_errors(&program);
+
+        // Instruction names become function names and discriminators
+        for instruction in &mut program.instructions {
+            instruction.name = naming::to_snake_case(&instruction.name);
+        }
+
+        Ok(Hir {
+            program,
+            imports,
+            errors,
+            arithmetic: Arithmetic::default(),
+            invariant_checks: false,
+            lint_levels: BTreeMap::new(),
+        })
+    }
+
+    /// Use `arithmetic` for the program's integer operations. Checked
+    /// arithmetic adds the `ArithmeticOverflow` error if the program has any.
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.arithmetic = arithmetic;
+        self.add_overflow_error();
+        self
+    }
+
+    /// Check account invariants at the end of every instruction that writes
+    /// the account. Each invariant raises its message as a custom error.
+    pub fn with_invariant_checks(mut self, invariant_checks: bool) -> Self {
+        self.invariant_checks = invariant_checks;
+        let messages: Vec<String> = self
+            .program
+            .instructions
+            .iter()
+            .flat_map(|i| self.invariant_checks(i))
+            .filter_map(|check| match check {
+                Statement::Require { message, .. } => message,
+                _ => None,
+            })
+            .collect();
+        for msg in messages {
+            errors::add_error(&mut self.errors, &msg);
+        }
+        // The checks may be the only arithmetic in the program
+        self.add_overflow_error();
+        self
+    }
+
+    /// The type of an expression in the body of `instruction`.
+    pub fn type_of(&self, instruction: &Instruction, expr: &Expr) -> Result<Type> {
+        Scope::new(&self.program, instruction).type_of(expr)
+    }
+
+    pub fn extern_fn(&self, name: &str) -> Option<&ExternFn> {
+        self.program.externs.iter().find(|e| e.name == name)
+    }
+
+    pub fn import(&self, module: &str) -> Option<&ImportedProgram> {
+        self.imports.iter().find(|i| i.module == module)
+    }
+
+    /// The custom error raised by `require ..., "msg"`.
+    pub fn error_for(&self, msg: &str) -> Option<&ErrorDef> {
+        self.errors.iter().find(|e| e.msg == msg)
+    }
+
+    fn add_overflow_error(&mut self) {
+        if self.arithmetic == Arithmetic::Checked && self.uses_arithmetic() {
+            errors::add_error(&mut self.errors, errors::ARITHMETIC_OVERFLOW);
+        }
+    }
+
+    /// Whether the generated instructions do arithmetic, in their bodies or
+    /// in the invariant checks added to them.
+    fn uses_arithmetic(&self) -> bool {
+        let mut found = false;
+        for instruction in &self.program.instructions {
+            let checks = self.invariant_checks(instruction);
+            for stmt in instruction.body.iter().chain(&checks) {
+                for expr in stmt.exprs() {
+                    expr.visit(&mut |e| {
+                        found |= matches!(e, Expr::BinaryOp { op, .. } if op.is_arithmetic());
+                    });
+                }
+            }
+        }
+        found
+    }
+}
+
+/// Prefix an error with the `file:line:column` of the declaration it is
+/// about, when the program was parsed from source.
+fn at(program: &Program, span: Span, err: anyhow::Error) -> anyhow::Error {
+    match program.location(span) {
+        Some(location) => anyhow::anyhow!("{}: {:#}", location, err),
+        None => err,
+    }
+}
+
+fn load_imports(program: &Program, root: &Path) -> Result<Vec<ImportedProgram>> {
+    let mut imports: Vec<ImportedProgram> = Vec::new();
+    for import in &program.imports {
+        let module = naming::to_snake_case(&import.name);
+        if imports.iter().any(|i| i.module == module) {
+            anyhow::bail!("Program {} is imported more than once", import.name);
+        }
+        let path = root.join(&import.idl_path);
+        let json = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read IDL {}", path.display()))?;
+        let idl = idl::parse_idl(&json)
+            .with_context(|| format!("Failed to load IDL {}", path.display()))?;
+        imports.push(ImportedProgram {
+            name: import.name.clone(),
+            module,
+            idl,
+        });
+    }
+    Ok(imports)
+}
+
+/// Type check the instruction body. CPI calls (`vault.deposit(...)`) are
+/// checked against the imported IDLs and are only allowed as statements.
+fn check_body(
+    program: &Program,
+    imports: &[ImportedProgram],
+    instruction: &Instruction,
+) -> Result<()> {
+    let scope = Scope::new(program, instruction);
+
+    for stmt in &instruction.body {
+        match stmt {
+            Statement::Expr(Expr::MethodCall {
+                object,
+                method,
+                args,
+            }) if is_import(imports, object) => {
+                check_cpi_call(&scope, imports, object, method, args)?;
+            }
+            Statement::Expr(Expr::MethodCall {
+                object,
+                method,
+                args,
+            }) if scope.is_helpers(object) => {
+                scope.helper_call(method, args)?;
+            }
+            Statement::Expr(expr) => {
+                scope.type_of(expr)?;
+            }
+            Statement::Require { condition, .. } => {
+                scope.check(condition, &Type::Bool)?;
+            }
+            Statement::Assign { target, value } => {
+                let ty = scope.type_of(target)?;
+                check_target(program, instruction, target)?;
+                scope.check(value, &ty)?;
+            }
+            Statement::Emit { event, fields } => {
+                check_emit(&scope, event, fields)?;
+            }
+            Statement::Close { account, recipient } => {
+                check_close(instruction, account, recipient)?;
+            }
+            Statement::InitAccount { .. } => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// `close account to recipient` deletes a program account parameter once,
+/// sending its lamports to another account parameter.
+fn check_close(instruction: &Instruction, account: &str, recipient: &str) -> Result<()> {
+    let param = |name: &str| instruction.params.iter().find(|p| p.name == name);
+    if !matches!(param(account), Some(p) if matches!(p.ty, Type::Account(_))) {
+        anyhow::bail!(
+            "In instruction {}: cannot close {}; only program account parameters can be closed",
+            instruction.name,
+            account
+        );
+    }
+    if !matches!(param(recipient), Some(p) if p.ty.is_account()) || recipient == account {
+        anyhow::bail!(
+            "In instruction {}: the lamports of {} must go to another account parameter, not {}",
+            instruction.name,
+            account,
+            recipient
+        );
+    }
+    let closes = instruction
+        .body
+        .iter()
+        .filter(|s| matches!(s, Statement::Close { account: a, .. } if a == account))
+        .count();
+    if closes > 1 {
+        anyhow::bail!(
+            "In instruction {}: {} is closed more than once",
+            instruction.name,
+            account
+        );
+    }
+    if instruction
+        .body
+        .iter()
+        .any(|s| matches!(s, Statement::InitAccount { var_name, .. } if var_name == account))
+    {
+        anyhow::bail!(
+            "In instruction {}: {} is created and closed in the same instruction",
+            instruction.name,
+            account
+        );
+    }
+    Ok(())
+}
+
+/// Only the data of program accounts can be assigned: arguments are
+/// immutable, and builtin properties are read only.
+fn check_target(program: &Program, instruction: &Instruction, target: &Expr) -> Result<()> {
+    let mut object = target;
+    let mut access = None;
+    while let Expr::FieldAccess {
+        object: inner,
+        field,
+    } = object
+    {
+        access = Some((inner.as_ref(), field));
+        object = inner;
+    }
+    let assignable = match (object, access) {
+        (Expr::Ident(name), Some((root, field))) => {
+            instruction
+                .params
+                .iter()
+                .any(|p| p.name == *name && matches!(p.ty, Type::Account(_)))
+                && property(program, instruction, root, field).is_none()
+        }
+        _ => false,
+    };
+    if !assignable {
+        anyhow::bail!(
+            "In instruction {}: cannot assign to {}; only fields of program accounts can be assigned",
+            instruction.name,
+            target
+        );
+    }
+    Ok(())
+}
+
+/// Accounts, structs and events share the Rust type namespace of the
+/// generated crate.
+fn validate_type_names(program: &Program) -> Result<()> {
+    let mut seen = std::collections::HashSet::new();
+    let names = program
+        .accounts
+        .iter()
+        .map(|a| (a.name.as_str(), a.span))
+        .chain(program.structs.iter().map(|s| (s.name.as_str(), s.span)))
+        .chain(program.events.iter().map(|e| (e.name.as_str(), e.span)));
+    for (name, span) in names {
+        if !seen.insert(name) {
+            return Err(at(
+                program,
+                span,
+                anyhow::anyhow!("Duplicate account, struct or event name: {}", name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `extern fn` names must be unique, and no imported program may take the
+/// `helpers` name.
+fn validate_externs(program: &Program) -> Result<()> {
+    for (i, def) in program.externs.iter().enumerate() {
+        if program.externs[..i].iter().any(|e| e.name == def.name) {
+            return Err(at(
+                program,
+                def.span,
+                anyhow::anyhow!("Duplicate extern fn: {}", def.name),
+            ));
+        }
+    }
+    if let Some(import) = program
+        .imports
+        .iter()
+        .find(|i| naming::to_snake_case(&i.name) == HELPERS_MODULE)
+    {
+        anyhow::bail!(
+            "Imported program {} conflicts with the {} module",
+            import.name,
+            HELPERS_MODULE
+        );
+    }
+    Ok(())
+}
+
+/// Tests are selected by name, so names must be unique.
+fn validate_tests(program: &Program) -> Result<()> {
+    for (i, test) in program.tests.iter().enumerate() {
+        if program.tests[..i].iter().any(|t| t.name == test.name) {
+            return Err(at(
+                program,
+                test.span,
+                anyhow::anyhow!("Duplicate test name: \"{}\"", test.name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Every event field must be given exactly once, with a value of its type.
+fn check_emit(scope: &Scope, event: &str, fields: &[(String, Expr)]) -> Result<()> {
+    let instruction = &scope.instruction.name;
+    let def = scope
+        .program
+        .events
+        .iter()
+        .find(|e| e.name == event)
+        .ok_or_else(|| {
+            anyhow::anyhow!("In instruction {}: unknown event {}", instruction, event)
+        })?;
+
+    for (name, value) in fields {
+        let field = def.fields.iter().find(|f| f.name == *name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "In instruction {}: event {} has no field {}",
+                instruction,
+                event,
+                name
+            )
+        })?;
+        if fields.iter().filter(|(n, _)| n == name).count() > 1 {
+            anyhow::bail!(
+                "In instruction {}: field {} of event {} is given more than once",
+                instruction,
+                name,
+                event
+            );
+        }
+        scope
+            .check(value, &field.ty)
+            .with_context(|| format!("field {} of event {}", name, event))?;
+    }
+    for field in &def.fields {
+        if !fields.iter().any(|(n, _)| *n == field.name) {
+            anyhow::bail!(
+                "In instruction {}: missing field {} in emit {}",
+                instruction,
+                field.name,
+                event
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn is_import(imports: &[ImportedProgram], object: &Expr) -> bool {
+    matches!(object, Expr::Ident(name) if imports.iter().any(|i| i.module == *name))
+}
+
+fn check_cpi_call(
+    scope: &Scope,
+    imports: &[ImportedProgram],
+    object: &Expr,
+    method: &str,
+    args: &[Expr],
+) -> Result<()> {
+    let instruction = scope.instruction;
+    let import = match object {
+        Expr::Ident(name) => imports.iter().find(|i| i.module == *name),
+        _ => None,
+    };
+    let import = import.ok_or_else(|| {
+        anyhow::anyhow!(
+            "In instruction {}: unknown method {}",
+            instruction.name,
+            method
+        )
+    })?;
+    let ix = import.idl.instruction(method).ok_or_else(|| {
+        anyhow::anyhow!(
+            "In instruction {}: program {} has no instruction {}",
+            instruction.name,
+            import.name,
+            method
+        )
+    })?;
+
+    let expected = ix.accounts.len() + ix.args.len();
+    if args.len() != expected {
+        anyhow::bail!(
+            "In instruction {}: {}.{} expects {} arguments ({} accounts, {} args), found {}",
+            instruction.name,
+            import.module,
+            method,
+            expected,
+            ix.accounts.len(),
+            ix.args.len(),
+            args.len()
+        );
+    }
+
+    let (account_args, data_args) = args.split_at(ix.accounts.len());
+    for (arg, meta) in account_args.iter().zip(&ix.accounts) {
+        let param = match arg {
+            Expr::Ident(name) => instruction.params.iter().find(|p| p.name == *name),
+            _ => None,
+        };
+        let param = match param {
+            Some(p) if p.ty.is_account() => p,
+            _ => anyhow::bail!(
+                "In instruction {}: account {} of {}.{} must be an account parameter",
+                instruction.name,
+                meta.name,
+                import.module,
+                method
+            ),
+        };
+        // The program signs for its PDAs
+        if meta.signer && param.ty != Type::Signer && accounts::seeds(param).is_none() {
+            anyhow::bail!(
+                "In instruction {}: account {} of {}.{} must be a Signer or an @seeds account",
+                instruction.name,
+                meta.name,
+                import.module,
+                method
+            );
+        }
+    }
+    for (arg, (name, ty)) in data_args.iter().zip(&ix.args) {
+        scope
+            .check(arg, ty)
+            .with_context(|| format!("argument {} of {}.{}", name, import.module, method))?;
+    }
+
+    Ok(())
+}
+
+/// Check that parameter annotations only reference parameters of the same
+/// instruction, and that those parameters have the expected kinds.
+fn validate_annotations(instruction: &Instruction) -> Result<()> {
+    let find_param = |name: &str| instruction.params.iter().find(|p| p.name == name);
+
+    for param in &instruction.params {
+        for annotation in &param.annotations {
+            match annotation {
+                ParamAnnotation::AssociatedToken { mint, authority } => {
+                    if param.ty != Type::TokenAccount {
+                        anyhow::bail!(
+                            "In instruction {}: @associated_token on {} requires type TokenAccount",
+                            instruction.name,
+                            param.name
+                        );
+                    }
+                    match find_param(mint) {
+                        Some(p) if p.ty == Type::Mint => {}
+                        Some(_) => anyhow::bail!(
+                            "In instruction {}: associated token mint {} must be of type Mint",
+                            instruction.name,
+                            mint
+                        ),
+                        None => anyhow::bail!(
+                            "In instruction {}: associated token mint {} is not a parameter",
+                            instruction.name,
+                            mint
+                        ),
+                    }
+                    match find_param(authority) {
+                        Some(p) if p.ty.is_account() => {}
+                        Some(_) => anyhow::bail!(
+                            "In instruction {}: associated token authority {} must be an account",
+                            instruction.name,
+                            authority
+                        ),
+                        None => anyhow::bail!(
+                            "In instruction {}: associated token authority {} is not a parameter",
+                            instruction.name,
+                            authority
+                        ),
+                    }
+                }
+                ParamAnnotation::Seeds(seeds) => {
+                    if !matches!(param.ty, Type::Account(_)) {
+                        anyhow::bail!(
+                            "In instruction {}: @seeds on {} requires a program account type",
+                            instruction.name,
+                            param.name
+                        );
+                    }
+                    if param
+                        .annotations
+                        .iter()
+                        .filter(|a| matches!(a, ParamAnnotation::Seeds(_)))
+                        .count()
+                        > 1
+                    {
+                        anyhow::bail!(
+                            "In instruction {}: {} has more than one @seeds",
+                            instruction.name,
+                            param.name
+                        );
+                    }
+                    for seed in seeds {
+                        if let Seed::Account(name) = seed {
+                            match find_param(name) {
+                                Some(p) if p.ty.is_account() && p.name != param.name => {}
+                                _ => anyhow::bail!(
+                                    "In instruction {}: seed {} of {} must be another account parameter",
+                                    instruction.name,
+                                    name,
+                                    param.name
+                                ),
+                            }
+                        }
+                    }
+                }
+                ParamAnnotation::Mut => {
+                    if !param.ty.is_account() {
+                        anyhow::bail!(
+                            "In instruction {}: @mut on {} requires an account type",
+                            instruction.name,
+                            param.name
+                        );
+                    }
+                }
+                ParamAnnotation::InitIfNeeded { payer } => {
+                    if !param
+                        .annotations
+                        .iter()
+                        .any(|a| matches!(a, ParamAnnotation::AssociatedToken { .. }))
+                    {
+                        anyhow::bail!(
+                            "In instruction {}: @init_if_needed on {} requires @associated_token",
+                            instruction.name,
+                            param.name
+                        );
+                    }
+                    match find_param(payer) {
+                        Some(p) if p.ty == Type::Signer => {}
+                        Some(_) => anyhow::bail!(
+                            "In instruction {}: payer {} must be a Signer",
+                            instruction.name,
+                            payer
+                        ),
+                        None => anyhow::bail!(
+                            "In instruction {}: payer {} is not a parameter",
+                            instruction.name,
+                            payer
+                        ),
                     }
                 }
             }
         }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Program {
+        solx_parser::parse(source).unwrap()
+    }
+
+    fn error(source: &str) -> String {
+        format!(
+            "{:#}",
+            Hir::from_ast(parse(source))
+                .err()
+                .expect("expected an error")
+        )
+    }
+
+    #[test]
+    fn test_associated_token_mint_must_be_param() {
+        let err = error(
+            r#"
+program Vault
+
+instruction deposit(
+  user: Signer,
+  user_ata: TokenAccount @associated_token(mint = mint, authority = user)
+) {
+}
+"#,
+        );
+        assert!(err.contains("mint mint is not a parameter"));
+    }
+
+    fn write_vault_idl(root: &Path) {
+        fs::write(
+            root.join("vault.json"),
+            r#"{
+              "address": "Vau1t11111111111111111111111111111111111111",
+              "instructions": [{
+                "name": "deposit",
+                "discriminator": [242, 35, 198, 137, 82, 225, 242, 182],
+                "accounts": [{ "name": "user", "writable": true, "signer": true }],
+                "args": [{ "name": "amount", "type": "u64" }]
+              }]
+            }"#,
+        )
+        .unwrap();
+    }
+
+    fn router(flag: &str, arg: &str) -> Program {
+        parse(&format!(
+            r#"
+program Router
+
+import program Vault from "vault.json"
+
+instruction route(user: Signer, flag: bool{flag}, amount: u64) {{
+  vault.deposit(user, {arg})
+}}
+"#
+        ))
+    }
+
+    #[test]
+    fn test_cpi_call_checked_against_idl() {
+        let dir = tempfile::tempdir().unwrap();
+        write_vault_idl(dir.path());
+
+        assert!(Hir::from_ast_in(router("", "amount"), dir.path()).is_ok());
+        assert!(Hir::from_ast_in(router("", "user"), dir.path()).is_err());
+        let err = Hir::from_ast_in(router("", "flag"), dir.path())
+            .err()
+            .expect("expected type error");
+        assert!(format!("{:#}", err).contains("expected u64 but found bool"));
+    }
+
+    #[test]
+    fn test_writable_accounts_inferred() {
+        let hir = Hir::from_ast(parse(
+            r#"
+program Counter
+
+account State {
+  count: u64
+}
+
+instruction bump(user: Signer, state: State, other: State @mut) {
+  state.count += 1
+}
+"#,
+        ))
+        .unwrap();
+        let bump = &hir.program.instructions[0];
+        let writable = |i: usize| hir.is_writable(bump, &bump.params[i]);
+        assert!(!writable(0));
+        assert!(writable(1));
+        assert!(writable(2));
+
+        // Closing writes the account and its recipient
+        let hir = Hir::from_ast(parse(
+            r#"
+program Counter
+
+account State {
+  count: u64
+}
+
+instruction retire(user: Signer, state: State, spare: State, log: State) {
+  require state.count == 0
+  close state to spare
+}
+"#,
+        ))
+        .unwrap();
+        let retire = &hir.program.instructions[0];
+        let writable: Vec<bool> = hir
+            .instruction_accounts(retire)
+            .iter()
+            .map(|a| a.writable)
+            .collect();
+        assert_eq!(writable, vec![false, true, true, false]);
+        let written: Vec<&str> = hir
+            .written_accounts(retire)
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(written, vec!["state", "spare"]);
+
+        // The callee writes `user`, so the signer is writable here
+        let dir = tempfile::tempdir().unwrap();
+        write_vault_idl(dir.path());
+        let hir = Hir::from_ast_in(router("", "amount"), dir.path()).unwrap();
+        let route = &hir.program.instructions[0];
+        assert!(hir.is_writable(route, &route.params[0]));
+
+        let err = Hir::from_ast_in(router(" @mut", "amount"), dir.path())
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .ends_with("In instruction route: @mut on flag requires an account type"));
+    }
+
+    #[test]
+    fn test_close_checked() {
+        let source = |body: &str| {
+            format!(
+                r#"
+program Escrow
+
+account Offer {{
+  amount: u64
+}}
+
+instruction settle(maker: Signer, offer: Offer, amount: u64) {{
+  {body}
+}}
+"#
+            )
+        };
+        assert!(Hir::from_ast(parse(&source("close offer to maker"))).is_ok());
+        assert!(error(&source("close maker to offer"))
+            .ends_with("cannot close maker; only program account parameters can be closed"));
+        assert!(error(&source("close offer to amount"))
+            .ends_with("the lamports of offer must go to another account parameter, not amount"));
+        assert!(error(&source("close offer to offer"))
+            .ends_with("the lamports of offer must go to another account parameter, not offer"));
+        assert!(
+            error(&source("close offer to maker\n  close offer to maker"))
+                .ends_with("In instruction settle: offer is closed more than once")
+        );
+        assert!(error(&source(
+            "init account offer: Offer payer maker\n  close offer to maker"
+        ))
+        .ends_with("offer is created and closed in the same instruction"));
+    }
+
+    #[test]
+    fn test_sysvar_expressions_are_typed() {
+        let source = |field: &str| {
+            format!(
+                r#"
+program Lock
+
+account LockState {{
+  unlock_at: i64
+}}
+
+instruction lock(state: LockState) {{
+  state.unlock_at = clock.{field}
+}}
+"#
+            )
+        };
+
+        assert!(Hir::from_ast(parse(&source("unix_timestamp"))).is_ok());
+        assert!(error(&source("slot")).contains("expected i64 but found u64"));
+    }
+
+    #[test]
+    fn test_account_properties_are_typed() {
+        let hir = Hir::from_ast(parse(
+            r#"
+program Registry
+
+account Entry {
+  owner: u8
+}
+
+instruction touch(user: Signer, entry: Entry, amount: u64) {
+}
+"#,
+        ))
+        .unwrap();
+        let touch = &hir.program.instructions[0];
+        let type_of = |object: &str, field: &str| {
+            let expr = Expr::FieldAccess {
+                object: Box::new(Expr::Ident(object.to_string())),
+                field: field.to_string(),
+            };
+            hir.type_of(touch, &expr).map_err(|e| e.to_string())
+        };
+
+        assert_eq!(type_of("user", "key"), Ok(Type::Pubkey));
+        assert_eq!(type_of("user", "lamports"), Ok(Type::U64));
+        assert_eq!(type_of("user", "owner"), Ok(Type::Pubkey));
+        assert_eq!(type_of("entry", "data_len"), Ok(Type::U64));
+        assert_eq!(type_of("entry", "is_writable"), Ok(Type::Bool));
+        // A data field shadows the property
+        assert_eq!(type_of("entry", "owner"), Ok(Type::U8));
+        assert_eq!(
+            type_of("amount", "lamports"),
+            Err("In instruction touch: type u64 has no field lamports".to_string())
+        );
+    }
+
+    #[test]
+    fn test_account_invariants_checked_where_written() {
+        let source = |condition: &str| {
+            format!(
+                r#"
+program Vault
+
+account Vault {{
+  total_shares: u64
+  total_deposits: u64
+  invariant {condition}
+}}
+
+instruction deposit(vault: Vault) {{
+  vault.total_deposits = 1
+}}
+
+instruction inspect(vault: Vault) {{
+}}
+"#
+            )
+        };
+
+        let hir = Hir::from_ast(parse(&source("total_shares <= total_deposits"))).unwrap();
+        let (deposit, inspect) = (&hir.program.instructions[0], &hir.program.instructions[1]);
+        assert!(hir.invariant_checks(deposit).is_empty());
+        assert_eq!(hir.written_accounts(deposit).len(), 1);
+        assert!(hir.written_accounts(inspect).is_empty());
+
+        let hir = hir.with_invariant_checks(true);
+        let (deposit, inspect) = (&hir.program.instructions[0], &hir.program.instructions[1]);
+        let checks = hir.invariant_checks(deposit);
+        let Statement::Require { condition, message } = &checks[0] else {
+            panic!("expected a require");
+        };
+        assert_eq!(hir.type_of(deposit, condition).unwrap(), Type::Bool);
+        assert_eq!(message.as_deref(), Some("Vault invariant violated"));
+        assert!(hir.error_for("Vault invariant violated").is_some());
+        assert!(hir.invariant_checks(inspect).is_empty());
+
+        // A closed account keeps no invariant
+        let hir = Hir::from_ast(parse(&source("total_shares <= total_deposits").replace(
+            "instruction inspect(vault: Vault) {\n",
+            "instruction inspect(vault: Vault, other: Vault) {\n  close vault to other\n",
+        )))
+        .unwrap()
+        .with_invariant_checks(true);
+        let inspect = &hir.program.instructions[1];
+        assert_eq!(hir.written_accounts(inspect).len(), 2);
+        let checks = hir.invariant_checks(inspect);
+        assert_eq!(checks.len(), 1);
+        let Statement::Require { condition, .. } = &checks[0] else {
+            panic!("expected a require");
+        };
+        assert_eq!(
+            condition.to_string(),
+            "other.total_shares <= other.total_deposits"
+        );
+
+        assert!(error(&source("total_shares"))
+            .ends_with("In invariant of Vault: expected bool but found u64"));
+        assert!(error(&source("shares <= total_deposits"))
+            .ends_with("In invariant of Vault: Vault has no field shares"));
+    }
+
+    #[test]
+    fn test_instruction_names_normalized_and_collisions_rejected() {
+        let source = |names: &[&str]| {
+            names
+                .iter()
+                .fold("program MyCounter\n".to_string(), |source, name| {
+                    format!("{source}\ninstruction {name}() {{\n}}\n")
+                })
+        };
+
+        let hir = Hir::from_ast(parse(&source(&["initializeVault"]))).unwrap();
+        assert_eq!(hir.program.instructions[0].name, "initialize_vault");
+
+        assert!(error(&source(&["initializeVault", "initialize_vault"])).ends_with(
+            "Instructions initializeVault and initialize_vault both generate the name initialize_vault"
+        ));
+    }
+
+    #[test]
+    fn test_rust_keywords_rejected_as_names() {
+        let source = |field: &str, instruction: &str| {
+            format!(
+                r#"
+program Registry
+
+account Entry {{
+  {field}: u64
+}}
+
+instruction {instruction}(entry: Entry) {{
+}}
+"#
+            )
+        };
+
+        assert!(Hir::from_ast(parse(&source("kind", "touch"))).is_ok());
+        assert_eq!(
+            error(&source("type", "touch")),
+            "4:9: In account Entry: field type is a Rust keyword; rename it"
+        );
+        assert_eq!(
+            error(&source("_", "touch")),
+            "4:9: In account Entry: field _ is not a valid name; names need a letter or digit"
+        );
+        assert_eq!(
+            error(&source("kind", "Match")),
+            "8:13: Instruction Match generates the Rust keyword match; rename it"
+        );
+    }
+
+    #[test]
+    fn test_helper_calls_checked_against_extern_fns() {
+        let source = |call: &str| {
+            format!(
+                r#"
+program Vault
+
+extern fn fee(amount: u64) -> u64
+
+instruction deposit(flag: bool) {{
+  require {call} > 0
+}}
+"#
+            )
+        };
+
+        assert!(Hir::from_ast(parse(&source("helpers.fee(5)"))).is_ok());
+        assert!(error(&source("helpers.fee(flag)")).contains("argument amount of helper fee"));
+        assert!(error(&source("helpers.tax(5)")).contains("unknown helper tax"));
+    }
+
+    #[test]
+    fn test_named_types_resolved_and_placed() {
+        let source = |point: &str, params: &str, body: &str| {
+            format!(
+                r#"
+program Canvas
+
+account Shape {{
+  line: Line
+}}
+
+struct Point {{
+  {point}
+}}
+
+struct Line {{
+  a: Point
+  b: Point
+}}
+
+instruction draw(user: Signer, shape: Shape, to: Point{params}) {{
+  {body}
+}}
+"#
+            )
+        };
+        let point = "x: u64\n  y: u64";
+        let path = |names: &[&str]| {
+            names[1..]
+                .iter()
+                .fold(Expr::Ident(names[0].to_string()), |object, name| {
+                    Expr::FieldAccess {
+                        object: Box::new(object),
+                        field: name.to_string(),
+                    }
+                })
+        };
+
+        let hir = Hir::from_ast(parse(&source(
+            point,
+            ", tags: Option<Vec<u8>>",
+            "shape.line.b = to",
+        )))
+        .unwrap();
+        let draw = &hir.program.instructions[0];
+        assert_eq!(draw.params[1].ty, Type::Account("Shape".to_string()));
+        assert_eq!(draw.params[2].ty, Type::Struct("Point".to_string()));
+        assert_eq!(
+            hir.program.accounts[0].fields[0].ty,
+            Type::Struct("Line".to_string())
+        );
+        assert_eq!(
+            hir.type_of(draw, &path(&["shape", "line", "a", "x"]))
+                .unwrap(),
+            Type::U64
+        );
+        assert_eq!(hir.type_of(draw, &path(&["to", "y"])).unwrap(), Type::U64);
+
+        // Account types only as top-level instruction parameters
+        assert!(error(&source(point, ", signers: Vec<Signer>", "")).ends_with(
+            "In instruction draw: parameter signers has type Vec<Signer>; account type Signer cannot be nested"
+        ));
+        assert!(error(&source("owner: Shape", "", "")).ends_with(
+            "In struct Point: field owner has account type Shape; only instruction parameters can be accounts"
+        ));
+        assert!(error(&source("x: Pixel", "", "")).ends_with("In struct Point: unknown type Pixel"));
+        assert!(error(&source("next: Line", "", ""))
+            .ends_with("In struct Point: Point contains itself"));
 
-        Ok(Hir { program })
+        // Only fields of program accounts can be assigned
+        assert!(error(&source(point, "", "to.x = 1")).ends_with(
+            "In instruction draw: cannot assign to to.x; only fields of program accounts can be assigned"
+        ));
     }
 }
@@ -0,0 +1,719 @@
+//! Security lints over instructions.
+//!
+//! Lints point at patterns that type check but are common sources of
+//! exploits in Solana programs. Each has a name, used under `[lints]` in
+//! `solx.toml`, and a default [`Level`]; `solx build` prints warnings and
+//! stops on denied lints. Diagnostics point at the parameter they are about,
+//! or at the instruction for problems in its body.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use solx_ast::*;
+
+use crate::accounts::close_recipient;
+use crate::invariants::root;
+use crate::properties::{property, Property};
+use crate::Hir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    pub const NAMES: [&'static str; 3] = ["allow", "warn", "deny"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        })
+    }
+}
+
+pub struct Lint {
+    pub name: &'static str,
+    pub default: Level,
+    pub description: &'static str,
+}
+
+pub const MISSING_SIGNER: &str = "missing-signer";
+pub const INIT_PAYER_NOT_SIGNER: &str = "init-payer-not-signer";
+pub const UNAUTHORIZED_WRITE: &str = "unauthorized-write";
+pub const DIVIDE_BEFORE_MULTIPLY: &str = "divide-before-multiply";
+pub const UNCHECKED_BALANCE_ARITHMETIC: &str = "unchecked-balance-arithmetic";
+pub const MISSING_CLOSE: &str = "missing-close";
+pub const ALIASED_MUT_ACCOUNTS: &str = "aliased-mut-accounts";
+
+pub const LINTS: [Lint; 7] = [
+    Lint {
+        name: MISSING_SIGNER,
+        default: Level::Warn,
+        description: "an instruction writes accounts but takes no Signer",
+    },
+    Lint {
+        name: INIT_PAYER_NOT_SIGNER,
+        default: Level::Deny,
+        description: "the payer of `init` is not a Signer",
+    },
+    Lint {
+        name: UNAUTHORIZED_WRITE,
+        default: Level::Warn,
+        description: "a program account is written but never checked against a signer",
+    },
+    Lint {
+        name: DIVIDE_BEFORE_MULTIPLY,
+        default: Level::Warn,
+        description: "a quotient is multiplied, losing the precision of the division",
+    },
+    Lint {
+        name: UNCHECKED_BALANCE_ARITHMETIC,
+        default: Level::Warn,
+        description:
+            "a balance is added to, subtracted from or multiplied without checked arithmetic",
+    },
+    Lint {
+        name: MISSING_CLOSE,
+        default: Level::Warn,
+        description: "an account is flagged as finished but not closed",
+    },
+    Lint {
+        name: ALIASED_MUT_ACCOUNTS,
+        default: Level::Warn,
+        description: "two mutable accounts of the same type may be the same account",
+    },
+];
+
+/// The lint called `name`.
+pub fn find(name: &str) -> Option<&'static Lint> {
+    LINTS.iter().find(|l| l.name == name)
+}
+
+/// A lint that fired.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub lint: &'static str,
+    pub level: Level,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// `warning: file:line:column: message [lint]`, without the location
+    /// when the program was not parsed from source.
+    pub fn render(&self, program: &Program) -> String {
+        let level = match self.level {
+            Level::Deny => "error",
+            _ => "warning",
+        };
+        match program.location(self.span) {
+            Some(location) => format!("{}: {}: {} [{}]", level, location, self.message, self.lint),
+            None => format!("{}: {} [{}]", level, self.message, self.lint),
+        }
+    }
+}
+
+/// Field and argument names that hold token or lamport amounts.
+const BALANCE_WORDS: [&str; 8] = [
+    "balance", "lamports", "amount", "deposit", "supply", "reserve", "stake", "shares",
+];
+
+/// Bool fields that mark an account as done when set to `true`.
+const FINISHED_FLAGS: [&str; 10] = [
+    "finished",
+    "closed",
+    "completed",
+    "complete",
+    "done",
+    "settled",
+    "cancelled",
+    "canceled",
+    "claimed",
+    "executed",
+];
+
+/// Bool fields that mark an account as done when set to `false`.
+const LIVE_FLAGS: [&str; 4] = ["initialized", "active", "open", "live"];
+
+impl Hir {
+    /// Override the default level of lints by name. Names must be in
+    /// [`LINTS`].
+    pub fn with_lint_levels(mut self, levels: BTreeMap<String, Level>) -> Self {
+        self.lint_levels = levels;
+        self
+    }
+
+    pub fn lint_level(&self, name: &str) -> Level {
+        match self.lint_levels.get(name) {
+            Some(level) => *level,
+            None => find(name).map_or(Level::Allow, |l| l.default),
+        }
+    }
+
+    /// Run every lint that is not allowed, instruction by instruction.
+    pub fn lints(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for instruction in &self.program.instructions {
+            let mut linter = Linter {
+                hir: self,
+                instruction,
+                diagnostics: &mut diagnostics,
+            };
+            linter.missing_signer();
+            linter.init_payer_not_signer();
+            linter.unauthorized_write();
+            linter.divide_before_multiply();
+            linter.unchecked_balance_arithmetic();
+            linter.missing_close();
+            linter.aliased_mut_accounts();
+        }
+        diagnostics
+    }
+
+    /// The writable accounts of an instruction, other than its signers.
+    pub fn mutable_accounts<'a>(&self, instruction: &'a Instruction) -> Vec<&'a Param> {
+        instruction
+            .params
+            .iter()
+            .filter(|p| p.ty != Type::Signer && self.is_writable(instruction, p))
+            .collect()
+    }
+}
+
+struct Linter<'a> {
+    hir: &'a Hir,
+    instruction: &'a Instruction,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, lint: &'static str, span: Span, message: String) {
+        let level = self.hir.lint_level(lint);
+        if level == Level::Allow {
+            return;
+        }
+        self.diagnostics.push(Diagnostic {
+            lint,
+            level,
+            span,
+            message: format!("In instruction {}: {}", self.instruction.name, message),
+        });
+    }
+
+    fn param(&self, name: &str) -> Option<&Param> {
+        self.instruction.params.iter().find(|p| p.name == name)
+    }
+
+    fn signers(&self) -> Vec<&Param> {
+        self.instruction
+            .params
+            .iter()
+            .filter(|p| p.ty == Type::Signer)
+            .collect()
+    }
+
+    fn created(&self, name: &str) -> bool {
+        self.instruction
+            .body
+            .iter()
+            .any(|s| matches!(s, Statement::InitAccount { var_name, .. } if var_name == name))
+    }
+
+    fn requires(&self) -> impl Iterator<Item = &Expr> {
+        self.instruction.body.iter().filter_map(|s| match s {
+            Statement::Require { condition, .. } => Some(condition),
+            _ => None,
+        })
+    }
+
+    /// Whether some `require` compares `a` and `b` with `op`, in either
+    /// order.
+    fn compared(&self, op: BinOp, a: &impl Fn(&Expr) -> bool, b: &impl Fn(&Expr) -> bool) -> bool {
+        let mut found = false;
+        for condition in self.requires() {
+            condition.visit(&mut |e| {
+                if let Expr::BinaryOp {
+                    op: cmp,
+                    left,
+                    right,
+                } = e
+                {
+                    found |= *cmp == op && ((a(left) && b(right)) || (b(left) && a(right)));
+                }
+            });
+        }
+        found
+    }
+
+    fn is_signer_key(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::FieldAccess { object, field }
+                if property(&self.hir.program, self.instruction, object, field)
+                    == Some(Property::Key) =>
+            {
+                matches!(
+                    object.as_ref(),
+                    Expr::Ident(name) if self.param(name).is_some_and(|p| p.ty == Type::Signer)
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether an account is tied to a signer: created here, derived from a
+    /// signer's address, or one of its fields required to equal one.
+    fn authorized(&self, param: &Param) -> bool {
+        if self.created(&param.name) {
+            return true;
+        }
+        let seeded_by_signer = seeds(param).is_some_and(|seeds| {
+            seeds.iter().any(|seed| {
+                matches!(seed, Seed::Account(name)
+                    if self.param(name).is_some_and(|p| p.ty == Type::Signer))
+            })
+        });
+        seeded_by_signer
+            || self.compared(
+                BinOp::Eq,
+                &|e| root(e) == Some(param.name.as_str()) && !matches!(e, Expr::Ident(_)),
+                &|e| self.is_signer_key(e),
+            )
+    }
+
+    fn missing_signer(&mut self) {
+        let mutable = self.hir.mutable_accounts(self.instruction);
+        if mutable.is_empty() || !self.signers().is_empty() {
+            return;
+        }
+        let names: Vec<&str> = mutable.iter().map(|p| p.name.as_str()).collect();
+        self.report(
+            MISSING_SIGNER,
+            self.instruction.span,
+            format!(
+                "writes {} but takes no Signer, so anyone can call it",
+                names.join(", ")
+            ),
+        );
+    }
+
+    fn init_payer_not_signer(&mut self) {
+        for stmt in &self.instruction.body {
+            let Statement::InitAccount {
+                var_name, payer, ..
+            } = stmt
+            else {
+                continue;
+            };
+            let (span, problem) = match self.param(payer) {
+                Some(p) if p.ty == Type::Signer => continue,
+                Some(p) => (p.span, "is not a Signer"),
+                None => (self.instruction.span, "is not a parameter"),
+            };
+            self.report(
+                INIT_PAYER_NOT_SIGNER,
+                span,
+                format!(
+                    "payer {} of {} {}; only a signer can pay rent",
+                    payer, var_name, problem
+                ),
+            );
+        }
+    }
+
+    fn unauthorized_write(&mut self) {
+        let signers = self.signers();
+        // Without any signer, `missing-signer` reports the instruction
+        let Some(signer) = signers.first().map(|s| s.name.clone()) else {
+            return;
+        };
+        for param in self.hir.written_accounts(self.instruction) {
+            if self.authorized(param) {
+                continue;
+            }
+            let Type::Account(account) = &param.ty else {
+                continue;
+            };
+            // Suggest a Pubkey field the account actually has, preferring `authority`
+            let keys: Vec<&str> = self
+                .hir
+                .program
+                .accounts
+                .iter()
+                .find(|a| a.name == *account)
+                .map(|a| {
+                    a.fields
+                        .iter()
+                        .filter(|f| f.ty == Type::Pubkey)
+                        .map(|f| f.name.as_str())
+                        .collect()
+                })
+                .unwrap_or_default();
+            let check = match keys.iter().find(|k| **k == "authority").or(keys.first()) {
+                Some(key) => format!("add `require {}.{} == {}.key`", param.name, key, signer),
+                None => format!(
+                    "store the signer's key in {} and require it matches",
+                    account
+                ),
+            };
+            self.report(
+                UNAUTHORIZED_WRITE,
+                param.span,
+                format!(
+                    "{} is written but never checked against a signer; \
+                     {} or derive it from the signer with @seeds",
+                    param.name, check
+                ),
+            );
+        }
+    }
+
+    fn divide_before_multiply(&mut self) {
+        let mut found = Vec::new();
+        for stmt in &self.instruction.body {
+            for expr in stmt.exprs() {
+                expr.visit(&mut |e| {
+                    if let Expr::BinaryOp {
+                        op: BinOp::Mul,
+                        left,
+                        right,
+                    } = e
+                    {
+                        let quotient =
+                            |e: &Expr| matches!(e, Expr::BinaryOp { op: BinOp::Div, .. });
+                        if quotient(left) || quotient(right) {
+                            found.push(e.to_string());
+                        }
+                    }
+                });
+            }
+        }
+        for expr in found {
+            self.report(
+                DIVIDE_BEFORE_MULTIPLY,
+                self.instruction.span,
+                format!(
+                    "`{}` divides before multiplying and loses precision; multiply first",
+                    expr
+                ),
+            );
+        }
+    }
+
+    fn unchecked_balance_arithmetic(&mut self) {
+        let mode = match self.hir.arithmetic {
+            crate::Arithmetic::Checked => return,
+            crate::Arithmetic::Panic => "panics",
+            crate::Arithmetic::Wrapping => "wraps around",
+        };
+        let mut found = Vec::new();
+        for stmt in &self.instruction.body {
+            for expr in stmt.exprs() {
+                expr.visit(&mut |e| {
+                    if let Expr::BinaryOp { op, left, right } = e {
+                        let arithmetic = matches!(op, BinOp::Add | BinOp::Sub | BinOp::Mul);
+                        if arithmetic && (is_balance(left) || is_balance(right)) {
+                            found.push(e.clone());
+                        }
+                    }
+                });
+            }
+        }
+        for expr in found {
+            let Expr::BinaryOp { op, left, right } = &expr else {
+                continue;
+            };
+            // `require a >= b` before `a - b` rules out the underflow
+            if *op == BinOp::Sub
+                && (self.compared(BinOp::Ge, &|e| e == left.as_ref(), &|e| e == right.as_ref())
+                    || self.compared(BinOp::Le, &|e| e == right.as_ref(), &|e| e == left.as_ref()))
+            {
+                continue;
+            }
+            self.report(
+                UNCHECKED_BALANCE_ARITHMETIC,
+                self.instruction.span,
+                format!(
+                    "`{}` on a balance {} when it overflows; \
+                     set `arithmetic = \"checked\"` under [build] or guard it with a require",
+                    expr, mode
+                ),
+            );
+        }
+    }
+
+    fn missing_close(&mut self) {
+        let mut found = Vec::new();
+        for stmt in &self.instruction.body {
+            let Statement::Assign {
+                target: Expr::FieldAccess { object, field },
+                value: Expr::Literal(Literal::Bool(value)),
+            } = stmt
+            else {
+                continue;
+            };
+            let Expr::Ident(name) = object.as_ref() else {
+                continue;
+            };
+            let flag = field.strip_prefix("is_").unwrap_or(field);
+            let finished = if *value {
+                FINISHED_FLAGS.contains(&flag)
+            } else {
+                LIVE_FLAGS.contains(&flag)
+            };
+            if finished {
+                found.push((name.clone(), format!("{}.{} = {}", name, field, value)));
+            }
+        }
+        for (name, assignment) in found {
+            let Some(param) = self.param(&name) else {
+                continue;
+            };
+            if !matches!(param.ty, Type::Account(_))
+                || close_recipient(self.instruction, &name).is_some()
+            {
+                continue;
+            }
+            // The rent usually goes back to whoever signed
+            let recipient = self
+                .instruction
+                .params
+                .iter()
+                .find(|p| p.ty == Type::Signer)
+                .map_or("<recipient>", |p| p.name.as_str());
+            self.report(
+                MISSING_CLOSE,
+                param.span,
+                format!(
+                    "`{}` marks {} as finished but the account stays open, \
+                     keeping its rent and its state readable as if it were live; \
+                     add `close {} to {}`",
+                    assignment, name, name, recipient
+                ),
+            );
+        }
+    }
+
+    fn aliased_mut_accounts(&mut self) {
+        let mutable = self.hir.mutable_accounts(self.instruction);
+        let mut found = Vec::new();
+        for (i, a) in mutable.iter().enumerate() {
+            for b in &mutable[i + 1..] {
+                if a.ty != b.ty || self.created(&a.name) || self.created(&b.name) {
+                    continue;
+                }
+                // PDAs with different seeds are different accounts
+                if let (Some(x), Some(y)) = (seeds(a), seeds(b)) {
+                    if x != y {
+                        continue;
+                    }
+                }
+                let key = |name: &str| {
+                    let name = name.to_string();
+                    move |e: &Expr| {
+                        matches!(e, Expr::FieldAccess { object, field }
+                            if field == "key" && **object == Expr::Ident(name.clone()))
+                    }
+                };
+                if self.compared(BinOp::Ne, &key(&a.name), &key(&b.name)) {
+                    continue;
+                }
+                found.push((a.name.clone(), *b));
+            }
+        }
+        for (first, second) in found {
+            let kind = match &second.ty {
+                Type::Account(name) => name.clone(),
+                _ => "TokenAccount".to_string(),
+            };
+            self.report(
+                ALIASED_MUT_ACCOUNTS,
+                second.span,
+                format!(
+                    "{} and {} are both mutable {} accounts and may be the same account; \
+                     add `require {}.key != {}.key`",
+                    first, second.name, kind, first, second.name
+                ),
+            );
+        }
+    }
+}
+
+fn seeds(param: &Param) -> Option<&Vec<Seed>> {
+    param.annotations.iter().find_map(|a| match a {
+        ParamAnnotation::Seeds(seeds) => Some(seeds),
+        _ => None,
+    })
+}
+
+fn is_balance(expr: &Expr) -> bool {
+    let name = match expr {
+        Expr::Ident(name) => name,
+        Expr::FieldAccess { field, .. } => field,
+        _ => return false,
+    };
+    BALANCE_WORDS.iter().any(|w| name.contains(w))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Arithmetic;
+
+    const BANK: &str = r#"program Bank
+
+account Vault {
+  authority: Pubkey
+  balance: u64
+  closed: bool
+}
+
+instruction transfer(
+  user: Signer,
+  from: Vault,
+  to: Vault,
+  amount: u64
+) {
+  require from.authority == user.key
+  from.balance = from.balance - amount
+  to.balance = to.balance + amount
+}
+
+instruction settle(vault: Vault, fee: u64) {
+  vault.closed = true
+  vault.balance = vault.balance / 100 * fee
+}
+
+instruction open(user: Signer, funder: Vault, vault: Vault) {
+  init account vault: Vault payer funder
+}
+"#;
+
+    fn check(source: &str) -> Hir {
+        let mut program = solx_parser::parse(source).unwrap();
+        program.files = vec!["bank.solx".to_string()];
+        Hir::from_ast(program).unwrap()
+    }
+
+    fn fired(diagnostics: &[Diagnostic]) -> Vec<(&str, usize)> {
+        diagnostics.iter().map(|d| (d.lint, d.span.line)).collect()
+    }
+
+    #[test]
+    fn test_security_lints() {
+        let hir = check(BANK);
+        let diagnostics = hir.lints();
+        assert_eq!(
+            fired(&diagnostics),
+            vec![
+                (UNAUTHORIZED_WRITE, 12),
+                (UNCHECKED_BALANCE_ARITHMETIC, 9),
+                (UNCHECKED_BALANCE_ARITHMETIC, 9),
+                (ALIASED_MUT_ACCOUNTS, 12),
+                (MISSING_SIGNER, 20),
+                (DIVIDE_BEFORE_MULTIPLY, 20),
+                (MISSING_CLOSE, 20),
+                (INIT_PAYER_NOT_SIGNER, 25),
+            ]
+        );
+        assert!(diagnostics[0]
+            .message
+            .contains("add `require to.authority == user.key`"));
+        assert_eq!(
+            diagnostics[3].render(&hir.program),
+            "warning: bank.solx:12:3: In instruction transfer: from and to are both mutable Vault \
+             accounts and may be the same account; add `require from.key != to.key` [aliased-mut-accounts]"
+        );
+        assert_eq!(
+            diagnostics[5].message,
+            "In instruction settle: `vault.balance / 100 * fee` divides before multiplying and \
+             loses precision; multiply first"
+        );
+        assert!(diagnostics[6]
+            .message
+            .ends_with("add `close vault to <recipient>`"));
+        assert_eq!(diagnostics[7].level, Level::Deny);
+
+        // Checked arithmetic cannot wrap; levels override the defaults
+        let levels = BTreeMap::from([
+            (MISSING_CLOSE.to_string(), Level::Allow),
+            (ALIASED_MUT_ACCOUNTS.to_string(), Level::Deny),
+        ]);
+        let hir = check(BANK)
+            .with_arithmetic(Arithmetic::Checked)
+            .with_lint_levels(levels);
+        let diagnostics = hir.lints();
+        assert_eq!(
+            fired(&diagnostics),
+            vec![
+                (UNAUTHORIZED_WRITE, 12),
+                (ALIASED_MUT_ACCOUNTS, 12),
+                (MISSING_SIGNER, 20),
+                (DIVIDE_BEFORE_MULTIPLY, 20),
+                (INIT_PAYER_NOT_SIGNER, 25),
+            ]
+        );
+        assert_eq!(diagnostics[1].level, Level::Deny);
+
+        // The suggested check names a Pubkey field the account has, if any
+        let hir = check(
+            "program Tally\n\naccount Counter {\n  owner: Pubkey\n  count: u64\n}\n\n\
+             instruction bump(user: Signer, counter: Counter) {\n  counter.count = 1\n}\n",
+        );
+        assert!(hir.lints()[0]
+            .message
+            .contains("add `require counter.owner == user.key`"));
+        let hir = check(
+            "program Tally\n\naccount Counter {\n  count: u64\n}\n\n\
+             instruction bump(user: Signer, counter: Counter) {\n  counter.count = 1\n}\n",
+        );
+        assert!(hir.lints()[0]
+            .message
+            .contains("store the signer's key in Counter and require it matches or derive it"));
+    }
+
+    #[test]
+    fn test_checks_silence_lints() {
+        // Guarding the subtraction and telling the accounts apart; an
+        // account derived from the signer is authorized
+        let source = BANK
+            .replace(
+                "  require from.authority",
+                "  require from.balance >= amount && to.key != from.key\n  require from.authority",
+            )
+            .replace("  to: Vault,", "  to: Vault @seeds(\"vault\", user),");
+        let hir = check(&source);
+        let transfer = hir
+            .lints()
+            .into_iter()
+            .filter(|d| d.message.starts_with("In instruction transfer"))
+            .collect::<Vec<_>>();
+        assert_eq!(fired(&transfer), vec![(UNCHECKED_BALANCE_ARITHMETIC, 9)]);
+        assert!(transfer[0]
+            .message
+            .contains("`to.balance + amount` on a balance panics"));
+
+        // Closing the finished account
+        let source = BANK.replace(
+            "  vault.closed = true\n",
+            "  vault.closed = true\n  close vault to user\n",
+        );
+        let source = source.replace("settle(vault: Vault", "settle(user: Signer, vault: Vault");
+        let hir = check(&source);
+        assert!(!hir.lints().iter().any(|d| d.lint == MISSING_CLOSE));
+    }
+}
@@ -0,0 +1,268 @@
+//! Case conversions for generated names.
+//!
+//! SOL-X names can be written in any case (`MyCounter`, `initializeVault`),
+//! but generated code follows the conventions of its target: snake_case
+//! modules, functions and IDL names, PascalCase Rust types and camelCase
+//! TypeScript. Every generator goes through these helpers so the same
+//! source name always maps to the same output names.
+
+use anyhow::Result;
+use solx_ast::{Program, Span};
+
+use crate::at;
+
+/// Rust keywords, strict and reserved. Generated code uses SOL-X names as
+/// identifiers, and `self`, `Self`, `super` and `crate` cannot even be
+/// written as raw identifiers, so none of them can be a name.
+const RUST_KEYWORDS: [&str; 51] = [
+    "Self", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while",
+];
+
+/// Split a name into lowercase words at underscores, dashes and case
+/// boundaries: `initializeVault` and `HTTPServer2` give `[initialize, vault]`
+/// and `[http, server2]`.
+pub fn words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !word.is_empty() {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower)
+            {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// `MyCounter` -> `my_counter`
+pub fn to_snake_case(name: &str) -> String {
+    words(name).join("_")
+}
+
+/// `MyCounter` -> `MY_COUNTER`
+pub fn to_screaming_snake_case(name: &str) -> String {
+    to_snake_case(name).to_uppercase()
+}
+
+/// `initialize_vault` -> `InitializeVault`
+pub fn to_pascal_case(name: &str) -> String {
+    words(name).iter().map(|w| capitalize(w)).collect()
+}
+
+/// `initialize_vault` -> `initializeVault`
+pub fn to_camel_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, word) in words(name).iter().enumerate() {
+        if i == 0 {
+            out.push_str(word);
+        } else {
+            out.push_str(&capitalize(word));
+        }
+    }
+    out
+}
+
+/// Name of the Anchor accounts struct of an instruction, e.g.
+/// `InitializeVaultContext`.
+pub fn context_name(instruction: &str) -> String {
+    format!("{}Context", to_pascal_case(instruction))
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Reject declarations that are distinct in the source but would generate
+/// the same name.
+pub(crate) fn check_collisions(program: &Program) -> Result<()> {
+    // Instruction functions, IDL names and discriminators use snake_case
+    let instructions: Vec<(&str, String)> = program
+        .instructions
+        .iter()
+        .map(|i| (i.name.as_str(), to_snake_case(&i.name)))
+        .collect();
+    check_unique("Instructions", &instructions)?;
+
+    // Context structs share the Rust type namespace with accounts and events
+    let mut types: Vec<(&str, String)> = program
+        .instructions
+        .iter()
+        .map(|i| (i.name.as_str(), context_name(&i.name)))
+        .collect();
+    types.extend(
+        program
+            .accounts
+            .iter()
+            .map(|a| (a.name.as_str(), to_pascal_case(&a.name)))
+            .chain(
+                program
+                    .events
+                    .iter()
+                    .map(|e| (e.name.as_str(), to_pascal_case(&e.name))),
+            ),
+    );
+    check_unique("Declarations", &types)?;
+
+    // TypeScript clients use camelCase fields and arguments
+    for account in &program.accounts {
+        let fields: Vec<(&str, String)> = account
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), to_camel_case(&f.name)))
+            .collect();
+        check_unique(&format!("Fields of {}", account.name), &fields)?;
+    }
+    for instruction in &program.instructions {
+        let params: Vec<(&str, String)> = instruction
+            .params
+            .iter()
+            .map(|p| (p.name.as_str(), to_camel_case(&p.name)))
+            .collect();
+        check_unique(&format!("Parameters of {}", instruction.name), &params)?;
+    }
+
+    Ok(())
+}
+
+/// Reject names that are not identifiers in the generated Rust: keywords,
+/// and `_` or other names without letters or digits. Errors point at the
+/// declaration the name is in.
+pub(crate) fn check_reserved(program: &Program) -> Result<()> {
+    reserved_names(program).map_err(|(span, e)| at(program, span, e))
+}
+
+fn reserved_names(program: &Program) -> std::result::Result<(), (Span, anyhow::Error)> {
+    let check = |span: Span, context: Option<&str>, what: &str, name: &str, generated: &str| {
+        let problem = if generated.is_empty() || generated == "_" {
+            "is not a valid name; names need a letter or digit".to_string()
+        } else if RUST_KEYWORDS.contains(&generated) && generated == name {
+            "is a Rust keyword; rename it".to_string()
+        } else if RUST_KEYWORDS.contains(&generated) {
+            format!("generates the Rust keyword {}; rename it", generated)
+        } else {
+            return Ok(());
+        };
+        let message = format!("{} {} {}", what, name, problem);
+        Err((
+            span,
+            match context {
+                Some(context) => anyhow::anyhow!("In {}: {}", context, message),
+                None => anyhow::anyhow!("{}", capitalize(&message)),
+            },
+        ))
+    };
+
+    // The program and its instructions become snake_case modules and
+    // functions; everything else keeps its name
+    let module = to_snake_case(&program.name);
+    check(Span::default(), None, "program", &program.name, &module)?;
+    let definitions = program
+        .accounts
+        .iter()
+        .map(|d| ("account", &d.name, d.span, &d.fields))
+        .chain(
+            program
+                .structs
+                .iter()
+                .map(|d| ("struct", &d.name, d.span, &d.fields)),
+        )
+        .chain(
+            program
+                .events
+                .iter()
+                .map(|d| ("event", &d.name, d.span, &d.fields)),
+        );
+    for (kind, name, span, fields) in definitions {
+        check(span, None, kind, name, name)?;
+        let context = format!("{} {}", kind, name);
+        for field in fields {
+            check(span, Some(&context), "field", &field.name, &field.name)?;
+        }
+    }
+    for def in &program.externs {
+        check(def.span, None, "extern fn", &def.name, &def.name)?;
+        let context = format!("extern fn {}", def.name);
+        for (name, _) in &def.params {
+            check(def.span, Some(&context), "parameter", name, name)?;
+        }
+    }
+    for instruction in &program.instructions {
+        let function = to_snake_case(&instruction.name);
+        check(
+            instruction.span,
+            None,
+            "instruction",
+            &instruction.name,
+            &function,
+        )?;
+        let context = format!("instruction {}", instruction.name);
+        for param in &instruction.params {
+            check(
+                param.span,
+                Some(&context),
+                "parameter",
+                &param.name,
+                &param.name,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn check_unique(what: &str, names: &[(&str, String)]) -> Result<()> {
+    for (i, (name, generated)) in names.iter().enumerate() {
+        if let Some((other, _)) = names[..i].iter().find(|(_, g)| g == generated) {
+            anyhow::bail!(
+                "{} {} and {} both generate the name {}",
+                what,
+                other,
+                name,
+                generated
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_conversions() {
+        assert_eq!(to_snake_case("MyCounter"), "my_counter");
+        assert_eq!(to_snake_case("initializeVault"), "initialize_vault");
+        assert_eq!(to_snake_case("HTTPServer2"), "http_server2");
+        assert_eq!(to_snake_case("already_snake"), "already_snake");
+        assert_eq!(to_pascal_case("initialize_vault"), "InitializeVault");
+        assert_eq!(to_pascal_case("initializeVault"), "InitializeVault");
+        assert_eq!(to_camel_case("initializer_amount"), "initializerAmount");
+        assert_eq!(to_camel_case("EscrowState"), "escrowState");
+        assert_eq!(to_screaming_snake_case("EscrowState"), "ESCROW_STATE");
+        assert_eq!(context_name("initialize"), "InitializeContext");
+    }
+}
@@ -0,0 +1,88 @@
+//! Builtin properties of account parameters, e.g. `user.key` or
+//! `vault.lamports`, read from the account itself rather than its data.
+//!
+//! A field of the account's data with the same name shadows the property,
+//! so an account type can still declare an `owner` field.
+
+use solx_ast::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    /// The account's address
+    Key,
+    Lamports,
+    /// The program that owns the account
+    Owner,
+    /// Bytes of account data
+    DataLen,
+    IsSigner,
+    IsWritable,
+}
+
+impl Property {
+    pub const ALL: [Property; 6] = [
+        Property::Key,
+        Property::Lamports,
+        Property::Owner,
+        Property::DataLen,
+        Property::IsSigner,
+        Property::IsWritable,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Property> {
+        Property::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Property::Key => "key",
+            Property::Lamports => "lamports",
+            Property::Owner => "owner",
+            Property::DataLen => "data_len",
+            Property::IsSigner => "is_signer",
+            Property::IsWritable => "is_writable",
+        }
+    }
+
+    pub fn ty(&self) -> Type {
+        match self {
+            Property::Key | Property::Owner => Type::Pubkey,
+            Property::Lamports | Property::DataLen => Type::U64,
+            Property::IsSigner | Property::IsWritable => Type::Bool,
+        }
+    }
+
+    /// The properties parameters of type `ty` have: every account has all
+    /// of them, instruction arguments have none.
+    pub fn of(ty: &Type) -> &'static [Property] {
+        if ty.is_account() {
+            &Property::ALL
+        } else {
+            &[]
+        }
+    }
+}
+
+/// The builtin property `object.field` reads, if `object` is an account
+/// parameter of `instruction` without a data field called `field`.
+pub fn property(
+    program: &Program,
+    instruction: &Instruction,
+    object: &Expr,
+    field: &str,
+) -> Option<Property> {
+    let Expr::Ident(name) = object else {
+        return None;
+    };
+    let param = instruction.params.iter().find(|p| p.name == *name)?;
+    if let Type::Account(account) = &param.ty {
+        let def = program.accounts.iter().find(|a| a.name == *account);
+        if def.is_some_and(|a| a.fields.iter().any(|f| f.name == field)) {
+            return None;
+        }
+    }
+    Property::of(&param.ty)
+        .iter()
+        .copied()
+        .find(|p| p.name() == field)
+}
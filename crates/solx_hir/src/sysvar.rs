@@ -0,0 +1,51 @@
+//! Builtin sysvars that can be used in expressions without declaring an
+//! account, e.g. `clock.unix_timestamp` or `rent.minimum_balance(n)`.
+//!
+//! A parameter with the same name shadows the sysvar.
+
+use solx_ast::Type;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sysvar {
+    Clock,
+    Rent,
+}
+
+impl Sysvar {
+    pub fn from_name(name: &str) -> Option<Sysvar> {
+        match name {
+            "clock" => Some(Sysvar::Clock),
+            "rent" => Some(Sysvar::Rent),
+            _ => None,
+        }
+    }
+
+    /// Rust expression that fetches the sysvar inside an instruction handler.
+    pub fn getter(&self) -> &'static str {
+        match self {
+            Sysvar::Clock => "Clock::get()?",
+            Sysvar::Rent => "Rent::get()?",
+        }
+    }
+
+    pub fn field_type(&self, field: &str) -> Option<Type> {
+        match (self, field) {
+            (Sysvar::Clock, "slot") => Some(Type::U64),
+            (Sysvar::Clock, "epoch") => Some(Type::U64),
+            (Sysvar::Clock, "leader_schedule_epoch") => Some(Type::U64),
+            (Sysvar::Clock, "unix_timestamp") => Some(Type::I64),
+            (Sysvar::Clock, "epoch_start_timestamp") => Some(Type::I64),
+            (Sysvar::Rent, "lamports_per_byte_year") => Some(Type::U64),
+            (Sysvar::Rent, "burn_percent") => Some(Type::U8),
+            _ => None,
+        }
+    }
+
+    /// Return type of a method taking a single integer argument.
+    pub fn method_type(&self, method: &str) -> Option<Type> {
+        match (self, method) {
+            (Sysvar::Rent, "minimum_balance") => Some(Type::U64),
+            _ => None,
+        }
+    }
+}
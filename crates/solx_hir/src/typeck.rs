@@ -0,0 +1,269 @@
+//! Expression typing within the scope of a single instruction.
+
+use anyhow::{Context, Result};
+use solx_ast::*;
+
+use crate::properties::property;
+use crate::sysvar::Sysvar;
+use crate::HELPERS_MODULE;
+
+pub(crate) struct Scope<'a> {
+    pub program: &'a Program,
+    pub instruction: &'a Instruction,
+    /// What errors are about, e.g. `instruction deposit`
+    context: String,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(program: &'a Program, instruction: &'a Instruction) -> Self {
+        Scope {
+            program,
+            instruction,
+            context: format!("instruction {}", instruction.name),
+        }
+    }
+
+    /// A scope for the invariants of the account `instruction` takes.
+    pub fn for_invariant(program: &'a Program, instruction: &'a Instruction) -> Self {
+        Scope {
+            program,
+            instruction,
+            context: format!("invariant of {}", instruction.name),
+        }
+    }
+
+    fn param(&self, name: &str) -> Option<&'a Param> {
+        self.instruction.params.iter().find(|p| p.name == name)
+    }
+
+    /// Resolve `name` to a sysvar unless a parameter shadows it.
+    pub fn sysvar(&self, name: &str) -> Option<Sysvar> {
+        if self.param(name).is_some() {
+            return None;
+        }
+        Sysvar::from_name(name)
+    }
+
+    /// Whether `object` names the hand-written `helpers` module, unless a
+    /// parameter shadows it.
+    pub fn is_helpers(&self, object: &Expr) -> bool {
+        match object {
+            Expr::Ident(name) => name == HELPERS_MODULE && self.param(name).is_none(),
+            _ => false,
+        }
+    }
+
+    /// Check a call to an `extern fn` and return its result type.
+    pub fn helper_call(&self, method: &str, args: &[Expr]) -> Result<Option<Type>> {
+        let def = self
+            .program
+            .externs
+            .iter()
+            .find(|e| e.name == method)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "In {}: unknown helper {}; declare it with `extern fn {}(...)`",
+                    self.context,
+                    method,
+                    method
+                )
+            })?;
+        if args.len() != def.params.len() {
+            anyhow::bail!(
+                "In {}: helper {} takes {} arguments but {} were given",
+                self.context,
+                method,
+                def.params.len(),
+                args.len()
+            );
+        }
+        for (arg, (name, ty)) in args.iter().zip(&def.params) {
+            self.check(arg, ty)
+                .with_context(|| format!("argument {} of helper {}", name, method))?;
+        }
+        Ok(def.ret.clone())
+    }
+
+    /// Check that `expr` has type `expected`. Integer literals are accepted
+    /// for any integer type.
+    pub fn check(&self, expr: &Expr, expected: &Type) -> Result<()> {
+        if let Expr::Literal(Literal::UInt(_) | Literal::Int(_)) = expr {
+            if expected.is_integer() {
+                return Ok(());
+            }
+        }
+        let actual = self.type_of(expr)?;
+        if actual != *expected {
+            anyhow::bail!(
+                "In {}: expected {} but found {}",
+                self.context,
+                expected.to_rust_type(),
+                actual.to_rust_type()
+            );
+        }
+        Ok(())
+    }
+
+    pub fn type_of(&self, expr: &Expr) -> Result<Type> {
+        match expr {
+            Expr::Literal(lit) => Ok(match lit {
+                Literal::Int(_) => Type::I64,
+                Literal::UInt(_) => Type::U64,
+                Literal::Bool(_) => Type::Bool,
+                Literal::String(_) => Type::String,
+            }),
+            Expr::Ident(name) if self.sysvar(name).is_some() => {
+                anyhow::bail!("In {}: sysvar {} is not a value", self.context, name)
+            }
+            Expr::Ident(name) => {
+                let param = self.param(name).ok_or_else(|| {
+                    anyhow::anyhow!("In {}: unknown identifier {}", self.context, name)
+                })?;
+                if param.ty.is_account() {
+                    anyhow::bail!("In {}: {} is an account, not a value", self.context, name);
+                }
+                Ok(param.ty.clone())
+            }
+            Expr::FieldAccess { object, field } => self.field_type(object, field),
+            Expr::BinaryOp { op, left, right } => self.binary_type(op, left, right),
+            Expr::UnaryOp { op, operand } => {
+                let ty = self.type_of(operand)?;
+                match op {
+                    UnOp::Not if ty == Type::Bool => Ok(ty),
+                    UnOp::Neg if ty.is_signed() => Ok(ty),
+                    _ => anyhow::bail!(
+                        "In {}: invalid operand type {} for {:?}",
+                        self.context,
+                        ty.to_rust_type(),
+                        op
+                    ),
+                }
+            }
+            Expr::MethodCall {
+                object,
+                method,
+                args,
+            } => self.method_type(object, method, args),
+        }
+    }
+
+    fn method_type(&self, object: &Expr, method: &str, args: &[Expr]) -> Result<Type> {
+        if self.is_helpers(object) {
+            return self.helper_call(method, args)?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "In {}: helper {} returns no value and can only be used as a statement",
+                    self.context,
+                    method
+                )
+            });
+        }
+        let sysvar = match object {
+            Expr::Ident(name) => self.sysvar(name),
+            _ => None,
+        };
+        let ret = sysvar.and_then(|s| s.method_type(method)).ok_or_else(|| {
+            anyhow::anyhow!(
+                "In {}: call to {} can only be used as a statement",
+                self.context,
+                method
+            )
+        })?;
+        match args {
+            [arg] if self.is_integer_expr(arg)? => Ok(ret),
+            _ => anyhow::bail!(
+                "In {}: {} expects a single integer argument",
+                self.context,
+                method
+            ),
+        }
+    }
+
+    fn is_integer_expr(&self, expr: &Expr) -> Result<bool> {
+        Ok(
+            matches!(expr, Expr::Literal(Literal::UInt(_) | Literal::Int(_)))
+                || self.type_of(expr)?.is_integer(),
+        )
+    }
+
+    fn field_type(&self, object: &Expr, field: &str) -> Result<Type> {
+        if let Expr::Ident(name) = object {
+            if let Some(sysvar) = self.sysvar(name) {
+                return sysvar.field_type(field).ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "In {}: sysvar {} has no field {}",
+                        self.context,
+                        name,
+                        field
+                    )
+                });
+            }
+            if let Some(param) = self.param(name).filter(|p| p.ty.is_account()) {
+                if let Type::Account(account_name) = &param.ty {
+                    let field_def = self
+                        .program
+                        .accounts
+                        .iter()
+                        .find(|a| a.name == *account_name)
+                        .and_then(|a| a.fields.iter().find(|f| f.name == field));
+                    if let Some(field_def) = field_def {
+                        return Ok(field_def.ty.clone());
+                    }
+                }
+                if let Some(property) = property(self.program, self.instruction, object, field) {
+                    return Ok(property.ty());
+                }
+                anyhow::bail!("In {}: {} has no field {}", self.context, name, field);
+            }
+        }
+        let ty = self.type_of(object)?;
+        let field_def = match &ty {
+            Type::Struct(name) => self
+                .program
+                .structs
+                .iter()
+                .find(|s| s.name == *name)
+                .and_then(|s| s.fields.iter().find(|f| f.name == field)),
+            _ => None,
+        };
+        match field_def {
+            Some(field_def) => Ok(field_def.ty.clone()),
+            None => anyhow::bail!(
+                "In {}: type {} has no field {}",
+                self.context,
+                ty.to_rust_type(),
+                field
+            ),
+        }
+    }
+
+    fn binary_type(&self, op: &BinOp, left: &Expr, right: &Expr) -> Result<Type> {
+        // Let the non-literal side decide the operand type.
+        let operand = if matches!(left, Expr::Literal(Literal::UInt(_) | Literal::Int(_))) {
+            self.type_of(right)?
+        } else {
+            self.type_of(left)?
+        };
+        self.check(left, &operand)?;
+        self.check(right, &operand)?;
+
+        let ok = match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => operand.is_integer(),
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => operand.is_integer(),
+            BinOp::Eq | BinOp::Ne => true,
+            BinOp::And | BinOp::Or => operand == Type::Bool,
+        };
+        if !ok {
+            anyhow::bail!(
+                "In {}: invalid operand type {} for {:?}",
+                self.context,
+                operand.to_rust_type(),
+                op
+            );
+        }
+
+        Ok(match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => operand,
+            _ => Type::Bool,
+        })
+    }
+}
@@ -0,0 +1,180 @@
+//! Resolution of declared type names, and where each kind of type may
+//! appear.
+//!
+//! The parser reads every type the same way, so `Named` types are resolved
+//! here to the `struct` or `account` they name. Data types are allowed
+//! everywhere; account types only as instruction parameters, and never
+//! inside `Vec` or `Option`.
+
+use anyhow::Result;
+use solx_ast::*;
+
+use crate::at;
+
+pub(crate) fn resolve(program: &mut Program) -> Result<()> {
+    if let Err((span, e)) = resolve_names(program) {
+        return Err(at(program, span, e));
+    }
+    for def in &program.structs {
+        if contains(program, &def.name, &def.fields, &mut Vec::new()) {
+            return Err(at(
+                program,
+                def.span,
+                anyhow::anyhow!("In struct {}: {} contains itself", def.name, def.name),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Resolve every type in the program, or give the first error with the
+/// span of the declaration it is in.
+fn resolve_names(program: &mut Program) -> std::result::Result<(), (Span, anyhow::Error)> {
+    let names = Names {
+        structs: program.structs.iter().map(|s| s.name.clone()).collect(),
+        accounts: program.accounts.iter().map(|a| a.name.clone()).collect(),
+    };
+    for def in &mut program.structs {
+        let context = format!("struct {}", def.name);
+        names
+            .fields(&context, &mut def.fields)
+            .map_err(|e| (def.span, e))?;
+    }
+    for def in &mut program.accounts {
+        let context = format!("account {}", def.name);
+        names
+            .fields(&context, &mut def.fields)
+            .map_err(|e| (def.span, e))?;
+    }
+    for def in &mut program.events {
+        let context = format!("event {}", def.name);
+        names
+            .fields(&context, &mut def.fields)
+            .map_err(|e| (def.span, e))?;
+    }
+    for def in &mut program.externs {
+        let context = format!("extern fn {}", def.name);
+        for (name, ty) in &mut def.params {
+            let what = format!("parameter {}", name);
+            names.data(&context, &what, ty).map_err(|e| (def.span, e))?;
+        }
+        if let Some(ty) = &mut def.ret {
+            names
+                .data(&context, "return type", ty)
+                .map_err(|e| (def.span, e))?;
+        }
+    }
+    for instruction in &mut program.instructions {
+        let context = format!("instruction {}", instruction.name);
+        for param in &mut instruction.params {
+            names.param(&context, param).map_err(|e| (param.span, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// The type names a program declares.
+struct Names {
+    structs: Vec<String>,
+    accounts: Vec<String>,
+}
+
+impl Names {
+    /// Resolve `Named` types within `ty`, checking declared names exist.
+    fn resolve(&self, context: &str, ty: &mut Type) -> Result<()> {
+        match ty {
+            Type::Vec(inner) | Type::Option(inner) => self.resolve(context, inner),
+            Type::Named(name) => {
+                *ty = if self.structs.contains(name) {
+                    Type::Struct(name.clone())
+                } else if self.accounts.contains(name) {
+                    Type::Account(name.clone())
+                } else {
+                    anyhow::bail!("In {}: unknown type {}", context, name)
+                };
+                Ok(())
+            }
+            Type::Struct(name) if !self.structs.contains(name) => {
+                anyhow::bail!("In {}: unknown struct {}", context, name)
+            }
+            Type::Account(name) if !self.accounts.contains(name) => {
+                anyhow::bail!("In {}: unknown account type {}", context, name)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn fields(&self, context: &str, fields: &mut [Field]) -> Result<()> {
+        for field in fields {
+            self.data(context, &format!("field {}", field.name), &mut field.ty)?;
+        }
+        Ok(())
+    }
+
+    /// Resolve `ty` and require a data type, e.g. for a field.
+    fn data(&self, context: &str, what: &str, ty: &mut Type) -> Result<()> {
+        self.resolve(context, ty)?;
+        if let Some(account) = account_type_in(ty) {
+            anyhow::bail!(
+                "In {}: {} has account type {}; only instruction parameters can be accounts",
+                context,
+                what,
+                account
+            );
+        }
+        Ok(())
+    }
+
+    /// Resolve a parameter's type: an account type or a data type.
+    fn param(&self, context: &str, param: &mut Param) -> Result<()> {
+        self.resolve(context, &mut param.ty)?;
+        if param.ty.is_account() {
+            return Ok(());
+        }
+        if let Some(account) = account_type_in(&param.ty) {
+            anyhow::bail!(
+                "In {}: parameter {} has type {}; account type {} cannot be nested",
+                context,
+                param.name,
+                param.ty,
+                account
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The first account type within `ty`, if any.
+fn account_type_in(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Vec(inner) | Type::Option(inner) => account_type_in(inner),
+        ty if ty.is_account() => Some(ty),
+        _ => None,
+    }
+}
+
+/// Whether `fields` hold a value of the struct `name`, directly or through
+/// other structs. Such a struct would have no finite size.
+fn contains(program: &Program, name: &str, fields: &[Field], seen: &mut Vec<String>) -> bool {
+    fields.iter().any(|field| {
+        let mut ty = &field.ty;
+        while let Type::Vec(inner) | Type::Option(inner) = ty {
+            ty = inner;
+        }
+        let Type::Struct(inner) = ty else {
+            return false;
+        };
+        if inner == name {
+            return true;
+        }
+        if seen.contains(inner) {
+            return false;
+        }
+        seen.push(inner.clone());
+        program
+            .structs
+            .iter()
+            .find(|s| s.name == *inner)
+            .is_some_and(|def| contains(program, name, &def.fields, seen))
+    })
+}
@@ -0,0 +1,304 @@
+//! Anchor IDL generation straight from the HIR, without running `anchor build`.
+//!
+//! The output follows the IDL spec `0.1.0` emitted by Anchor 0.30: snake_case
+//! instruction names, explicit discriminators, and account/event layouts
+//! listed under `types`.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use solx_ast::*;
+use solx_hir::accounts::seeds;
+use solx_hir::idl::sighash;
+use solx_hir::naming::to_snake_case;
+use solx_hir::Hir;
+
+pub const IDL_SPEC: &str = "0.1.0";
+
+#[derive(Debug, Serialize)]
+pub struct Idl {
+    pub address: String,
+    pub metadata: IdlMetadata,
+    pub instructions: Vec<IdlInstruction>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<IdlDiscriminated>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<IdlDiscriminated>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<IdlError>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<IdlTypeDef>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlMetadata {
+    pub name: String,
+    pub version: String,
+    pub spec: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlInstruction {
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+    pub discriminator: [u8; 8],
+    pub accounts: Vec<IdlInstructionAccount>,
+    pub args: Vec<IdlField>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlInstructionAccount {
+    pub name: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub writable: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub signer: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pda: Option<Value>,
+}
+
+/// Entry of the `accounts` and `events` sections; layouts live in `types`.
+#[derive(Debug, Serialize)]
+pub struct IdlDiscriminated {
+    pub name: String,
+    pub discriminator: [u8; 8],
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlError {
+    pub code: u32,
+    pub name: String,
+    pub msg: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlTypeDef {
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+    #[serde(rename = "type")]
+    pub ty: Value,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub docs: Vec<String>,
+    #[serde(rename = "type")]
+    pub ty: Value,
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+pub fn generate_idl(hir: &Hir, address: &str) -> Idl {
+    let program = &hir.program;
+
+    let instructions = program
+        .instructions
+        .iter()
+        .map(|instruction| IdlInstruction {
+            name: instruction.name.clone(),
+            docs: instruction.docs.clone(),
+            discriminator: sighash("global", &instruction.name),
+            accounts: hir
+                .instruction_accounts(instruction)
+                .into_iter()
+                .map(|a| IdlInstructionAccount {
+                    pda: instruction
+                        .params
+                        .iter()
+                        .find(|p| p.name == a.name)
+                        .and_then(seeds)
+                        .map(pda),
+                    name: a.name,
+                    writable: a.writable,
+                    signer: a.signer,
+                    address: a.address,
+                })
+                .collect(),
+            args: instruction
+                .params
+                .iter()
+                .filter(|p| !p.ty.is_account())
+                .map(|p| IdlField {
+                    name: p.name.clone(),
+                    docs: Vec::new(),
+                    ty: idl_type(&p.ty),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let accounts = program
+        .accounts
+        .iter()
+        .map(|a| IdlDiscriminated {
+            name: a.name.clone(),
+            discriminator: sighash("account", &a.name),
+        })
+        .collect();
+
+    let events = program
+        .events
+        .iter()
+        .map(|e| IdlDiscriminated {
+            name: e.name.clone(),
+            discriminator: sighash("event", &e.name),
+        })
+        .collect();
+
+    let errors = hir
+        .errors
+        .iter()
+        .map(|e| IdlError {
+            code: e.code,
+            name: e.name.clone(),
+            msg: e.msg.clone(),
+        })
+        .collect();
+
+    let types = program
+        .accounts
+        .iter()
+        .map(|a| struct_type(&a.name, &a.docs, &a.fields))
+        .chain(
+            program
+                .structs
+                .iter()
+                .map(|s| struct_type(&s.name, &s.docs, &s.fields)),
+        )
+        .chain(
+            program
+                .events
+                .iter()
+                .map(|e| struct_type(&e.name, &e.docs, &e.fields)),
+        )
+        .collect();
+
+    Idl {
+        address: address.to_string(),
+        metadata: IdlMetadata {
+            name: to_snake_case(&program.name),
+            version: "0.1.0".to_string(),
+            spec: IDL_SPEC.to_string(),
+            description: "Created with SOL-X".to_string(),
+        },
+        instructions,
+        accounts,
+        events,
+        errors,
+        types,
+    }
+}
+
+fn pda(seeds: &[Seed]) -> Value {
+    let seeds: Vec<Value> = seeds
+        .iter()
+        .map(|seed| match seed {
+            Seed::Const(s) => json!({ "kind": "const", "value": s.as_bytes() }),
+            Seed::Account(name) => json!({ "kind": "account", "path": name }),
+        })
+        .collect();
+    json!({ "seeds": seeds })
+}
+
+fn struct_type(name: &str, docs: &[String], fields: &[Field]) -> IdlTypeDef {
+    let fields: Vec<IdlField> = fields
+        .iter()
+        .map(|f| IdlField {
+            name: f.name.clone(),
+            docs: f.docs.clone(),
+            ty: idl_type(&f.ty),
+        })
+        .collect();
+    IdlTypeDef {
+        name: name.to_string(),
+        docs: docs.to_vec(),
+        ty: json!({ "kind": "struct", "fields": fields }),
+    }
+}
+
+fn idl_type(ty: &Type) -> Value {
+    let name = match ty {
+        Type::Pubkey => "pubkey",
+        Type::U8 => "u8",
+        Type::U16 => "u16",
+        Type::U32 => "u32",
+        Type::U64 => "u64",
+        Type::I8 => "i8",
+        Type::I16 => "i16",
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::Bool => "bool",
+        Type::String => "string",
+        Type::Vec(inner) => return json!({ "vec": idl_type(inner) }),
+        Type::Option(inner) => return json!({ "option": idl_type(inner) }),
+        Type::Struct(name) => return json!({ "defined": { "name": name } }),
+        Type::Signer | Type::Account(_) | Type::TokenAccount | Type::Mint | Type::Named(_) => {
+            unreachable!("{} is not a data type", ty)
+        }
+    };
+    Value::String(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_idl() {
+        let source = r#"
+program Counter
+
+/// Counter state
+account CounterState {
+  authority: Pubkey
+  count: u64
+}
+
+instruction initialize(authority: Signer, state: CounterState) {
+  init account state: CounterState payer authority
+  state.authority = authority.key
+  state.count = 0
+}
+
+instruction add(authority: Signer, state: CounterState, amount: u64) {
+  require state.authority == authority.key, "Unauthorized"
+  state.count += amount
+}
+"#;
+        let program = solx_parser::parse(source).unwrap();
+        let hir = Hir::from_ast(program).unwrap();
+        let idl =
+            serde_json::to_value(generate_idl(&hir, "11111111111111111111111111111111")).unwrap();
+
+        assert_eq!(idl["metadata"]["name"], "counter");
+        assert_eq!(
+            idl["instructions"][0]["discriminator"],
+            json!([175, 175, 109, 31, 13, 152, 155, 237])
+        );
+        assert_eq!(
+            idl["instructions"][0]["accounts"],
+            json!([
+                { "name": "authority", "writable": true, "signer": true },
+                { "name": "state", "writable": true, "signer": true },
+                { "name": "system_program", "address": "11111111111111111111111111111111" }
+            ])
+        );
+        assert_eq!(
+            idl["instructions"][1]["args"],
+            json!([{ "name": "amount", "type": "u64" }])
+        );
+        assert_eq!(
+            idl["errors"],
+            json!([{ "code": 6000, "name": "Unauthorized", "msg": "Unauthorized" }])
+        );
+        assert_eq!(idl["types"][0]["docs"], json!(["Counter state"]));
+    }
+}
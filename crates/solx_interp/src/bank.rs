@@ -0,0 +1,201 @@
+//! The in-memory ledger instructions run against.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+use solx_hir::accounts::{ASSOCIATED_TOKEN_PROGRAM_ID, SYSTEM_PROGRAM_ID, TOKEN_PROGRAM_ID};
+
+use crate::value::{Pubkey, Value};
+
+/// Bytes of an SPL token account.
+pub const TOKEN_ACCOUNT_SPACE: u64 = 165;
+
+/// Bytes of an SPL mint.
+pub const MINT_SPACE: u64 = 82;
+
+/// Default `Rent` sysvar values.
+pub const LAMPORTS_PER_BYTE_YEAR: u64 = 3480;
+pub const EXEMPTION_THRESHOLD_YEARS: u64 = 2;
+pub const BURN_PERCENT: u8 = 50;
+const ACCOUNT_STORAGE_OVERHEAD: u64 = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub lamports: u64,
+    pub owner: Pubkey,
+    pub data: Data,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data {
+    /// No data: a wallet, or an address nothing has been created at
+    Empty,
+    /// An account of the program, by account type name
+    Program {
+        ty: String,
+        fields: BTreeMap<String, Value>,
+    },
+    Token {
+        mint: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+    },
+    Mint {
+        authority: Pubkey,
+        supply: u64,
+        decimals: u8,
+    },
+}
+
+/// The `Clock` sysvar. Tests move time forward by setting it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Clock {
+    pub slot: u64,
+    pub epoch: u64,
+    pub leader_schedule_epoch: u64,
+    pub unix_timestamp: i64,
+    pub epoch_start_timestamp: i64,
+}
+
+/// Accounts by address, and the sysvars. Addresses without an account read
+/// as empty system accounts with no lamports.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Bank {
+    accounts: BTreeMap<Pubkey, Account>,
+    pub clock: Clock,
+}
+
+impl Bank {
+    pub fn account(&self, address: &Pubkey) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = (&Pubkey, &Account)> {
+        self.accounts.iter()
+    }
+
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.account(address).map_or(0, |a| a.lamports)
+    }
+
+    /// Give `address` lamports, creating a system account if needed.
+    pub fn airdrop(&mut self, address: Pubkey, lamports: u64) {
+        self.accounts
+            .entry(address)
+            .or_insert_with(|| Account {
+                lamports: 0,
+                owner: system_program(),
+                data: Data::Empty,
+            })
+            .lamports += lamports;
+    }
+
+    /// A field of a program account.
+    pub fn field(&self, address: &Pubkey, name: &str) -> Option<&Value> {
+        match &self.account(address)?.data {
+            Data::Program { fields, .. } => fields.get(name),
+            _ => None,
+        }
+    }
+
+    pub fn create_mint(&mut self, address: Pubkey, authority: Pubkey, decimals: u8) {
+        self.set(
+            address,
+            Account {
+                lamports: minimum_balance(MINT_SPACE),
+                owner: token_program(),
+                data: Data::Mint {
+                    authority,
+                    supply: 0,
+                    decimals,
+                },
+            },
+        );
+    }
+
+    /// Create the associated token account of `owner` for `mint` and return
+    /// its address.
+    pub fn create_token_account(&mut self, owner: Pubkey, mint: Pubkey) -> Pubkey {
+        let address = associated_token_address(&owner, &mint);
+        self.set(address, token_account(owner, mint));
+        address
+    }
+
+    /// Mint `amount` tokens into a token account.
+    pub fn mint_to(&mut self, address: &Pubkey, amount: u64) -> Result<()> {
+        let Some(Account {
+            data:
+                Data::Token {
+                    mint,
+                    amount: balance,
+                    ..
+                },
+            ..
+        }) = self.accounts.get_mut(address)
+        else {
+            anyhow::bail!("{} is not a token account", address);
+        };
+        *balance += amount;
+        let mint = *mint;
+        match self.accounts.get_mut(&mint) {
+            Some(Account {
+                data: Data::Mint { supply, .. },
+                ..
+            }) => *supply += amount,
+            _ => anyhow::bail!("mint {} does not exist", mint),
+        }
+        Ok(())
+    }
+
+    /// Close an account, moving its lamports to `recipient`.
+    pub fn close(&mut self, address: &Pubkey, recipient: Pubkey) {
+        if let Some(account) = self.accounts.remove(address) {
+            self.airdrop(recipient, account.lamports);
+        }
+    }
+
+    pub(crate) fn set(&mut self, address: Pubkey, account: Account) {
+        self.accounts.insert(address, account);
+    }
+
+    pub(crate) fn account_mut(&mut self, address: &Pubkey) -> Option<&mut Account> {
+        self.accounts.get_mut(address)
+    }
+}
+
+/// Lamports that make an account of `space` bytes rent exempt.
+pub fn minimum_balance(space: u64) -> u64 {
+    (ACCOUNT_STORAGE_OVERHEAD + space) * LAMPORTS_PER_BYTE_YEAR * EXEMPTION_THRESHOLD_YEARS
+}
+
+/// The associated token account of `owner` for `mint`.
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[&owner.0, &token_program().0, &mint.0],
+        &builtin(ASSOCIATED_TOKEN_PROGRAM_ID),
+    )
+}
+
+pub(crate) fn token_account(owner: Pubkey, mint: Pubkey) -> Account {
+    Account {
+        lamports: minimum_balance(TOKEN_ACCOUNT_SPACE),
+        owner: token_program(),
+        data: Data::Token {
+            mint,
+            owner,
+            amount: 0,
+        },
+    }
+}
+
+pub fn system_program() -> Pubkey {
+    builtin(SYSTEM_PROGRAM_ID)
+}
+
+pub fn token_program() -> Pubkey {
+    builtin(TOKEN_PROGRAM_ID)
+}
+
+fn builtin(address: &str) -> Pubkey {
+    Pubkey::from_base58(address).expect("builtin program IDs are valid")
+}
@@ -0,0 +1,634 @@
+//! Property-based testing of instruction sequences.
+//!
+//! Each run starts from an empty bank and makes random calls, choosing
+//! instructions, accounts, arguments and signers from the parameter types.
+//! Accounts come from a small pool of names: the wallets `alice`, `bob` and
+//! `carol`, the names used in invariants, and the names of account
+//! parameters; PDAs and associated token accounts are usually derived.
+//! Instructions with `Vec`, `Option` or struct arguments are not called,
+//! since calls have no way to write those values.
+//! Calls the program rejects are expected and skipped. A run fails when a
+//! call panics, or when an invariant is false after a call: a program
+//! `invariant`, once every account it reads exists, or the `invariant` of
+//! an account type, for every account of the type.
+//!
+//! Failing sequences are shrunk, by dropping calls and moving integers
+//! towards zero, and reported as a `test` block that reproduces them.
+
+use anyhow::Result;
+
+use solx_ast::*;
+use solx_hir::accounts::seeds;
+use solx_hir::invariants::on_account;
+use solx_hir::Hir;
+
+use crate::runner::{signs_by_default, TestRun};
+use crate::value::int_range;
+use crate::{Data, Pubkey};
+
+/// Wallets that sign fuzzed calls.
+const WALLETS: [&str; 3] = ["alice", "bob", "carol"];
+
+/// Sequences run while shrinking, at most.
+const SHRINK_BUDGET: usize = 2000;
+
+pub struct FuzzOptions {
+    /// Sequences to run
+    pub runs: u32,
+    /// Calls per sequence
+    pub calls: usize,
+    pub seed: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct FuzzReport {
+    pub runs: u32,
+    pub calls: u64,
+    /// Calls the program rejected with an error
+    pub rejected: u64,
+    pub failure: Option<Counterexample>,
+}
+
+/// A shrunk sequence of calls that fails.
+#[derive(Debug)]
+pub struct Counterexample {
+    pub calls: Vec<TestCall>,
+    /// Why the last call or the state after it is wrong
+    pub message: String,
+    /// The invariant that does not hold, if any
+    pub invariant: Option<Expr>,
+}
+
+impl Counterexample {
+    /// A `test` block reproducing the failure.
+    pub fn to_test(&self, name: &str) -> String {
+        let mut out = format!("test \"{}\" {{\n", name);
+        for call in &self.calls {
+            out.push_str(&format!("  {}\n", format_call(call)));
+        }
+        if let Some(invariant) = &self.invariant {
+            out.push_str(&format!("  expect {}\n", invariant));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Fuzz the program's instructions against its invariants. Fails only if
+/// the program cannot be interpreted, e.g. it calls an `extern fn`.
+pub fn fuzz(hir: &Hir, options: &FuzzOptions) -> Result<FuzzReport> {
+    let mut generator = Generator::new(hir, options.seed);
+    if generator.instructions.is_empty() {
+        anyhow::bail!(
+            "No instructions to fuzz; Vec, Option and struct arguments cannot be generated"
+        );
+    }
+    let mut report = FuzzReport::default();
+    for _ in 0..options.runs {
+        let calls: Vec<TestCall> = (0..options.calls).map(|_| generator.call()).collect();
+        let run = run(hir, &calls)?;
+        report.runs += 1;
+        report.calls += run.calls;
+        report.rejected += run.rejected;
+        if let Some(failure) = run.failure {
+            report.failure = Some(shrink(hir, calls, failure)?);
+            break;
+        }
+    }
+    Ok(report)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Panic,
+    /// Index into `Program::invariants`
+    Invariant(usize),
+    /// Index into the invariants of an account type
+    AccountInvariant(String, usize),
+}
+
+struct Failure {
+    /// Index of the call that failed, or after which an invariant failed
+    call: usize,
+    kind: Kind,
+    message: String,
+    /// The invariant that does not hold, in terms of the test's names
+    invariant: Option<Expr>,
+}
+
+struct Run {
+    calls: u64,
+    rejected: u64,
+    failure: Option<Failure>,
+}
+
+fn run(hir: &Hir, calls: &[TestCall]) -> Result<Run> {
+    let mut test = TestRun::new(hir);
+    let mut result = Run {
+        calls: 0,
+        rejected: 0,
+        failure: None,
+    };
+    for (i, call) in calls.iter().enumerate() {
+        result.calls += 1;
+        match test.execute(call)? {
+            // A panic is a bug; any other error is the program refusing a
+            // bad call
+            Err(err) if err.code.is_none() && err.name == "ProgramFailedToComplete" => {
+                result.failure = Some(Failure {
+                    call: i,
+                    kind: Kind::Panic,
+                    message: format!("{} panicked: {}", call.instruction, err.message),
+                    invariant: None,
+                });
+                break;
+            }
+            Err(_) => result.rejected += 1,
+            Ok(_) => {
+                if let Some((index, message)) = violated(&test, &hir.program.invariants)? {
+                    result.failure = Some(Failure {
+                        call: i,
+                        kind: Kind::Invariant(index),
+                        message,
+                        invariant: Some(hir.program.invariants[index].condition.clone()),
+                    });
+                    break;
+                }
+                if let Some(failure) = violated_account_invariant(hir, &mut test, &calls[..=i])? {
+                    result.failure = Some(Failure { call: i, ..failure });
+                    break;
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The first invariant that does not hold, with its operands.
+fn violated(test: &TestRun, invariants: &[InvariantDef]) -> Result<Option<(usize, String)>> {
+    for (i, invariant) in invariants.iter().enumerate() {
+        if !reads_existing_accounts(test, &invariant.condition)? {
+            continue;
+        }
+        match test.eval(&invariant.condition)? {
+            crate::Value::Bool(true) => {}
+            crate::Value::Bool(false) => {
+                let message = format!(
+                    "invariant violated: {}",
+                    test.describe(&invariant.condition)
+                );
+                return Ok(Some((i, message)));
+            }
+            value => anyhow::bail!(
+                "invariant {} must be a bool, found {}",
+                invariant.condition,
+                value
+            ),
+        }
+    }
+    Ok(None)
+}
+
+/// The first account invariant that does not hold for a program account
+/// in the bank. Accounts are named as in the calls that passed them.
+fn violated_account_invariant(
+    hir: &Hir,
+    test: &mut TestRun,
+    calls: &[TestCall],
+) -> Result<Option<Failure>> {
+    let accounts: Vec<(Pubkey, String)> = test
+        .interp
+        .bank
+        .accounts()
+        .filter_map(|(address, account)| match &account.data {
+            Data::Program { ty, .. } => Some((*address, ty.clone())),
+            _ => None,
+        })
+        .collect();
+    for (address, ty) in accounts {
+        let Some(def) = hir.program.accounts.iter().find(|a| a.name == ty) else {
+            continue;
+        };
+        let name = calls
+            .iter()
+            .flat_map(|c| &c.args)
+            .find_map(|(_, value)| match value {
+                Expr::Ident(name) if test.address(name).ok() == Some(address) => Some(name.clone()),
+                _ => None,
+            });
+        let object = name.as_deref().unwrap_or("account");
+        for (i, invariant) in def.invariants.iter().enumerate() {
+            let condition = on_account(&invariant.condition, object);
+            let (value, description) = test.eval_at(object, address, &condition)?;
+            if value == crate::Value::Bool(true) {
+                continue;
+            }
+            let message = invariant
+                .message
+                .clone()
+                .unwrap_or_else(|| solx_hir::invariants::default_message(&ty));
+            return Ok(Some(Failure {
+                call: 0,
+                kind: Kind::AccountInvariant(ty.clone(), i),
+                message: match &name {
+                    Some(_) => format!("{}: {}", message, description),
+                    None => format!("{}: {} at {}", message, description, address),
+                },
+                invariant: name.map(|_| condition),
+            }));
+        }
+    }
+    Ok(None)
+}
+
+fn reads_existing_accounts(test: &TestRun, condition: &Expr) -> Result<bool> {
+    let mut names = Vec::new();
+    condition.visit(&mut |e| {
+        if let Expr::FieldAccess { object, .. } = e {
+            if let Expr::Ident(name) = object.as_ref() {
+                names.push(name.clone());
+            }
+        }
+    });
+    for name in names {
+        let address = test.address(&name)?;
+        let exists = test
+            .interp
+            .bank
+            .account(&address)
+            .is_some_and(|a| matches!(a.data, Data::Program { .. }));
+        if !exists {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Shrink a failing sequence to a smaller one that fails the same way.
+fn shrink(hir: &Hir, mut calls: Vec<TestCall>, mut failure: Failure) -> Result<Counterexample> {
+    calls.truncate(failure.call + 1);
+    let mut budget = SHRINK_BUDGET;
+    let mut try_candidate = |candidate: Vec<TestCall>,
+                             calls: &mut Vec<TestCall>,
+                             failure: &mut Failure|
+     -> Result<bool> {
+        if budget == 0 {
+            return Ok(false);
+        }
+        budget -= 1;
+        match run(hir, &candidate)?.failure {
+            Some(found) if found.kind == failure.kind => {
+                *calls = candidate;
+                calls.truncate(found.call + 1);
+                *failure = found;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    };
+
+    loop {
+        let mut progress = false;
+
+        // Drop calls, last first
+        let mut i = calls.len();
+        while i > 0 {
+            i -= 1;
+            if i >= calls.len() || calls.len() == 1 {
+                continue;
+            }
+            let mut candidate = calls.clone();
+            candidate.remove(i);
+            progress |= try_candidate(candidate, &mut calls, &mut failure)?;
+        }
+
+        // Move integer arguments towards zero, and sign as by default
+        for i in 0..calls.len() {
+            for j in 0..calls[i].args.len() {
+                let Some(value) = int_literal(&calls[i].args[j].1) else {
+                    continue;
+                };
+                for smaller in [0, value / 2, value - value.signum()] {
+                    if smaller == value || i >= calls.len() {
+                        continue;
+                    }
+                    let mut candidate = calls.clone();
+                    candidate[i].args[j].1 = int_expr(smaller);
+                    if try_candidate(candidate, &mut calls, &mut failure)? {
+                        progress = true;
+                        break;
+                    }
+                }
+            }
+            if i < calls.len() && calls[i].signers.is_some() {
+                let mut candidate = calls.clone();
+                candidate[i].signers = None;
+                progress |= try_candidate(candidate, &mut calls, &mut failure)?;
+            }
+        }
+
+        if !progress {
+            break;
+        }
+    }
+
+    Ok(Counterexample {
+        calls,
+        message: failure.message,
+        invariant: failure.invariant,
+    })
+}
+
+fn int_literal(expr: &Expr) -> Option<i128> {
+    match expr {
+        Expr::Literal(Literal::UInt(value)) => Some(*value as i128),
+        Expr::Literal(Literal::Int(value)) => Some(*value as i128),
+        _ => None,
+    }
+}
+
+/// An integer that fits a SOL-X integer type.
+fn int_expr(value: i128) -> Expr {
+    Expr::Literal(if value < 0 {
+        Literal::Int(value as i64)
+    } else {
+        Literal::UInt(value as u64)
+    })
+}
+
+fn format_call(call: &TestCall) -> String {
+    let args: Vec<String> = call
+        .args
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect();
+    let mut out = format!("call {}({})", call.instruction, args.join(", "));
+    if let Some(signers) = &call.signers {
+        out.push_str(&format!(" signed_by({})", signers.join(", ")));
+    }
+    out
+}
+
+/// Random calls of the program's instructions.
+struct Generator<'a> {
+    /// The instructions whose arguments can be generated
+    instructions: Vec<&'a Instruction>,
+    rng: Rng,
+    /// Names of accounts that are not wallets
+    accounts: Vec<String>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(hir: &'a Hir, seed: u64) -> Self {
+        let mut accounts: Vec<String> = Vec::new();
+        let mut add = |name: &str| {
+            if !WALLETS.contains(&name) && !accounts.iter().any(|a| a == name) {
+                accounts.push(name.to_string());
+            }
+        };
+        for invariant in &hir.program.invariants {
+            invariant.condition.visit(&mut |e| {
+                if let Expr::FieldAccess { object, .. } = e {
+                    if let Expr::Ident(name) = object.as_ref() {
+                        add(name);
+                    }
+                }
+            });
+        }
+        for param in hir.program.instructions.iter().flat_map(|i| &i.params) {
+            if param.ty.is_account() && param.ty != Type::Signer && !is_derived(param) {
+                add(&param.name);
+            }
+        }
+        let instructions = hir
+            .program
+            .instructions
+            .iter()
+            .filter(|i| {
+                i.params
+                    .iter()
+                    .all(|p| p.ty.is_account() || is_literal(&p.ty))
+            })
+            .collect();
+        Generator {
+            instructions,
+            rng: Rng(seed),
+            accounts,
+        }
+    }
+
+    fn call(&mut self) -> TestCall {
+        let instruction =
+            self.instructions[self.rng.below(self.instructions.len() as u64) as usize];
+        let mut args = Vec::new();
+        for param in &instruction.params {
+            let value = match &param.ty {
+                Type::Signer => Expr::Ident(self.wallet()),
+                ty if ty.is_account() => {
+                    // Derived addresses are usually left to the interpreter
+                    if is_derived(param) && self.rng.below(10) != 0 {
+                        continue;
+                    }
+                    Expr::Ident(self.account())
+                }
+                ty => self.value(ty),
+            };
+            args.push((param.name.clone(), value));
+        }
+
+        // Sometimes leave out one of the accounts that would sign
+        let mut signers = None;
+        if self.rng.below(10) == 0 {
+            let mut names: Vec<String> = args
+                .iter()
+                .filter(|(name, _)| {
+                    instruction
+                        .params
+                        .iter()
+                        .any(|p| p.name == *name && signs_by_default(instruction, p))
+                })
+                .filter_map(|(_, value)| match value {
+                    Expr::Ident(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect();
+            if !names.is_empty() {
+                names.remove(self.rng.below(names.len() as u64) as usize);
+            }
+            signers = Some(names);
+        }
+
+        TestCall {
+            instruction: instruction.name.clone(),
+            span: Span::default(),
+            args,
+            signers,
+            expect_err: None,
+        }
+    }
+
+    /// Usually the first wallet, so that calls build on each other.
+    fn wallet(&mut self) -> String {
+        if self.rng.below(2) == 0 {
+            return WALLETS[0].to_string();
+        }
+        WALLETS[self.rng.below(WALLETS.len() as u64) as usize].to_string()
+    }
+
+    /// Usually an account name, sometimes a wallet.
+    fn account(&mut self) -> String {
+        if self.accounts.is_empty() || self.rng.below(5) == 0 {
+            return self.wallet();
+        }
+        self.accounts[self.rng.below(self.accounts.len() as u64) as usize].clone()
+    }
+
+    fn value(&mut self, ty: &Type) -> Expr {
+        if let Some(range) = int_range(ty) {
+            let (low, high) = (*range.start(), *range.end());
+            // Edges and small numbers, which pass most `require`s
+            let value = match self.rng.below(8) {
+                0 => 0,
+                1 => 1,
+                2 => high,
+                3 => low,
+                4..=6 => self.rng.below(11) as i128,
+                _ => low + (self.rng.next() as u128 % (high - low + 1) as u128) as i128,
+            };
+            return int_expr(value.clamp(low, high));
+        }
+        match ty {
+            Type::Bool => Expr::Literal(Literal::Bool(self.rng.below(2) == 0)),
+            Type::Pubkey => Expr::Ident(self.account()),
+            _ => {
+                let text = ["", "a", "hello"][self.rng.below(3) as usize];
+                Expr::Literal(Literal::String(text.to_string()))
+            }
+        }
+    }
+}
+
+/// Whether values of `ty` can be written as literals in a call.
+fn is_literal(ty: &Type) -> bool {
+    int_range(ty).is_some() || matches!(ty, Type::Bool | Type::Pubkey | Type::String)
+}
+
+/// Whether the interpreter can derive the account's address.
+fn is_derived(param: &Param) -> bool {
+    seeds(param).is_some()
+        || param
+            .annotations
+            .iter()
+            .any(|a| matches!(a, ParamAnnotation::AssociatedToken { .. }))
+}
+
+/// SplitMix64, so that a seed reproduces a run on every platform.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_finds_and_shrinks_invariant_violation() {
+        let source = r#"
+program Counter
+
+account CounterState {
+  authority: Pubkey
+  count: u8
+}
+
+instruction initialize(authority: Signer, state: CounterState) {
+  init account state: CounterState payer authority
+  state.authority = authority.key
+}
+
+instruction add(authority: Signer, state: CounterState, amount: u8) {
+  require state.authority == authority.key, "Not the authority"
+  require amount <= 10, "Too much"
+  state.count += amount
+}
+
+invariant state.count < 12
+"#;
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let options = FuzzOptions {
+            runs: 500,
+            calls: 20,
+            seed: 7,
+        };
+        let failure = fuzz(&hir, &options)
+            .unwrap()
+            .failure
+            .expect("no failure found");
+        // initialize, then two adds of at least 12 in total
+        assert_eq!(failure.calls.len(), 3, "{}", failure.to_test("fuzz"));
+        assert!(failure
+            .message
+            .starts_with("invariant violated: state.count < 12"));
+
+        let test = failure.to_test("fuzz");
+        let reproducer = format!("{}\n{}", source, test);
+        let hir = Hir::from_ast(solx_parser::parse(&reproducer).unwrap()).unwrap();
+        let err = crate::runner::run_test(&hir, &hir.program.tests[0]).unwrap_err();
+        assert!(err.to_string().contains("expectation failed"), "{:#}", err);
+    }
+
+    #[test]
+    fn test_fuzz_checks_account_invariants() {
+        let source = r#"
+program Vault
+
+account Vault {
+  total_shares: u64
+  total_deposits: u64
+  invariant total_shares <= total_deposits, "Shares exceed deposits"
+}
+
+instruction open(user: Signer, vault: Vault) {
+  init account vault: Vault payer user
+}
+
+instruction deposit(user: Signer, vault: Vault, amount: u64, shares: u64) {
+  require amount <= 1000 && shares <= 1000, "Too much"
+  vault.total_deposits += amount
+  vault.total_shares += shares
+}
+"#;
+        let options = FuzzOptions {
+            runs: 200,
+            calls: 10,
+            seed: 3,
+        };
+        let hir = Hir::from_ast(solx_parser::parse(source).unwrap()).unwrap();
+        let failure = fuzz(&hir, &options)
+            .unwrap()
+            .failure
+            .expect("no failure found");
+        assert_eq!(failure.calls.len(), 2, "{}", failure.to_test("fuzz"));
+        assert!(failure
+            .message
+            .starts_with("Shares exceed deposits: vault.total_shares <= vault.total_deposits"));
+
+        // With the checks compiled in, the program rejects those deposits
+        let hir = hir.with_invariant_checks(true);
+        let report = fuzz(&hir, &options).unwrap();
+        assert!(report.failure.is_none());
+        assert!(report.rejected > 0);
+    }
+}
@@ -0,0 +1,1287 @@
+//! An in-process interpreter for SOL-X programs.
+//!
+//! Instructions run straight from the HIR against an in-memory [`Bank`],
+//! with the semantics of the generated Anchor program:
+//! - account constraints are checked in parameter order before the body
+//!   runs: signers, `init` (which charges the payer rent), PDA seeds,
+//!   associated token accounts, and that each account exists, belongs to
+//!   the right program and has the right type
+//! - `require` raises the program's custom error, or `RequireViolated`
+//! - `+ - * / %` overflow as [`Hir::arithmetic`] says
+//! - with [`Hir::invariant_checks`] on, the invariants of the accounts an
+//!   instruction writes are checked after its body
+//! - a failed instruction leaves the bank unchanged
+//!
+//! Failures are [`ProgramError`]s named as Anchor names them, so tests can
+//! expect `NotTheAuthority` or `ConstraintSeeds`. Mistakes in the call
+//! itself, such as a missing argument, are ordinary errors.
+//!
+//! Not modeled: compute limits, transaction size, CPIs into imported programs
+//! (recorded in the [`Receipt`] but not executed), and `extern fn` helpers
+//! unless an implementation is registered with [`Interpreter::helper`].
+
+pub mod bank;
+pub mod fuzz;
+pub mod runner;
+pub mod value;
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use solx_ast::*;
+use solx_hir::accounts::{account_space, seeds};
+use solx_hir::errors::ARITHMETIC_OVERFLOW;
+use solx_hir::properties::{property, Property};
+use solx_hir::sysvar::Sysvar;
+use solx_hir::{Arithmetic, Hir};
+
+pub use bank::{Account, Bank, Clock, Data};
+pub use value::{Pubkey, Value};
+
+use bank::{
+    associated_token_address, minimum_balance, system_program, token_account, token_program,
+    BURN_PERCENT, LAMPORTS_PER_BYTE_YEAR, MINT_SPACE, TOKEN_ACCOUNT_SPACE,
+};
+
+/// One instruction to execute: its accounts and arguments by parameter name,
+/// and the addresses that signed the transaction.
+#[derive(Debug, Clone, Default)]
+pub struct Call {
+    pub instruction: String,
+    pub accounts: BTreeMap<String, Pubkey>,
+    pub args: BTreeMap<String, Value>,
+    pub signers: BTreeSet<Pubkey>,
+}
+
+impl Call {
+    pub fn new(instruction: &str) -> Self {
+        Call {
+            instruction: instruction.to_string(),
+            ..Call::default()
+        }
+    }
+
+    pub fn account(mut self, name: &str, address: Pubkey) -> Self {
+        self.accounts.insert(name.to_string(), address);
+        self
+    }
+
+    pub fn arg(mut self, name: &str, value: Value) -> Self {
+        self.args.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn signer(mut self, address: Pubkey) -> Self {
+        self.signers.insert(address);
+        self
+    }
+}
+
+/// What a successful instruction did besides changing accounts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Receipt {
+    pub events: Vec<Event>,
+    /// Calls into imported programs, which are not executed
+    pub cpis: Vec<Cpi>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub name: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cpi {
+    pub program: String,
+    pub instruction: String,
+    pub args: Vec<Value>,
+}
+
+/// Why an instruction failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramError {
+    /// Anchor's name for the error, e.g. `AccountNotSigner`, or the name of a
+    /// custom error such as `NotTheAuthority`
+    pub name: String,
+    /// The error code, for Anchor and custom errors
+    pub code: Option<u32>,
+    pub message: String,
+}
+
+impl ProgramError {
+    fn anchor(name: &str, code: u32, message: String) -> Self {
+        ProgramError {
+            name: name.to_string(),
+            code: Some(code),
+            message,
+        }
+    }
+
+    /// A system program failure, which has no Anchor error code.
+    fn system(name: &str, message: String) -> Self {
+        ProgramError {
+            name: name.to_string(),
+            code: None,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{} ({}): {}", self.name, code, self.message),
+            None => write!(f, "{}: {}", self.name, self.message),
+        }
+    }
+}
+
+/// The result of an instruction that could be executed: a receipt, or the
+/// error that rolled it back.
+pub type Outcome = std::result::Result<Receipt, ProgramError>;
+
+type Helper = Box<dyn Fn(&[Value]) -> Value>;
+
+pub struct Interpreter<'a> {
+    hir: &'a Hir,
+    pub program_id: Pubkey,
+    pub bank: Bank,
+    helpers: BTreeMap<String, Helper>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// An interpreter with an empty bank. The program ID is derived from the
+    /// program name.
+    pub fn new(hir: &'a Hir) -> Self {
+        Interpreter {
+            hir,
+            program_id: Pubkey::named(&hir.program.name),
+            bank: Bank::default(),
+            helpers: BTreeMap::new(),
+        }
+    }
+
+    /// Implement the `extern fn` called `name`.
+    pub fn helper(&mut self, name: &str, helper: impl Fn(&[Value]) -> Value + 'static) {
+        self.helpers.insert(name.to_string(), Box::new(helper));
+    }
+
+    /// A PDA of the program.
+    pub fn pda(&self, seeds: &[&[u8]]) -> Pubkey {
+        Pubkey::find_program_address(seeds, &self.program_id)
+    }
+
+    /// Execute `call` and, if it succeeds, commit its changes to the bank.
+    /// PDAs and associated token accounts may be left out of
+    /// `call.accounts`; they are derived from the other accounts.
+    pub fn execute(&mut self, call: &Call) -> Result<Outcome> {
+        let instruction = self
+            .hir
+            .program
+            .instructions
+            .iter()
+            .find(|i| i.name == call.instruction)
+            .ok_or_else(|| anyhow::anyhow!("unknown instruction {}", call.instruction))?;
+        let args = check_args(instruction, call)?;
+        let accounts = self.resolve_accounts(instruction, call)?;
+
+        let mut frame = Frame {
+            hir: self.hir,
+            helpers: &self.helpers,
+            instruction,
+            program_id: self.program_id,
+            bank: self.bank.clone(),
+            accounts,
+            args,
+            signers: &call.signers,
+            receipt: Receipt::default(),
+        };
+        let result = frame.check_accounts().and_then(|()| frame.run());
+        match result {
+            Ok(()) => {
+                self.bank = frame.bank;
+                Ok(Ok(frame.receipt))
+            }
+            Err(Halt::Error(err)) => Ok(Err(err)),
+            Err(Halt::Invalid(err)) => {
+                Err(err.context(format!("In instruction {}", instruction.name)))
+            }
+        }
+    }
+
+    /// The address of every account parameter: as given, or derived from
+    /// its seeds or associated token constraint.
+    fn resolve_accounts(
+        &self,
+        instruction: &Instruction,
+        call: &Call,
+    ) -> Result<BTreeMap<String, Pubkey>> {
+        let params: Vec<&Param> = instruction
+            .params
+            .iter()
+            .filter(|p| p.ty.is_account())
+            .collect();
+        for name in call.accounts.keys() {
+            if !params.iter().any(|p| p.name == *name) {
+                anyhow::bail!("instruction {} has no account {}", instruction.name, name);
+            }
+        }
+
+        let mut accounts = call.accounts.clone();
+        // Derived addresses may depend on each other; derive until nothing changes
+        loop {
+            let mut progress = false;
+            for param in &params {
+                if accounts.contains_key(&param.name) {
+                    continue;
+                }
+                if let Some(address) = self.derive(param, &accounts) {
+                    accounts.insert(param.name.clone(), address);
+                    progress = true;
+                }
+            }
+            if !progress {
+                break;
+            }
+        }
+
+        if let Some(missing) = params.iter().find(|p| !accounts.contains_key(&p.name)) {
+            anyhow::bail!(
+                "missing account {} for instruction {}",
+                missing.name,
+                instruction.name
+            );
+        }
+        Ok(accounts)
+    }
+
+    /// The address `param` must have, when its constraints fix it and the
+    /// accounts they refer to are known.
+    fn derive(&self, param: &Param, accounts: &BTreeMap<String, Pubkey>) -> Option<Pubkey> {
+        if let Some(seeds) = seeds(param) {
+            let bytes = seed_bytes(seeds, accounts)?;
+            let refs: Vec<&[u8]> = bytes.iter().map(Vec::as_slice).collect();
+            return Some(self.pda(&refs));
+        }
+        param.annotations.iter().find_map(|a| match a {
+            ParamAnnotation::AssociatedToken { mint, authority } => Some(associated_token_address(
+                accounts.get(authority)?,
+                accounts.get(mint)?,
+            )),
+            _ => None,
+        })
+    }
+}
+
+fn check_args(instruction: &Instruction, call: &Call) -> Result<BTreeMap<String, Value>> {
+    for name in call.args.keys() {
+        if !instruction
+            .params
+            .iter()
+            .any(|p| p.name == *name && !p.ty.is_account())
+        {
+            anyhow::bail!("instruction {} has no argument {}", instruction.name, name);
+        }
+    }
+    let mut args = BTreeMap::new();
+    for param in instruction.params.iter().filter(|p| !p.ty.is_account()) {
+        let ty = &param.ty;
+        let value = call.args.get(&param.name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "missing argument {} for instruction {}",
+                param.name,
+                instruction.name
+            )
+        })?;
+        if !value.has_type(ty) {
+            anyhow::bail!(
+                "argument {} of instruction {} must be a {} (found {})",
+                param.name,
+                instruction.name,
+                ty.to_rust_type(),
+                value
+            );
+        }
+        args.insert(param.name.clone(), value.clone());
+    }
+    Ok(args)
+}
+
+/// The bytes of PDA seeds: string literals as UTF-8, accounts as their
+/// address. `None` if a seed account is not known.
+fn seed_bytes(seeds: &[Seed], accounts: &BTreeMap<String, Pubkey>) -> Option<Vec<Vec<u8>>> {
+    seeds
+        .iter()
+        .map(|seed| match seed {
+            Seed::Const(text) => Some(text.as_bytes().to_vec()),
+            Seed::Account(name) => accounts.get(name).map(|a| a.0.to_vec()),
+        })
+        .collect()
+}
+
+/// Why execution stopped: the program failed, or the call or program cannot
+/// be interpreted.
+enum Halt {
+    Error(ProgramError),
+    Invalid(anyhow::Error),
+}
+
+impl From<ProgramError> for Halt {
+    fn from(err: ProgramError) -> Self {
+        Halt::Error(err)
+    }
+}
+
+impl From<anyhow::Error> for Halt {
+    fn from(err: anyhow::Error) -> Self {
+        Halt::Invalid(err)
+    }
+}
+
+/// Bytes of an account of type `def`: the discriminator and its fields.
+/// One instruction in flight, working on a copy of the bank.
+struct Frame<'a> {
+    hir: &'a Hir,
+    helpers: &'a BTreeMap<String, Helper>,
+    instruction: &'a Instruction,
+    program_id: Pubkey,
+    bank: Bank,
+    accounts: BTreeMap<String, Pubkey>,
+    args: BTreeMap<String, Value>,
+    signers: &'a BTreeSet<Pubkey>,
+    receipt: Receipt,
+}
+
+impl Frame<'_> {
+    fn address(&self, name: &str) -> Pubkey {
+        self.accounts[name]
+    }
+
+    fn param(&self, name: &str) -> Option<&Param> {
+        self.instruction.params.iter().find(|p| p.name == name)
+    }
+
+    /// Anchor's account validation, in parameter order.
+    fn check_accounts(&mut self) -> std::result::Result<(), Halt> {
+        for param in &self.instruction.params {
+            let address = match self.accounts.get(&param.name) {
+                Some(address) => *address,
+                None => continue,
+            };
+            match &param.ty {
+                Type::Signer => self.check_signer(&param.name, address)?,
+                Type::Account(ty) => {
+                    match init_payer(self.instruction, &param.name) {
+                        Some(payer) => self.init_account(param, ty, payer)?,
+                        None => self.check_program_account(&param.name, ty, address)?,
+                    }
+                    self.check_seeds(param, address)?;
+                }
+                Type::TokenAccount => self.check_token_account(param, address)?,
+                Type::Mint => match self.bank.account(&address) {
+                    Some(Account {
+                        data: Data::Mint { .. },
+                        ..
+                    }) => {}
+                    account => {
+                        return Err(self
+                            .wrong_account(&param.name, address, account, token_program())
+                            .into())
+                    }
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn check_signer(&self, name: &str, address: Pubkey) -> std::result::Result<(), ProgramError> {
+        if self.signers.contains(&address) {
+            return Ok(());
+        }
+        Err(ProgramError::anchor(
+            "AccountNotSigner",
+            3010,
+            format!("{} ({}) did not sign", name, address),
+        ))
+    }
+
+    /// Why `account`, which is not of the expected type, was rejected: it
+    /// does not exist, belongs to a program other than `owner`, or holds
+    /// other data.
+    fn wrong_account(
+        &self,
+        name: &str,
+        address: Pubkey,
+        account: Option<&Account>,
+        owner: Pubkey,
+    ) -> ProgramError {
+        match account {
+            None
+            | Some(Account {
+                data: Data::Empty, ..
+            }) => ProgramError::anchor(
+                "AccountNotInitialized",
+                3012,
+                format!("{} ({}) is not initialized", name, address),
+            ),
+            Some(account) if account.owner != owner => ProgramError::anchor(
+                "AccountOwnedByWrongProgram",
+                3007,
+                format!("{} is owned by {}, not {}", name, account.owner, owner),
+            ),
+            Some(_) => ProgramError::anchor(
+                "AccountDidNotDeserialize",
+                3003,
+                format!("{} ({}) has the wrong account type", name, address),
+            ),
+        }
+    }
+
+    fn check_program_account(
+        &self,
+        name: &str,
+        ty: &str,
+        address: Pubkey,
+    ) -> std::result::Result<(), ProgramError> {
+        let account = self.bank.account(&address);
+        match account {
+            Some(Account {
+                owner,
+                data: Data::Program { ty: actual, .. },
+                ..
+            }) if *owner == self.program_id => {
+                if actual == ty {
+                    Ok(())
+                } else {
+                    Err(ProgramError::anchor(
+                        "AccountDiscriminatorMismatch",
+                        3002,
+                        format!("{} is a {}, not a {}", name, actual, ty),
+                    ))
+                }
+            }
+            account => Err(self.wrong_account(name, address, account, self.program_id)),
+        }
+    }
+
+    /// Create the account of an `init` statement, paid for by `payer`.
+    fn init_account(
+        &mut self,
+        param: &Param,
+        ty: &str,
+        payer: &str,
+    ) -> std::result::Result<(), Halt> {
+        let address = self.address(&param.name);
+        // Keypair accounts sign for their own creation; PDAs are signed for by
+        // the program
+        if seeds(param).is_none() {
+            self.check_signer(&param.name, address)?;
+        }
+        if let Some(account) = self.bank.account(&address) {
+            if account.data != Data::Empty {
+                return Err(ProgramError::system(
+                    "AccountAlreadyInUse",
+                    format!("{} ({}) already exists", param.name, address),
+                )
+                .into());
+            }
+        }
+
+        let def = self
+            .hir
+            .program
+            .accounts
+            .iter()
+            .find(|a| a.name == ty)
+            .with_context(|| format!("unknown account type {}", ty))?;
+        self.pay_rent(payer, address, account_space(&self.hir.program, def))?;
+
+        let fields = def
+            .fields
+            .iter()
+            .map(|f| (f.name.clone(), Value::default_for(&self.hir.program, &f.ty)))
+            .collect();
+        let account = self.bank.account_mut(&address).expect("rent was paid");
+        account.owner = self.program_id;
+        account.data = Data::Program {
+            ty: ty.to_string(),
+            fields,
+        };
+        Ok(())
+    }
+
+    /// Move the rent exempt minimum for `space` bytes from `payer` to
+    /// `address`, less what it already holds.
+    fn pay_rent(
+        &mut self,
+        payer: &str,
+        address: Pubkey,
+        space: u64,
+    ) -> std::result::Result<(), ProgramError> {
+        let payer_address = self.address(payer);
+        let rent = minimum_balance(space).saturating_sub(self.bank.lamports(&address));
+        let balance = self.bank.lamports(&payer_address);
+        if balance < rent {
+            return Err(ProgramError::system(
+                "InsufficientFunds",
+                format!(
+                    "{} ({}) has {} lamports but {} are needed",
+                    payer, payer_address, balance, rent
+                ),
+            ));
+        }
+        if let Some(account) = self.bank.account_mut(&payer_address) {
+            account.lamports -= rent;
+        }
+        self.bank.airdrop(address, rent);
+        Ok(())
+    }
+
+    fn check_seeds(&self, param: &Param, address: Pubkey) -> std::result::Result<(), ProgramError> {
+        let Some(seeds) = seeds(param) else {
+            return Ok(());
+        };
+        let bytes = seed_bytes(seeds, &self.accounts).unwrap_or_default();
+        let refs: Vec<&[u8]> = bytes.iter().map(Vec::as_slice).collect();
+        if Pubkey::find_program_address(&refs, &self.program_id) == address {
+            return Ok(());
+        }
+        Err(ProgramError::anchor(
+            "ConstraintSeeds",
+            2006,
+            format!("{} ({}) is not the PDA of its seeds", param.name, address),
+        ))
+    }
+
+    fn check_token_account(
+        &mut self,
+        param: &Param,
+        address: Pubkey,
+    ) -> std::result::Result<(), ProgramError> {
+        let associated = param.annotations.iter().find_map(|a| match a {
+            ParamAnnotation::AssociatedToken { mint, authority } => {
+                Some((self.address(mint), self.address(authority)))
+            }
+            _ => None,
+        });
+        let payer = param.annotations.iter().find_map(|a| match a {
+            ParamAnnotation::InitIfNeeded { payer } => Some(payer.as_str()),
+            _ => None,
+        });
+
+        if let (Some((mint, authority)), Some(payer)) = (associated, payer) {
+            let missing = self
+                .bank
+                .account(&address)
+                .is_none_or(|a| a.data == Data::Empty);
+            if missing && address == associated_token_address(&authority, &mint) {
+                self.pay_rent(payer, address, bank::TOKEN_ACCOUNT_SPACE)?;
+                let lamports = self.bank.lamports(&address);
+                self.bank.set(
+                    address,
+                    Account {
+                        lamports,
+                        ..token_account(authority, mint)
+                    },
+                );
+            }
+        }
+
+        let (token_mint, token_owner) = match self.bank.account(&address) {
+            Some(Account {
+                data: Data::Token { mint, owner, .. },
+                ..
+            }) => (*mint, *owner),
+            account => {
+                return Err(self.wrong_account(&param.name, address, account, token_program()))
+            }
+        };
+        let Some((mint, authority)) = associated else {
+            return Ok(());
+        };
+        if token_mint != mint {
+            return Err(ProgramError::anchor(
+                "ConstraintTokenMint",
+                2014,
+                format!("{} holds mint {}, not {}", param.name, token_mint, mint),
+            ));
+        }
+        if token_owner != authority {
+            return Err(ProgramError::anchor(
+                "ConstraintTokenOwner",
+                2015,
+                format!(
+                    "{} is owned by {}, not {}",
+                    param.name, token_owner, authority
+                ),
+            ));
+        }
+        if address != associated_token_address(&authority, &mint) {
+            return Err(ProgramError::anchor(
+                "AccountNotAssociatedTokenAccount",
+                3014,
+                format!(
+                    "{} ({}) is not an associated token account",
+                    param.name, address
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn run(&mut self) -> std::result::Result<(), Halt> {
+        for stmt in &self.instruction.body {
+            self.statement(stmt)?;
+        }
+        for check in self.hir.invariant_checks(self.instruction) {
+            self.statement(&check)?;
+        }
+        for stmt in &self.instruction.body {
+            if let Statement::Close { account, recipient } = stmt {
+                let (address, recipient) = (self.address(account), self.address(recipient));
+                self.bank.close(&address, recipient);
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> std::result::Result<(), Halt> {
+        match stmt {
+            // Accounts are created before the body runs and closed in `run`
+            // once the checks pass
+            Statement::InitAccount { .. } | Statement::Close { .. } => Ok(()),
+            Statement::Require { condition, message } => {
+                if self.eval(condition)? == Value::Bool(true) {
+                    return Ok(());
+                }
+                Err(
+                    match message.as_deref().and_then(|m| self.hir.error_for(m)) {
+                        Some(error) => ProgramError {
+                            name: error.name.clone(),
+                            code: Some(error.code),
+                            message: error.msg.clone(),
+                        },
+                        None => ProgramError::anchor(
+                            "RequireViolated",
+                            2500,
+                            "A require expression was violated".to_string(),
+                        ),
+                    }
+                    .into(),
+                )
+            }
+            Statement::Assign { target, value } => {
+                let value = self.eval(value)?;
+                self.assign(target, value)
+            }
+            Statement::Emit { event, fields } => {
+                let mut values = Vec::new();
+                for (name, expr) in fields {
+                    values.push((name.clone(), self.eval(expr)?));
+                }
+                self.receipt.events.push(Event {
+                    name: event.clone(),
+                    fields: values,
+                });
+                Ok(())
+            }
+            Statement::Expr(Expr::MethodCall {
+                object,
+                method,
+                args,
+            }) if self.cpi_module(object).is_some() => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                self.receipt.cpis.push(Cpi {
+                    program: self.cpi_module(object).unwrap_or_default(),
+                    instruction: method.clone(),
+                    args: values,
+                });
+                Ok(())
+            }
+            Statement::Expr(expr) => self.eval(expr).map(|_| ()),
+        }
+    }
+
+    /// The imported program a call is made on, if any.
+    fn cpi_module(&self, object: &Expr) -> Option<String> {
+        match object {
+            Expr::Ident(name) if self.param(name).is_none() => {
+                self.hir.import(name).map(|i| i.module.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn assign(&mut self, target: &Expr, value: Value) -> std::result::Result<(), Halt> {
+        // `vault.fees.rate` is the path `fees`, `rate` into account `vault`
+        let mut path = Vec::new();
+        let mut object = target;
+        while let Expr::FieldAccess {
+            object: inner,
+            field,
+        } = object
+        {
+            path.push(field.as_str());
+            object = inner;
+        }
+        path.reverse();
+        let (Expr::Ident(name), Some((field, parents))) = (object, path.split_last()) else {
+            return Err(anyhow::anyhow!("cannot assign to {:?}", target).into());
+        };
+        let ty = self.hir.type_of(self.instruction, target)?;
+        if !value.has_type(&ty) {
+            return Err(anyhow::anyhow!(
+                "{} does not fit {} of type {}",
+                value,
+                target,
+                ty.to_rust_type()
+            )
+            .into());
+        }
+        let address = self.address(name);
+        let Some(Data::Program { fields, .. }) =
+            self.bank.account_mut(&address).map(|a| &mut a.data)
+        else {
+            return Err(anyhow::anyhow!("{} is not a program account", name).into());
+        };
+        let mut fields = fields;
+        for parent in parents {
+            match fields.get_mut(*parent) {
+                Some(Value::Struct { fields: inner, .. }) => fields = inner,
+                _ => return Err(anyhow::anyhow!("{} has no struct field {}", name, parent).into()),
+            }
+        }
+        fields.insert(field.to_string(), value);
+        Ok(())
+    }
+
+    fn eval(&self, expr: &Expr) -> std::result::Result<Value, Halt> {
+        Ok(match expr {
+            Expr::Literal(Literal::Int(value)) => Value::Int(*value as i128),
+            Expr::Literal(Literal::UInt(value)) => Value::Int(*value as i128),
+            Expr::Literal(Literal::Bool(value)) => Value::Bool(*value),
+            Expr::Literal(Literal::String(value)) => Value::String(value.clone()),
+            Expr::Ident(name) => self
+                .args
+                .get(name)
+                .cloned()
+                .with_context(|| format!("{} is not a value", name))?,
+            Expr::FieldAccess { object, field } => self.field(object, field)?,
+            Expr::BinaryOp { op, left, right } => self.binary(expr, op, left, right)?,
+            Expr::UnaryOp { op, operand } => match (op, self.eval(operand)?) {
+                (UnOp::Not, Value::Bool(value)) => Value::Bool(!value),
+                (UnOp::Neg, Value::Int(value)) => {
+                    let ty = self.hir.type_of(self.instruction, operand)?;
+                    self.fit(-value, &ty, "negate")?
+                }
+                (_, value) => return Err(anyhow::anyhow!("invalid operand {}", value).into()),
+            },
+            Expr::MethodCall {
+                object,
+                method,
+                args,
+            } => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.eval(arg)?);
+                }
+                self.call(object, method, &values)?
+            }
+        })
+    }
+
+    fn field(&self, object: &Expr, field: &str) -> std::result::Result<Value, Halt> {
+        let name = match object {
+            Expr::Ident(name) if self.param(name).is_none_or(|p| p.ty.is_account()) => name,
+            // A field of a struct value
+            _ => {
+                return match self.eval(object)? {
+                    Value::Struct { fields, .. } => fields
+                        .get(field)
+                        .cloned()
+                        .with_context(|| format!("{} has no field {}", object, field))
+                        .map_err(Halt::from),
+                    value => Err(anyhow::anyhow!("{} has no field {}", value, field).into()),
+                };
+            }
+        };
+        if let Some(param) = self.param(name) {
+            let address = self.address(name);
+            if let Some(property) = property(&self.hir.program, self.instruction, object, field) {
+                return Ok(self.property(param, address, property));
+            }
+            if let Some(value) = self.bank.field(&address, field) {
+                return Ok(value.clone());
+            }
+            return Err(anyhow::anyhow!("{} has no field {}", name, field).into());
+        }
+        let clock = &self.bank.clock;
+        let value = match (Sysvar::from_name(name), field) {
+            (Some(Sysvar::Clock), "slot") => clock.slot as i128,
+            (Some(Sysvar::Clock), "epoch") => clock.epoch as i128,
+            (Some(Sysvar::Clock), "leader_schedule_epoch") => clock.leader_schedule_epoch as i128,
+            (Some(Sysvar::Clock), "unix_timestamp") => clock.unix_timestamp as i128,
+            (Some(Sysvar::Clock), "epoch_start_timestamp") => clock.epoch_start_timestamp as i128,
+            (Some(Sysvar::Rent), "lamports_per_byte_year") => LAMPORTS_PER_BYTE_YEAR as i128,
+            (Some(Sysvar::Rent), "burn_percent") => BURN_PERCENT as i128,
+            _ => return Err(anyhow::anyhow!("unknown field {}.{}", name, field).into()),
+        };
+        Ok(Value::Int(value))
+    }
+
+    /// A builtin property of the account `param` at `address`, as the
+    /// runtime would pass it: writable as the instruction's account list
+    /// marks it.
+    fn property(&self, param: &Param, address: Pubkey, property: Property) -> Value {
+        let account = self.bank.account(&address);
+        match property {
+            Property::Key => Value::Pubkey(address),
+            Property::Lamports => Value::Int(self.bank.lamports(&address) as i128),
+            Property::Owner => Value::Pubkey(account.map_or_else(system_program, |a| a.owner)),
+            Property::DataLen => {
+                let len = match account.map(|a| &a.data) {
+                    None | Some(Data::Empty) => 0,
+                    Some(Data::Program { ty, .. }) => self
+                        .hir
+                        .program
+                        .accounts
+                        .iter()
+                        .find(|a| a.name == *ty)
+                        .map_or(8, |def| account_space(&self.hir.program, def)),
+                    Some(Data::Token { .. }) => TOKEN_ACCOUNT_SPACE,
+                    Some(Data::Mint { .. }) => MINT_SPACE,
+                };
+                Value::Int(len as i128)
+            }
+            Property::IsSigner => Value::Bool(self.signers.contains(&address)),
+            Property::IsWritable => Value::Bool(self.hir.is_writable(self.instruction, param)),
+        }
+    }
+
+    fn call(
+        &self,
+        object: &Expr,
+        method: &str,
+        args: &[Value],
+    ) -> std::result::Result<Value, Halt> {
+        let Expr::Ident(name) = object else {
+            return Err(anyhow::anyhow!("cannot call {} on {:?}", method, object).into());
+        };
+        if self.hir.extern_fn(method).is_some() && self.param(name).is_none() {
+            let helper = self.helpers.get(method).with_context(|| {
+                format!(
+                    "extern fn {} has no implementation in the interpreter",
+                    method
+                )
+            })?;
+            return Ok(helper(args));
+        }
+        match (Sysvar::from_name(name), method, args) {
+            (Some(Sysvar::Rent), "minimum_balance", [Value::Int(space)]) => {
+                let space = u64::try_from(*space).context("negative account size")?;
+                Ok(Value::Int(minimum_balance(space) as i128))
+            }
+            _ => Err(anyhow::anyhow!("unknown method {}.{}", name, method).into()),
+        }
+    }
+
+    fn binary(
+        &self,
+        expr: &Expr,
+        op: &BinOp,
+        left: &Expr,
+        right: &Expr,
+    ) -> std::result::Result<Value, Halt> {
+        // Both sides are evaluated, except where Rust short-circuits
+        let left = self.eval(left)?;
+        match (op, &left) {
+            (BinOp::And, Value::Bool(false)) => return Ok(Value::Bool(false)),
+            (BinOp::Or, Value::Bool(true)) => return Ok(Value::Bool(true)),
+            _ => {}
+        }
+        let right = self.eval(right)?;
+
+        Ok(match (op, left, right) {
+            (BinOp::And | BinOp::Or, _, right) => right,
+            (BinOp::Eq, left, right) => Value::Bool(left == right),
+            (BinOp::Ne, left, right) => Value::Bool(left != right),
+            (op, Value::Int(l), Value::Int(r)) => match op {
+                BinOp::Lt => Value::Bool(l < r),
+                BinOp::Le => Value::Bool(l <= r),
+                BinOp::Gt => Value::Bool(l > r),
+                BinOp::Ge => Value::Bool(l >= r),
+                _ => {
+                    let ty = self.hir.type_of(self.instruction, expr)?;
+                    self.arithmetic(op, l, r, &ty)?
+                }
+            },
+            (op, left, right) => {
+                return Err(anyhow::anyhow!("invalid operands {} {:?} {}", left, op, right).into())
+            }
+        })
+    }
+
+    /// `l op r` in type `ty`, overflowing as the program's arithmetic mode
+    /// says.
+    fn arithmetic(
+        &self,
+        op: &BinOp,
+        l: i128,
+        r: i128,
+        ty: &Type,
+    ) -> std::result::Result<Value, Halt> {
+        let (verb, result) = match op {
+            BinOp::Add => ("add", l.checked_add(r)),
+            BinOp::Sub => ("subtract", l.checked_sub(r)),
+            BinOp::Mul => ("multiply", l.checked_mul(r)),
+            BinOp::Div => ("divide", l.checked_div(r)),
+            BinOp::Mod => ("calculate the remainder", l.checked_rem(r)),
+            _ => unreachable!("comparisons are handled by the caller"),
+        };
+        match result {
+            Some(value) => self.fit(value, ty, verb),
+            // Division by zero; `wrapping_div` panics on it too
+            None if self.hir.arithmetic == Arithmetic::Checked => Err(self.overflow().into()),
+            None => Err(panic(format!("attempt to {} with a divisor of zero", verb)).into()),
+        }
+    }
+
+    /// `value` if it fits `ty`; otherwise the overflow behavior of the
+    /// program's arithmetic mode.
+    fn fit(&self, value: i128, ty: &Type, verb: &str) -> std::result::Result<Value, Halt> {
+        let range = value::int_range(ty)
+            .with_context(|| format!("{} is not an integer type", ty.to_rust_type()))?;
+        if range.contains(&value) {
+            return Ok(Value::Int(value));
+        }
+        match self.hir.arithmetic {
+            Arithmetic::Panic => Err(panic(format!("attempt to {} with overflow", verb)).into()),
+            Arithmetic::Checked => Err(self.overflow().into()),
+            Arithmetic::Wrapping => {
+                let modulus = range.end() - range.start() + 1;
+                Ok(Value::Int(
+                    (value - range.start()).rem_euclid(modulus) + range.start(),
+                ))
+            }
+        }
+    }
+
+    fn overflow(&self) -> ProgramError {
+        match self.hir.error_for(ARITHMETIC_OVERFLOW) {
+            Some(error) => ProgramError {
+                name: error.name.clone(),
+                code: Some(error.code),
+                message: error.msg.clone(),
+            },
+            None => panic("arithmetic overflow".to_string()),
+        }
+    }
+}
+
+/// A Rust panic in the program, which Solana reports without an error code.
+fn panic(message: String) -> ProgramError {
+    ProgramError::system("ProgramFailedToComplete", message)
+}
+
+/// The payer of the `init` statement for `name`, if any.
+fn init_payer<'a>(instruction: &'a Instruction, name: &str) -> Option<&'a str> {
+    instruction.body.iter().find_map(|s| match s {
+        Statement::InitAccount {
+            var_name, payer, ..
+        } if var_name == name => Some(payer.as_str()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COUNTER: &str = r#"
+program Counter
+
+account CounterState {
+  authority: Pubkey
+  count: u8
+}
+
+instruction initialize(authority: Signer, state: CounterState) {
+  init account state: CounterState payer authority
+  state.authority = authority.key
+}
+
+instruction add(authority: Signer, state: CounterState @seeds("state", authority), amount: u8) {
+  require state.authority == authority.key, "Not the authority"
+  state.count += amount
+}
+"#;
+
+    fn hir(source: &str, arithmetic: Arithmetic) -> Hir {
+        let program = solx_parser::parse(source).unwrap();
+        Hir::from_ast(program).unwrap().with_arithmetic(arithmetic)
+    }
+
+    #[test]
+    fn test_counter_constraints_and_rollback() {
+        let hir = hir(
+            &COUNTER.replace(
+                "state: CounterState @seeds(\"state\", authority)",
+                "state: CounterState",
+            ),
+            Arithmetic::Panic,
+        );
+        let mut interp = Interpreter::new(&hir);
+        let alice = Pubkey::named("alice");
+        let bob = Pubkey::named("bob");
+        let state = Pubkey::named("state");
+        interp.bank.airdrop(alice, 1_000_000_000);
+
+        let init = Call::new("initialize")
+            .account("authority", alice)
+            .account("state", state)
+            .signer(alice);
+        let err = interp.execute(&init).unwrap().unwrap_err();
+        assert_eq!(err.name, "AccountNotSigner");
+        assert_eq!(interp.bank.lamports(&alice), 1_000_000_000);
+
+        interp
+            .execute(&init.clone().signer(state))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            interp.bank.field(&state, "authority"),
+            Some(&Value::Pubkey(alice))
+        );
+        assert_eq!(
+            interp.bank.lamports(&alice),
+            1_000_000_000 - bank::minimum_balance(8 + 32 + 1)
+        );
+        let err = interp
+            .execute(&init.clone().signer(state))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.name, "AccountAlreadyInUse");
+
+        let add = |authority: Pubkey, amount: i128| {
+            Call::new("add")
+                .account("authority", authority)
+                .account("state", state)
+                .arg("amount", Value::Int(amount))
+                .signer(authority)
+        };
+        interp.execute(&add(alice, 200)).unwrap().unwrap();
+        let err = interp.execute(&add(bob, 1)).unwrap().unwrap_err();
+        assert_eq!(
+            (err.name.as_str(), err.code),
+            ("NotTheAuthority", Some(6000))
+        );
+        let err = interp.execute(&add(alice, 100)).unwrap().unwrap_err();
+        assert_eq!(err.name, "ProgramFailedToComplete");
+        assert_eq!(interp.bank.field(&state, "count"), Some(&Value::Int(200)));
+
+        assert!(interp.execute(&add(alice, 256)).is_err());
+        assert!(interp.execute(&Call::new("add")).is_err());
+    }
+
+    #[test]
+    fn test_closed_accounts_refund_their_rent() {
+        let source = format!(
+            "{}\ninstruction retire(authority: Signer, state: CounterState) {{\n  \
+             require state.authority == authority.key, \"Not the authority\"\n  \
+             close state to authority\n}}\n",
+            COUNTER.replace(
+                "state: CounterState @seeds(\"state\", authority)",
+                "state: CounterState",
+            )
+        );
+        let hir = hir(&source, Arithmetic::Checked);
+        let mut interp = Interpreter::new(&hir);
+        let alice = Pubkey::named("alice");
+        let bob = Pubkey::named("bob");
+        let state = Pubkey::named("state");
+        interp.bank.airdrop(alice, 1_000_000_000);
+        interp
+            .execute(
+                &Call::new("initialize")
+                    .account("authority", alice)
+                    .account("state", state)
+                    .signer(alice)
+                    .signer(state),
+            )
+            .unwrap()
+            .unwrap();
+
+        let retire = |authority: Pubkey| {
+            Call::new("retire")
+                .account("authority", authority)
+                .account("state", state)
+                .signer(authority)
+        };
+        let err = interp.execute(&retire(bob)).unwrap().unwrap_err();
+        assert_eq!(err.name, "NotTheAuthority");
+        assert!(interp.bank.account(&state).is_some());
+
+        interp.execute(&retire(alice)).unwrap().unwrap();
+        assert_eq!(interp.bank.lamports(&alice), 1_000_000_000);
+        assert!(interp.bank.account(&state).is_none());
+        assert_eq!(interp.bank.field(&state, "count"), None);
+
+        let add = Call::new("add")
+            .account("authority", alice)
+            .account("state", state)
+            .arg("amount", Value::Int(1))
+            .signer(alice);
+        let err = interp.execute(&add).unwrap().unwrap_err();
+        assert_eq!(err.name, "AccountNotInitialized");
+        let err = interp.execute(&retire(alice)).unwrap().unwrap_err();
+        assert_eq!(err.name, "AccountNotInitialized");
+    }
+
+    #[test]
+    fn test_arithmetic_modes_and_seeds() {
+        let hir_checked = hir(COUNTER, Arithmetic::Checked);
+        let hir_wrapping = hir(COUNTER, Arithmetic::Wrapping);
+        for hir in [&hir_checked, &hir_wrapping] {
+            let mut interp = Interpreter::new(hir);
+            let alice = Pubkey::named("alice");
+            interp.bank.airdrop(alice, 1_000_000_000);
+            // The PDA is derived when the call leaves it out
+            let init = Call::new("initialize")
+                .account("authority", alice)
+                .account("state", interp.pda(&[b"state", &alice.0]))
+                .signer(alice);
+            let err = interp.execute(&init).unwrap().unwrap_err();
+            assert_eq!(err.name, "AccountNotSigner");
+
+            let state = interp.pda(&[b"state", &alice.0]);
+            let wrong = Pubkey::named("wrong");
+            let add = |amount: i128| {
+                Call::new("add")
+                    .account("authority", alice)
+                    .arg("amount", Value::Int(amount))
+                    .signer(alice)
+            };
+            let err = interp
+                .execute(&add(1).account("state", wrong))
+                .unwrap()
+                .unwrap_err();
+            assert_eq!(err.name, "AccountNotInitialized");
+            let err = interp.execute(&add(1)).unwrap().unwrap_err();
+            assert_eq!(err.name, "AccountNotInitialized", "{}", state);
+
+            // Create the PDA account directly, as `initialize` cannot sign for it
+            interp.bank.set(
+                state,
+                Account {
+                    lamports: 1,
+                    owner: interp.program_id,
+                    data: Data::Program {
+                        ty: "CounterState".to_string(),
+                        fields: [
+                            ("authority".to_string(), Value::Pubkey(alice)),
+                            ("count".to_string(), Value::Int(250)),
+                        ]
+                        .into(),
+                    },
+                },
+            );
+            interp
+                .bank
+                .set(wrong, interp.bank.account(&state).unwrap().clone());
+            let err = interp
+                .execute(&add(1).account("state", wrong))
+                .unwrap()
+                .unwrap_err();
+            assert_eq!(err.name, "ConstraintSeeds");
+
+            let outcome = interp.execute(&add(10)).unwrap();
+            match hir.arithmetic {
+                Arithmetic::Checked => {
+                    assert_eq!(outcome.unwrap_err().name, "ArithmeticOverflow");
+                    assert_eq!(interp.bank.field(&state, "count"), Some(&Value::Int(250)));
+                }
+                _ => {
+                    outcome.unwrap();
+                    assert_eq!(interp.bank.field(&state, "count"), Some(&Value::Int(4)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_struct_fields_and_arguments() {
+        let hir = hir(
+            r#"
+program Config
+
+struct Fees {
+  rate: u16
+  recipient: Pubkey
+}
+
+account Settings {
+  fees: Fees
+}
+
+instruction initialize(authority: Signer, settings: Settings) {
+  init account settings: Settings payer authority
+  settings.fees.recipient = authority.key
+}
+
+instruction update(authority: Signer, settings: Settings, fees: Fees) {
+  require fees.rate <= 10000, "Rate too high"
+  settings.fees = fees
+}
+"#,
+            Arithmetic::Checked,
+        );
+        let mut interp = Interpreter::new(&hir);
+        let alice = Pubkey::named("alice");
+        let settings = Pubkey::named("settings");
+        interp.bank.airdrop(alice, 1_000_000_000);
+        let fees = |rate: i128, recipient: Pubkey| Value::Struct {
+            ty: "Fees".to_string(),
+            fields: [
+                ("rate".to_string(), Value::Int(rate)),
+                ("recipient".to_string(), Value::Pubkey(recipient)),
+            ]
+            .into(),
+        };
+
+        let init = Call::new("initialize")
+            .account("authority", alice)
+            .account("settings", settings)
+            .signer(alice)
+            .signer(settings);
+        interp.execute(&init).unwrap().unwrap();
+        assert_eq!(interp.bank.field(&settings, "fees"), Some(&fees(0, alice)));
+
+        let update = |value: Value| {
+            Call::new("update")
+                .account("authority", alice)
+                .account("settings", settings)
+                .arg("fees", value)
+                .signer(alice)
+        };
+        let err = interp
+            .execute(&update(fees(20_000, settings)))
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(err.name, "RateTooHigh");
+        interp
+            .execute(&update(fees(30, settings)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            interp.bank.field(&settings, "fees"),
+            Some(&fees(30, settings))
+        );
+        assert!(interp.execute(&update(Value::Int(30))).is_err());
+    }
+}
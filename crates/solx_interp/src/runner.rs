@@ -0,0 +1,259 @@
+//! Runs `test` blocks against the interpreter.
+//!
+//! Each test starts from an empty bank. Names not bound with `let` are the
+//! addresses of test wallets, e.g. `alice`; an address passed as a `Signer`
+//! that has no account yet is funded with [`WALLET_LAMPORTS`]. Unless a
+//! call says `signed_by(...)`, its `Signer` accounts and the accounts it
+//! creates with `init` sign.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+
+use solx_ast::*;
+use solx_hir::accounts::seeds;
+use solx_hir::naming::to_snake_case;
+use solx_hir::Hir;
+
+use crate::{init_payer, Call, Interpreter, Outcome, Pubkey, Value};
+
+/// 100 SOL.
+pub const WALLET_LAMPORTS: u64 = 100_000_000_000;
+
+/// Run `test`, failing with the location and reason of the first call or
+/// expectation that does not hold.
+pub fn run_test(hir: &Hir, test: &TestDef) -> Result<()> {
+    let mut run = TestRun::new(hir);
+    for stmt in &test.body {
+        let span = match stmt {
+            TestStatement::Let { .. } => test.span,
+            TestStatement::Call(call) => call.span,
+            TestStatement::Expect { span, .. } => *span,
+        };
+        run.statement(stmt)
+            .map_err(|err| match hir.program.location(span) {
+                Some(location) => anyhow::anyhow!("{}: {:#}", location, err),
+                None => err,
+            })?;
+    }
+    Ok(())
+}
+
+/// The state of one test: the interpreter and the `let` bindings.
+pub(crate) struct TestRun<'a> {
+    pub(crate) interp: Interpreter<'a>,
+    bindings: BTreeMap<String, Value>,
+}
+
+impl<'a> TestRun<'a> {
+    pub(crate) fn new(hir: &'a Hir) -> Self {
+        TestRun {
+            interp: Interpreter::new(hir),
+            bindings: BTreeMap::new(),
+        }
+    }
+
+    fn statement(&mut self, stmt: &TestStatement) -> Result<()> {
+        match stmt {
+            TestStatement::Let { name, value } => {
+                let value = match value {
+                    TestValue::Expr(expr) => self.eval(expr)?,
+                    TestValue::Pda(seeds) => Value::Pubkey(self.pda(seeds)?),
+                };
+                self.bindings.insert(name.clone(), value);
+                Ok(())
+            }
+            TestStatement::Call(call) => self.call(call),
+            TestStatement::Expect { condition, .. } => match self.eval(condition)? {
+                Value::Bool(true) => Ok(()),
+                Value::Bool(false) => {
+                    anyhow::bail!("expectation failed: {}", self.describe(condition))
+                }
+                value => anyhow::bail!("expect needs a bool, found {}", value),
+            },
+        }
+    }
+
+    fn call(&mut self, test_call: &TestCall) -> Result<()> {
+        let name = to_snake_case(&test_call.instruction);
+        match (self.execute(test_call)?, &test_call.expect_err) {
+            (Ok(_), None) => Ok(()),
+            (Ok(_), Some(expected)) => {
+                anyhow::bail!(
+                    "expected {} to fail with {}, but it succeeded",
+                    name,
+                    expected
+                )
+            }
+            (Err(err), Some(expected)) if err.name == *expected => Ok(()),
+            (Err(err), Some(expected)) => {
+                anyhow::bail!(
+                    "expected {} to fail with {}, but got {}",
+                    name,
+                    expected,
+                    err
+                )
+            }
+            (Err(err), None) => anyhow::bail!("{} failed: {}", name, err),
+        }
+    }
+
+    /// Execute a call, funding new `Signer` wallets and choosing the
+    /// signers. Fails if the call is invalid, not if the program fails.
+    pub(crate) fn execute(&mut self, test_call: &TestCall) -> Result<Outcome> {
+        let hir = self.interp.hir;
+        let name = to_snake_case(&test_call.instruction);
+        let instruction = hir
+            .program
+            .instructions
+            .iter()
+            .find(|i| i.name == name)
+            .with_context(|| format!("unknown instruction {}", test_call.instruction))?;
+
+        let mut call = Call::new(&name);
+        for (param_name, expr) in &test_call.args {
+            let param = instruction
+                .params
+                .iter()
+                .find(|p| p.name == *param_name)
+                .with_context(|| format!("instruction {} has no parameter {}", name, param_name))?;
+            let value = self.eval(expr)?;
+            if !param.ty.is_account() {
+                call = call.arg(param_name, value);
+                continue;
+            }
+            let Value::Pubkey(address) = value else {
+                anyhow::bail!("account {} must be an address, found {}", param_name, value);
+            };
+            if param.ty == Type::Signer && self.interp.bank.account(&address).is_none() {
+                self.interp.bank.airdrop(address, WALLET_LAMPORTS);
+            }
+            if test_call.signers.is_none() && signs_by_default(instruction, param) {
+                call = call.signer(address);
+            }
+            call = call.account(param_name, address);
+        }
+        for signer in test_call.signers.iter().flatten() {
+            call = call.signer(self.address(signer)?);
+        }
+
+        self.interp.execute(&call)
+    }
+
+    fn pda(&self, seeds: &[Seed]) -> Result<Pubkey> {
+        let mut bytes = Vec::new();
+        for seed in seeds {
+            bytes.push(match seed {
+                Seed::Const(text) => text.as_bytes().to_vec(),
+                Seed::Account(name) => self.address(name)?.0.to_vec(),
+            });
+        }
+        let refs: Vec<&[u8]> = bytes.iter().map(Vec::as_slice).collect();
+        Ok(self.interp.pda(&refs))
+    }
+
+    /// The address bound to `name`, or the wallet called `name`.
+    pub(crate) fn address(&self, name: &str) -> Result<Pubkey> {
+        match self.bindings.get(name) {
+            Some(Value::Pubkey(address)) => Ok(*address),
+            Some(value) => anyhow::bail!("{} is {}, not an address", name, value),
+            None => Ok(Pubkey::named(name)),
+        }
+    }
+
+    /// Evaluate and describe `expr` with `name` bound to `address` for the
+    /// duration.
+    pub(crate) fn eval_at(
+        &mut self,
+        name: &str,
+        address: Pubkey,
+        expr: &Expr,
+    ) -> Result<(Value, String)> {
+        let previous = self
+            .bindings
+            .insert(name.to_string(), Value::Pubkey(address));
+        let result = self.eval(expr).map(|value| (value, self.describe(expr)));
+        match previous {
+            Some(value) => self.bindings.insert(name.to_string(), value),
+            None => self.bindings.remove(name),
+        };
+        result
+    }
+
+    pub(crate) fn eval(&self, expr: &Expr) -> Result<Value> {
+        Ok(match expr {
+            Expr::Literal(Literal::Int(value)) => Value::Int(*value as i128),
+            Expr::Literal(Literal::UInt(value)) => Value::Int(*value as i128),
+            Expr::Literal(Literal::Bool(value)) => Value::Bool(*value),
+            Expr::Literal(Literal::String(value)) => Value::String(value.clone()),
+            Expr::Ident(name) => match self.bindings.get(name) {
+                Some(value) => value.clone(),
+                None => Value::Pubkey(Pubkey::named(name)),
+            },
+            Expr::FieldAccess { object, field } => {
+                let value = match self.eval(object)? {
+                    Value::Pubkey(address) => self.interp.bank.field(&address, field).cloned(),
+                    Value::Struct { fields, .. } => fields.get(field).cloned(),
+                    _ => anyhow::bail!("{} is not an account", self.describe(object)),
+                };
+                value
+                    .with_context(|| format!("{} has no field {}", self.describe(object), field))?
+            }
+            Expr::UnaryOp { op, operand } => match (op, self.eval(operand)?) {
+                (UnOp::Not, Value::Bool(value)) => Value::Bool(!value),
+                (UnOp::Neg, Value::Int(value)) => Value::Int(-value),
+                (_, value) => anyhow::bail!("invalid operand {}", value),
+            },
+            Expr::BinaryOp { op, left, right } => binary(op, self.eval(left)?, self.eval(right)?)?,
+            Expr::MethodCall { method, .. } => {
+                anyhow::bail!("cannot call {} in a test", method)
+            }
+        })
+    }
+
+    /// An expression with the values of its operands, e.g.
+    /// `s.count == 1 (2 == 1)`.
+    pub(crate) fn describe(&self, expr: &Expr) -> String {
+        let source = expr.to_string();
+        match expr {
+            Expr::BinaryOp { op, left, right } => match (self.eval(left), self.eval(right)) {
+                (Ok(left), Ok(right)) => format!("{} ({} {} {})", source, left, op.symbol(), right),
+                _ => source,
+            },
+            _ => source,
+        }
+    }
+}
+
+/// Whether a parameter signs when the test does not say who signs: signers,
+/// and keypair accounts created by `init`.
+pub(crate) fn signs_by_default(instruction: &Instruction, param: &Param) -> bool {
+    param.ty == Type::Signer
+        || (init_payer(instruction, &param.name).is_some() && seeds(param).is_none())
+}
+
+fn binary(op: &BinOp, left: Value, right: Value) -> Result<Value> {
+    Ok(match (op, left, right) {
+        (BinOp::Eq, left, right) => Value::Bool(left == right),
+        (BinOp::Ne, left, right) => Value::Bool(left != right),
+        (BinOp::And, Value::Bool(l), Value::Bool(r)) => Value::Bool(l && r),
+        (BinOp::Or, Value::Bool(l), Value::Bool(r)) => Value::Bool(l || r),
+        (op, Value::Int(l), Value::Int(r)) => {
+            let result = match op {
+                BinOp::Lt => return Ok(Value::Bool(l < r)),
+                BinOp::Le => return Ok(Value::Bool(l <= r)),
+                BinOp::Gt => return Ok(Value::Bool(l > r)),
+                BinOp::Ge => return Ok(Value::Bool(l >= r)),
+                BinOp::Add => l.checked_add(r),
+                BinOp::Sub => l.checked_sub(r),
+                BinOp::Mul => l.checked_mul(r),
+                BinOp::Div => l.checked_div(r),
+                BinOp::Mod => l.checked_rem(r),
+                _ => None,
+            };
+            Value::Int(
+                result.with_context(|| format!("cannot compute {} {} {}", l, op.symbol(), r))?,
+            )
+        }
+        (op, left, right) => anyhow::bail!("invalid operands {} {} {}", left, op.symbol(), right),
+    })
+}
@@ -0,0 +1,148 @@
+//! Runtime values and account addresses.
+
+use sha2::{Digest, Sha256};
+use solx_ast::{Program, Type};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A 32 byte account address, shown in Base58.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pubkey(pub [u8; 32]);
+
+impl Pubkey {
+    /// A stable address for a name, so tests can talk about `alice` or
+    /// `vault` without generating keypairs.
+    pub fn named(name: &str) -> Pubkey {
+        Pubkey(Sha256::digest(name.as_bytes()).into())
+    }
+
+    pub fn from_base58(address: &str) -> Option<Pubkey> {
+        let bytes = bs58::decode(address).into_vec().ok()?;
+        Some(Pubkey(bytes.try_into().ok()?))
+    }
+
+    /// The program derived address of `seeds`, hashed as Solana does with
+    /// bump 255. The address is not checked to be off the ed25519 curve, so
+    /// it is stable within the interpreter but may differ from the address
+    /// on chain.
+    pub fn find_program_address(seeds: &[&[u8]], program_id: &Pubkey) -> Pubkey {
+        let mut hasher = Sha256::new();
+        for seed in seeds {
+            hasher.update(seed);
+        }
+        hasher.update([255]);
+        hasher.update(program_id.0);
+        hasher.update(b"ProgramDerivedAddress");
+        Pubkey(hasher.finalize().into())
+    }
+}
+
+impl fmt::Display for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&bs58::encode(self.0).into_string())
+    }
+}
+
+impl fmt::Debug for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Pubkey({})", self)
+    }
+}
+
+/// A value of a SOL-X [`Type`]. Integers of every width are held as `i128`
+/// and kept within the range of their type by the interpreter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i128),
+    Bool(bool),
+    Pubkey(Pubkey),
+    String(String),
+    Vec(Vec<Value>),
+    Option(Option<Box<Value>>),
+    /// A value of a `struct`, by struct name
+    Struct {
+        ty: String,
+        fields: BTreeMap<String, Value>,
+    },
+}
+
+impl Value {
+    /// The value of a field in a newly created account, which Anchor
+    /// initializes with `Default`.
+    pub fn default_for(program: &Program, ty: &Type) -> Value {
+        match ty {
+            Type::Pubkey => Value::Pubkey(Pubkey::default()),
+            Type::Bool => Value::Bool(false),
+            Type::String => Value::String(String::new()),
+            Type::Vec(_) => Value::Vec(Vec::new()),
+            Type::Option(_) => Value::Option(None),
+            Type::Struct(name) => Value::Struct {
+                ty: name.clone(),
+                fields: program
+                    .structs
+                    .iter()
+                    .find(|s| s.name == *name)
+                    .into_iter()
+                    .flat_map(|s| &s.fields)
+                    .map(|f| (f.name.clone(), Value::default_for(program, &f.ty)))
+                    .collect(),
+            },
+            _ => Value::Int(0),
+        }
+    }
+
+    /// Whether this value can be stored in a field or argument of type `ty`.
+    pub fn has_type(&self, ty: &Type) -> bool {
+        match (self, ty) {
+            (Value::Int(value), ty) => int_range(ty).is_some_and(|r| r.contains(value)),
+            (Value::Bool(_), Type::Bool) => true,
+            (Value::Pubkey(_), Type::Pubkey) => true,
+            (Value::String(_), Type::String) => true,
+            (Value::Vec(items), Type::Vec(inner)) => items.iter().all(|i| i.has_type(inner)),
+            (Value::Option(value), Type::Option(inner)) => {
+                value.as_ref().is_none_or(|v| v.has_type(inner))
+            }
+            (Value::Struct { ty, .. }, Type::Struct(name)) => ty == name,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Pubkey(key) => write!(f, "{}", key),
+            Value::String(value) => write!(f, "{:?}", value),
+            Value::Vec(items) => {
+                let items: Vec<String> = items.iter().map(|i| i.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Option(Some(value)) => write!(f, "Some({})", value),
+            Value::Option(None) => f.write_str("None"),
+            Value::Struct { ty, fields } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect();
+                write!(f, "{} {{ {} }}", ty, fields.join(", "))
+            }
+        }
+    }
+}
+
+/// The values of an integer type, or `None` for other types.
+pub fn int_range(ty: &Type) -> Option<std::ops::RangeInclusive<i128>> {
+    Some(match ty {
+        Type::U8 => 0..=u8::MAX as i128,
+        Type::U16 => 0..=u16::MAX as i128,
+        Type::U32 => 0..=u32::MAX as i128,
+        Type::U64 => 0..=u64::MAX as i128,
+        Type::I8 => i8::MIN as i128..=i8::MAX as i128,
+        Type::I16 => i16::MIN as i128..=i16::MAX as i128,
+        Type::I32 => i32::MIN as i128..=i32::MAX as i128,
+        Type::I64 => i64::MIN as i128..=i64::MAX as i128,
+        _ => return None,
+    })
+}
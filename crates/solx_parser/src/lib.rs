@@ -1,99 +1,339 @@
 use anyhow::Result;
 use chumsky::prelude::*;
 use solx_ast::*;
+use std::ops::Range;
+use std::rc::Rc;
 
+mod loader;
+
+pub use loader::load;
+
+/// Parse a program from a single string. Use [`load`] for programs that
+/// `import` other files.
 pub fn parse(source: &str) -> Result<Program> {
-    program_parser()
+    let file = parse_source(source, 0, None)?;
+    let name = file
+        .header
+        .ok_or_else(|| anyhow::anyhow!("Parse errors:\nexpected `program <Name>`"))?;
+    if let Some((path, span)) = file.file_imports.first() {
+        anyhow::bail!(
+            "{}: cannot import \"{}\" without a file path; load the program from disk",
+            span.display_in(None),
+            path
+        );
+    }
+    let mut program = Program {
+        name,
+        imports: file.program_imports,
+        accounts: Vec::new(),
+        structs: Vec::new(),
+        events: Vec::new(),
+        externs: Vec::new(),
+        instructions: Vec::new(),
+        tests: Vec::new(),
+        invariants: Vec::new(),
+        files: Vec::new(),
+    };
+    add_items(&mut program, file.items);
+    Ok(program)
+}
+
+/// One parsed `.solx` file, before its imports are resolved.
+struct SourceFile {
+    /// `program Name`; only the root file has one
+    header: Option<String>,
+    program_imports: Vec<ProgramImport>,
+    /// `import "accounts/vault.solx"`, relative to this file
+    file_imports: Vec<(String, Span)>,
+    items: Vec<Item>,
+}
+
+/// Parse one file, giving spans in file number `file` of the program.
+/// Errors are reported as `name:line:column: message`.
+fn parse_source(source: &str, file: usize, name: Option<&str>) -> Result<SourceFile> {
+    let locator = Rc::new(Locator::new(file, source));
+    source_parser(locator.clone())
         .parse(source)
         .map_err(|errs| {
             anyhow::anyhow!(
                 "Parse errors:\n{}",
                 errs.into_iter()
-                    .map(|e| e.to_string())
+                    .map(|e| format!("{}: {}", locator.span(e.span().start).display_in(name), e))
                     .collect::<Vec<_>>()
                     .join("\n")
             )
         })
 }
 
-fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
+fn add_items(program: &mut Program, items: Vec<Item>) {
+    for item in items {
+        match item {
+            Item::Account(account) => program.accounts.push(account),
+            Item::Struct(def) => program.structs.push(def),
+            Item::Event(event) => program.events.push(event),
+            Item::Extern(extern_fn) => program.externs.push(extern_fn),
+            Item::Instruction(instruction) => program.instructions.push(instruction),
+            Item::Test(test) => program.tests.push(test),
+            Item::Invariant(invariant) => program.invariants.push(invariant),
+        }
+    }
+}
+
+/// Maps character offsets (chumsky spans) to lines and columns.
+struct Locator {
+    file: usize,
+    line_starts: Vec<usize>,
+}
+
+impl Locator {
+    fn new(file: usize, source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(
+                source
+                    .chars()
+                    .enumerate()
+                    .filter(|(_, c)| *c == '\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+        Locator { file, line_starts }
+    }
+
+    fn span(&self, offset: usize) -> Span {
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        Span {
+            file: self.file,
+            line,
+            column: offset - self.line_starts[line - 1] + 1,
+        }
+    }
+}
+
+/// A top-level declaration following the program header and imports.
+enum Item {
+    Account(AccountDef),
+    Struct(StructDef),
+    Event(EventDef),
+    Extern(ExternFn),
+    Instruction(Instruction),
+    Test(TestDef),
+    Invariant(InvariantDef),
+}
+
+/// A field or an invariant inside an `account` block.
+enum AccountMember {
+    Field(Field),
+    Invariant(AccountInvariant),
+}
+
+/// Anything that may follow the program header. Imports may be
+/// interleaved with declarations.
+enum Entry {
+    ProgramImport(ProgramImport),
+    FileImport((String, Span)),
+    Item(Item),
+}
+
+/// Parses consecutive `/// text` lines preceding a declaration.
+fn doc_comments() -> impl Parser<char, Vec<String>, Error = Simple<char>> + Clone {
+    just("///")
+        .ignore_then(filter(|c: &char| *c != '\n').repeated().collect::<String>())
+        .map(|line| line.trim().to_string())
+        .padded()
+        .repeated()
+}
+
+/// Parses a PDA seed: a string literal or an account parameter name.
+fn seed() -> impl Parser<char, Seed, Error = Simple<char>> + Clone {
+    choice((
+        just('"')
+            .ignore_then(none_of('"').repeated().collect::<String>())
+            .then_ignore(just('"'))
+            .map(Seed::Const),
+        text::ident().map(Seed::Account),
+    ))
+    .padded()
+}
+
+/// Parses a declaration name together with where it appears.
+fn located_ident(
+    locator: Rc<Locator>,
+) -> impl Parser<char, (String, Span), Error = Simple<char>> + Clone {
+    text::ident()
+        .map_with_span(move |name, span: Range<usize>| (name, locator.span(span.start)))
+        .padded()
+}
+
+/// Parses a keyword, giving where it appears.
+fn located_keyword(
+    locator: Rc<Locator>,
+    keyword: &'static str,
+) -> impl Parser<char, Span, Error = Simple<char>> + Clone {
+    text::keyword(keyword)
+        .map_with_span(move |(), span: Range<usize>| locator.span(span.start))
+        .padded()
+}
+
+/// Parses `name = ident` inside an annotation argument list.
+fn named_arg(name: &'static str) -> impl Parser<char, String, Error = Simple<char>> + Clone {
+    text::keyword(name)
+        .padded()
+        .ignore_then(just("=").padded())
+        .ignore_then(text::ident().padded())
+}
+
+fn source_parser(locator: Rc<Locator>) -> impl Parser<char, SourceFile, Error = Simple<char>> {
     let ident = text::ident().padded();
+    let name = located_ident(locator.clone());
 
-    let keyword = |s: &'static str| {
-        just(s).padded()
-    };
+    let keyword = |s: &'static str| just(s).padded();
 
+    // Every type parses everywhere; the HIR checks where account types and
+    // data types may appear.
     let type_parser = recursive(|ty| {
+        let argument = ty.delimited_by(just("<").padded(), just(">").padded());
         choice((
-            just("Pubkey").to(Type::Pubkey),
-            just("u8").to(Type::U8),
-            just("u16").to(Type::U16),
-            just("u32").to(Type::U32),
-            just("u64").to(Type::U64),
-            just("i8").to(Type::I8),
-            just("i16").to(Type::I16),
-            just("i32").to(Type::I32),
-            just("i64").to(Type::I64),
-            just("bool").to(Type::Bool),
-            just("String").to(Type::String),
-            ty.clone()
-                .delimited_by(just("Vec<"), just(">"))
+            text::keyword("Pubkey").to(Type::Pubkey),
+            text::keyword("u8").to(Type::U8),
+            text::keyword("u16").to(Type::U16),
+            text::keyword("u32").to(Type::U32),
+            text::keyword("u64").to(Type::U64),
+            text::keyword("i8").to(Type::I8),
+            text::keyword("i16").to(Type::I16),
+            text::keyword("i32").to(Type::I32),
+            text::keyword("i64").to(Type::I64),
+            text::keyword("bool").to(Type::Bool),
+            text::keyword("String").to(Type::String),
+            text::keyword("Vec")
+                .ignore_then(argument.clone())
                 .map(|t| Type::Vec(Box::new(t))),
-            ty.clone()
-                .delimited_by(just("Option<"), just(">"))
+            text::keyword("Option")
+                .ignore_then(argument.clone())
                 .map(|t| Type::Option(Box::new(t))),
+            text::keyword("Signer").to(Type::Signer),
+            text::keyword("TokenAccount").to(Type::TokenAccount),
+            text::keyword("Mint").to(Type::Mint),
+            text::ident().map(Type::Named),
         ))
         .padded()
     });
 
-    let field = ident
+    let field = doc_comments()
+        .then(ident)
         .then_ignore(just(":").padded())
-        .then(type_parser)
-        .map(|(name, ty)| Field { name, ty })
+        .then(type_parser.clone())
+        .then_ignore(just(",").padded().or_not())
+        .map(|((docs, name), ty)| Field { docs, name, ty })
         .padded();
 
-    let account_def = keyword("account")
-        .ignore_then(ident)
+    let event_def = doc_comments()
+        .then_ignore(keyword("event"))
+        .then(name.clone())
         .then(
-            field.repeated()
+            field
+                .clone()
+                .repeated()
                 .delimited_by(just("{").padded(), just("}").padded()),
         )
-        .map(|(name, fields)| AccountDef { name, fields })
+        .map(|((docs, (name, span)), fields)| EventDef {
+            docs,
+            name,
+            span,
+            fields,
+        })
         .padded();
 
-    let param_type_parser = choice((
-        just("Signer").to(ParamType::Signer),
-        just("Pubkey").to(ParamType::Pubkey),
-        just("u8").to(ParamType::U8),
-        just("u16").to(ParamType::U16),
-        just("u32").to(ParamType::U32),
-        just("u64").to(ParamType::U64),
-        just("i8").to(ParamType::I8),
-        just("i16").to(ParamType::I16),
-        just("i32").to(ParamType::I32),
-        just("i64").to(ParamType::I64),
-        just("bool").to(ParamType::Bool),
-        just("String").to(ParamType::String),
-        ident.map(ParamType::Account),
-    ))
-    .padded();
+    let struct_def = doc_comments()
+        .then_ignore(keyword("struct"))
+        .then(name.clone())
+        .then(
+            field
+                .clone()
+                .repeated()
+                .delimited_by(just("{").padded(), just("}").padded()),
+        )
+        .map(|((docs, (name, span)), fields)| StructDef {
+            docs,
+            name,
+            span,
+            fields,
+        })
+        .padded();
+
+    let extern_fn = doc_comments()
+        .then_ignore(keyword("extern"))
+        .then_ignore(keyword("fn"))
+        .then(name.clone())
+        .then(
+            ident
+                .then_ignore(just(":").padded())
+                .then(type_parser.clone())
+                .separated_by(just(",").padded())
+                .delimited_by(just("(").padded(), just(")").padded()),
+        )
+        .then(
+            just("->")
+                .padded()
+                .ignore_then(type_parser.clone())
+                .or_not(),
+        )
+        .map(|(((docs, (name, span)), params), ret)| ExternFn {
+            docs,
+            name,
+            span,
+            params,
+            ret,
+        })
+        .padded();
+
+    let annotation = just("@")
+        .ignore_then(choice((
+            text::keyword("associated_token")
+                .ignore_then(
+                    named_arg("mint")
+                        .then_ignore(just(",").padded())
+                        .then(named_arg("authority"))
+                        .delimited_by(just("(").padded(), just(")").padded()),
+                )
+                .map(|(mint, authority)| ParamAnnotation::AssociatedToken { mint, authority }),
+            text::keyword("init_if_needed")
+                .ignore_then(
+                    named_arg("payer").delimited_by(just("(").padded(), just(")").padded()),
+                )
+                .map(|payer| ParamAnnotation::InitIfNeeded { payer }),
+            text::keyword("seeds")
+                .ignore_then(
+                    seed()
+                        .separated_by(just(",").padded())
+                        .delimited_by(just("(").padded(), just(")").padded()),
+                )
+                .map(ParamAnnotation::Seeds),
+            text::keyword("mut").to(ParamAnnotation::Mut),
+        )))
+        .padded();
 
-    let param = ident
+    let param = name
+        .clone()
         .then_ignore(just(":").padded())
-        .then(param_type_parser)
-        .map(|(name, ty)| Param { name, ty })
+        .then(type_parser.clone())
+        .then(annotation.repeated())
+        .map(|(((name, span), ty), annotations)| Param {
+            name,
+            span,
+            ty,
+            annotations,
+        })
         .padded();
 
-    let expr_parser = recursive(|_expr| {
+    let expr_parser = recursive(|expr| {
         let literal = choice((
-            text::int(10)
-                .map(|s: String| {
-                    if s.starts_with('-') {
-                        Literal::Int(s.parse().unwrap_or(0))
-                    } else {
-                        Literal::UInt(s.parse().unwrap_or(0))
-                    }
-                }),
+            text::int(10).map(|s: String| {
+                if s.starts_with('-') {
+                    Literal::Int(s.parse().unwrap_or(0))
+                } else {
+                    Literal::UInt(s.parse().unwrap_or(0))
+                }
+            }),
             just("true").to(Literal::Bool(true)),
             just("false").to(Literal::Bool(false)),
             just('"')
@@ -104,35 +344,39 @@ fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
         .map(Expr::Literal)
         .padded();
 
-        let atom = choice((
-            literal,
-            ident.map(Expr::Ident),
-        ));
+        let atom = choice((literal, ident.map(Expr::Ident)));
+
+        let call_args = expr
+            .separated_by(just(",").padded())
+            .delimited_by(just("(").padded(), just(")").padded());
 
         let field_access = atom
             .then(
                 just(".")
                     .ignore_then(ident)
+                    .then(call_args.or_not())
                     .repeated()
                     .collect::<Vec<_>>(),
             )
-            .foldl(|obj, field| {
-                Expr::FieldAccess {
+            .foldl(|obj, (field, args)| match args {
+                Some(args) => Expr::MethodCall {
+                    object: Box::new(obj),
+                    method: field,
+                    args,
+                },
+                None => Expr::FieldAccess {
                     object: Box::new(obj),
                     field,
-                }
+                },
             });
 
-        let unary = choice((
-            just("!").to(UnOp::Not),
-            just("-").to(UnOp::Neg),
-        ))
-        .then(field_access.clone())
-        .map(|(op, expr)| Expr::UnaryOp {
-            op,
-            operand: Box::new(expr),
-        })
-        .or(field_access);
+        let unary = choice((just("!").to(UnOp::Not), just("-").to(UnOp::Neg)))
+            .then(field_access.clone())
+            .map(|(op, expr)| Expr::UnaryOp {
+                op,
+                operand: Box::new(expr),
+            })
+            .or(field_access);
 
         let product = unary
             .clone()
@@ -155,13 +399,10 @@ fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
         let sum = product
             .clone()
             .then(
-                choice((
-                    just("+").to(BinOp::Add),
-                    just("-").to(BinOp::Sub),
-                ))
-                .then(product.clone())
-                .repeated()
-                .collect::<Vec<_>>(),
+                choice((just("+").to(BinOp::Add), just("-").to(BinOp::Sub)))
+                    .then(product.clone())
+                    .repeated()
+                    .collect::<Vec<_>>(),
             )
             .foldl(|lhs, (op, rhs)| Expr::BinaryOp {
                 op,
@@ -175,10 +416,10 @@ fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
                 choice((
                     just("==").to(BinOp::Eq),
                     just("!=").to(BinOp::Ne),
-                    just("<").to(BinOp::Lt),
                     just("<=").to(BinOp::Le),
-                    just(">").to(BinOp::Gt),
+                    just("<").to(BinOp::Lt),
                     just(">=").to(BinOp::Ge),
+                    just(">").to(BinOp::Gt),
                 ))
                 .then(sum.clone())
                 .repeated()
@@ -219,6 +460,56 @@ fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
             })
     });
 
+    let account_invariant = located_keyword(locator.clone(), "invariant")
+        .then(expr_parser.clone())
+        .then(
+            just(",")
+                .padded()
+                .ignore_then(
+                    just('"')
+                        .ignore_then(none_of('"').repeated().collect::<String>())
+                        .then_ignore(just('"')),
+                )
+                .or_not(),
+        )
+        .then_ignore(just(",").padded().or_not())
+        .map(|((span, condition), message)| AccountInvariant {
+            span,
+            condition,
+            message,
+        })
+        .padded();
+
+    // Fields come first: `invariant: u64` is a field, not an invariant.
+    let account_def = doc_comments()
+        .then_ignore(keyword("account"))
+        .then(name.clone())
+        .then(
+            choice((
+                field.map(AccountMember::Field),
+                account_invariant.map(AccountMember::Invariant),
+            ))
+            .repeated()
+            .delimited_by(just("{").padded(), just("}").padded()),
+        )
+        .map(|((docs, (name, span)), members)| {
+            let mut account = AccountDef {
+                docs,
+                name,
+                span,
+                fields: Vec::new(),
+                invariants: Vec::new(),
+            };
+            for member in members {
+                match member {
+                    AccountMember::Field(field) => account.fields.push(field),
+                    AccountMember::Invariant(invariant) => account.invariants.push(invariant),
+                }
+            }
+            account
+        })
+        .padded();
+
     let statement_parser = recursive(|_stmt| {
         let init_account = keyword("init")
             .ignore_then(keyword("account"))
@@ -227,23 +518,22 @@ fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
             .then(ident) // account type name
             .then_ignore(keyword("payer").padded())
             .then(ident) // payer name
-            .then(
-                keyword("signer")
-                    .ignore_then(ident)
-                    .or_not(),
+            .then(keyword("signer").ignore_then(ident).or_not())
+            .map(
+                |(((var_name, acc_type), payer), signer)| Statement::InitAccount {
+                    var_name,
+                    account_name: acc_type,
+                    payer,
+                    signer,
+                },
             )
-            .map(|(((var_name, acc_type), payer), signer)| Statement::InitAccount {
-                var_name,
-                account_name: acc_type,
-                payer,
-                signer,
-            })
             .padded();
 
         let require = keyword("require")
             .ignore_then(expr_parser.clone())
             .then(
                 just(",")
+                    .padded()
                     .ignore_then(
                         just('"')
                             .ignore_then(none_of('"').repeated().collect::<String>())
@@ -254,6 +544,32 @@ fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
             .map(|(condition, message)| Statement::Require { condition, message })
             .padded();
 
+        let close = text::keyword("close")
+            .padded()
+            .ignore_then(ident)
+            .then_ignore(text::keyword("to").padded())
+            .then(ident)
+            .map(|(account, recipient)| Statement::Close { account, recipient })
+            .padded();
+
+        let field_init = ident
+            .then(just(":").padded().ignore_then(expr_parser.clone()).or_not())
+            .map(|(name, value)| {
+                let value = value.unwrap_or_else(|| Expr::Ident(name.clone()));
+                (name, value)
+            });
+
+        let emit = keyword("emit")
+            .ignore_then(ident)
+            .then(
+                field_init
+                    .separated_by(just(",").padded())
+                    .allow_trailing()
+                    .delimited_by(just("{").padded(), just("}").padded()),
+            )
+            .map(|(event, fields)| Statement::Emit { event, fields })
+            .padded();
+
         let assign_op = choice((
             just("+=").to(Some(BinOp::Add)),
             just("-=").to(Some(BinOp::Sub)),
@@ -290,15 +606,19 @@ fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
         choice((
             init_account,
             require,
+            emit,
+            close,
             assign,
             expr_parser.clone().map(Statement::Expr),
         ))
     });
 
-    let instruction = keyword("instruction")
-        .ignore_then(ident)
+    let instruction = doc_comments()
+        .then_ignore(keyword("instruction"))
+        .then(name.clone())
         .then(
-            param.separated_by(just(",").padded())
+            param
+                .separated_by(just(",").padded())
                 .delimited_by(just("(").padded(), just(")").padded()),
         )
         .then(
@@ -306,17 +626,146 @@ fn program_parser() -> impl Parser<char, Program, Error = Simple<char>> {
                 .repeated()
                 .delimited_by(just("{").padded(), just("}").padded()),
         )
-        .map(|((name, params), body)| Instruction { name, params, body })
+        .map(|(((docs, (name, span)), params), body)| Instruction {
+            docs,
+            name,
+            span,
+            params,
+            body,
+        })
         .padded();
 
+    let test_call = text::keyword("call")
+        .padded()
+        .ignore_then(name.clone())
+        .then(
+            ident
+                .then_ignore(just(":").padded())
+                .then(expr_parser.clone())
+                .separated_by(just(",").padded())
+                .allow_trailing()
+                .delimited_by(just("(").padded(), just(")").padded()),
+        )
+        .then(
+            text::keyword("signed_by")
+                .padded()
+                .ignore_then(
+                    ident
+                        .separated_by(just(",").padded())
+                        .delimited_by(just("(").padded(), just(")").padded()),
+                )
+                .or_not(),
+        )
+        .then(
+            text::keyword("expect_err")
+                .padded()
+                .ignore_then(ident)
+                .or_not(),
+        )
+        .map(|((((instruction, span), args), signers), expect_err)| {
+            TestStatement::Call(TestCall {
+                instruction,
+                span,
+                args,
+                signers,
+                expect_err,
+            })
+        });
+
+    let test_let = text::keyword("let")
+        .padded()
+        .ignore_then(ident)
+        .then_ignore(just("=").padded())
+        .then(choice((
+            text::keyword("pda")
+                .padded()
+                .ignore_then(
+                    seed()
+                        .separated_by(just(",").padded())
+                        .delimited_by(just("(").padded(), just(")").padded()),
+                )
+                .map(TestValue::Pda),
+            expr_parser.clone().map(TestValue::Expr),
+        )))
+        .map(|(name, value)| TestStatement::Let { name, value });
+
+    let test_expect = located_keyword(locator.clone(), "expect")
+        .then(expr_parser.clone())
+        .map(|(span, condition)| TestStatement::Expect { condition, span });
+
+    let test_def = located_keyword(locator.clone(), "test")
+        .then(
+            just('"')
+                .ignore_then(none_of('"').repeated().collect::<String>())
+                .then_ignore(just('"'))
+                .padded(),
+        )
+        .then(
+            choice((test_call, test_let, test_expect))
+                .then_ignore(just(";").padded().or_not())
+                .repeated()
+                .delimited_by(just("{").padded(), just("}").padded()),
+        )
+        .map(|((span, name), body)| TestDef { name, span, body })
+        .padded();
+
+    let invariant = located_keyword(locator.clone(), "invariant")
+        .then(expr_parser.clone())
+        .map(|(span, condition)| InvariantDef { span, condition })
+        .padded();
+
+    let program_import = keyword("import")
+        .ignore_then(keyword("program"))
+        .ignore_then(ident)
+        .then_ignore(keyword("from"))
+        .then(
+            just('"')
+                .ignore_then(none_of('"').repeated().collect::<String>())
+                .then_ignore(just('"')),
+        )
+        .map(|(name, idl_path)| ProgramImport { name, idl_path })
+        .padded();
+
+    let file_import = keyword("import")
+        .ignore_then(
+            just('"')
+                .ignore_then(none_of('"').repeated().collect::<String>())
+                .then_ignore(just('"'))
+                .map_with_span(move |path, span: Range<usize>| (path, locator.span(span.start))),
+        )
+        .padded();
+
+    let entry = choice((
+        program_import.map(Entry::ProgramImport),
+        file_import.map(Entry::FileImport),
+        account_def.map(|a| Entry::Item(Item::Account(a))),
+        struct_def.map(|s| Entry::Item(Item::Struct(s))),
+        event_def.map(|e| Entry::Item(Item::Event(e))),
+        extern_fn.map(|e| Entry::Item(Item::Extern(e))),
+        instruction.map(|i| Entry::Item(Item::Instruction(i))),
+        test_def.map(|t| Entry::Item(Item::Test(t))),
+        invariant.map(|i| Entry::Item(Item::Invariant(i))),
+    ));
+
     keyword("program")
         .ignore_then(ident)
-        .then(account_def.repeated())
-        .then(instruction.repeated())
-        .map(|((name, accounts), instructions)| Program {
-            name,
-            accounts,
-            instructions,
+        .or_not()
+        .then(entry.repeated())
+        .map(|(header, entries)| {
+            let mut file = SourceFile {
+                header,
+                program_imports: Vec::new(),
+                file_imports: Vec::new(),
+                items: Vec::new(),
+            };
+            for entry in entries {
+                match entry {
+                    Entry::ProgramImport(import) => file.program_imports.push(import),
+                    Entry::FileImport(import) => file.file_imports.push(import),
+                    Entry::Item(item) => file.items.push(item),
+                }
+            }
+            file
         })
         .then_ignore(end())
 }
@@ -349,4 +798,359 @@ instruction increment(authority: Signer) {
         let result = parse(source);
         assert!(result.is_ok(), "Parse failed: {:?}", result.err());
     }
+
+    #[test]
+    fn test_parse_associated_token_annotations() {
+        let source = r#"
+program Vault
+
+instruction deposit(
+  user: Signer,
+  mint: Mint,
+  user_ata: TokenAccount @associated_token(mint = mint, authority = user) @init_if_needed(payer = user)
+) {
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        let param = &program.instructions[0].params[2];
+        assert_eq!(param.ty, Type::TokenAccount);
+        assert_eq!(
+            param.annotations,
+            vec![
+                ParamAnnotation::AssociatedToken {
+                    mint: "mint".to_string(),
+                    authority: "user".to_string(),
+                },
+                ParamAnnotation::InitIfNeeded {
+                    payer: "user".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_program_import_and_cpi_call() {
+        let source = r#"
+program Router
+
+import program Vault from "idl/vault.json"
+
+instruction route(user: Signer, amount: u64) {
+  vault.deposit(user, amount + 1)
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        assert_eq!(
+            program.imports,
+            vec![ProgramImport {
+                name: "Vault".to_string(),
+                idl_path: "idl/vault.json".to_string(),
+            }]
+        );
+        match &program.instructions[0].body[0] {
+            Statement::Expr(Expr::MethodCall {
+                object,
+                method,
+                args,
+            }) => {
+                assert_eq!(**object, Expr::Ident("vault".to_string()));
+                assert_eq!(method, "deposit");
+                assert_eq!(args.len(), 2);
+            }
+            other => panic!("expected method call, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_require_with_comparison_and_message() {
+        let source = r#"
+program Lock
+
+instruction unlock(state: LockState) {
+  require clock.unix_timestamp >= state.unlock_at, "Locked"
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        match &program.instructions[0].body[0] {
+            Statement::Require {
+                condition: Expr::BinaryOp { op, .. },
+                message,
+            } => {
+                assert_eq!(*op, BinOp::Ge);
+                assert_eq!(message.as_deref(), Some("Locked"));
+            }
+            other => panic!("expected require, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_event_and_emit() {
+        let source = r#"
+program Vault
+
+event Deposited { user: Pubkey, amount: u64 }
+
+instruction deposit(user: Signer, amount: u64) {
+  emit Deposited { user: user.key, amount }
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        assert_eq!(program.events[0].name, "Deposited");
+        assert_eq!(program.events[0].fields.len(), 2);
+        match &program.instructions[0].body[0] {
+            Statement::Emit { event, fields } => {
+                assert_eq!(event, "Deposited");
+                assert_eq!(
+                    fields[1],
+                    ("amount".to_string(), Expr::Ident("amount".to_string()))
+                );
+            }
+            other => panic!("expected emit, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_close() {
+        let source = r#"
+program Escrow
+
+instruction cancel(maker: Signer, escrow: EscrowState) {
+  close escrow to maker
+  closed_at = 1
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        assert_eq!(
+            program.instructions[0].body[0],
+            Statement::Close {
+                account: "escrow".to_string(),
+                recipient: "maker".to_string(),
+            }
+        );
+        assert!(matches!(
+            program.instructions[0].body[1],
+            Statement::Assign { .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_doc_comments() {
+        let source = r#"
+program Counter
+
+/// Global counter state
+account CounterState {
+  /// Who may increment
+  authority: Pubkey
+  count: u64
+}
+
+/// Bump the counter
+instruction increment(authority: Signer, state: CounterState) {
+  state.count += 1
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        assert_eq!(program.accounts[0].docs, vec!["Global counter state"]);
+        assert_eq!(
+            program.accounts[0].fields[0].docs,
+            vec!["Who may increment"]
+        );
+        assert!(program.accounts[0].fields[1].docs.is_empty());
+        assert_eq!(program.instructions[0].docs, vec!["Bump the counter"]);
+    }
+
+    #[test]
+    fn test_parse_seeds_annotation() {
+        let source = r#"
+program Counter
+
+instruction initialize(authority: Signer, state: CounterState @seeds("counter", authority)) {
+  init account state: CounterState payer authority
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        assert_eq!(
+            program.instructions[0].params[1].annotations,
+            vec![ParamAnnotation::Seeds(vec![
+                Seed::Const("counter".to_string()),
+                Seed::Account("authority".to_string()),
+            ])]
+        );
+        let span = program.instructions[0].params[1].span;
+        assert_eq!((span.line, span.column), (4, 43));
+    }
+
+    #[test]
+    fn test_parse_account_invariants() {
+        let source = r#"
+program Vault
+
+account Vault {
+  total_shares: u64
+  invariant: u64
+  invariant total_shares <= total_deposits, "Shares exceed deposits"
+  total_deposits: u64
+  invariant invariant > 0
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        let vault = &program.accounts[0];
+        let fields: Vec<&str> = vault.fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(fields, vec!["total_shares", "invariant", "total_deposits"]);
+        assert_eq!(vault.invariants.len(), 2);
+        assert_eq!(vault.invariants[0].span.line, 7);
+        assert_eq!(
+            vault.invariants[0].message.as_deref(),
+            Some("Shares exceed deposits")
+        );
+        assert_eq!(vault.invariants[1].message, None);
+        assert!(matches!(
+            &vault.invariants[1].condition,
+            Expr::BinaryOp { op: BinOp::Gt, left, .. } if **left == Expr::Ident("invariant".to_string())
+        ));
+    }
+
+    #[test]
+    fn test_parse_extern_fn() {
+        let source = r#"
+program Vault
+
+/// Protocol fee for a deposit
+extern fn fee(amount: u64, rate: u16) -> u64
+extern fn log_deposit(user: Pubkey)
+
+instruction deposit(user: Signer, amount: u64) {
+  helpers.log_deposit(user.key)
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        assert_eq!(
+            program.externs[0],
+            ExternFn {
+                docs: vec!["Protocol fee for a deposit".to_string()],
+                name: "fee".to_string(),
+                span: Span {
+                    file: 0,
+                    line: 5,
+                    column: 11,
+                },
+                params: vec![
+                    ("amount".to_string(), Type::U64),
+                    ("rate".to_string(), Type::U16),
+                ],
+                ret: Some(Type::U64),
+            }
+        );
+        assert_eq!(program.externs[1].ret, None);
+    }
+
+    #[test]
+    fn test_parse_struct_and_data_params() {
+        let source = r#"
+program Registry
+
+/// A point on the grid
+struct Point {
+  x: u64
+  y: u64
+}
+
+account Entry {
+  at: Point
+  tags: Vec<u8>
+}
+
+instruction place(user: Signer, entry: Entry, at: Point, memo: Option<Vec<u8>>) {
+}
+"#;
+        let program = parse(source).expect("parse failed");
+        assert_eq!(program.structs[0].name, "Point");
+        assert_eq!(
+            program.structs[0].docs,
+            vec!["A point on the grid".to_string()]
+        );
+        assert_eq!(program.structs[0].fields[1].ty, Type::U64);
+        assert_eq!(
+            program.accounts[0].fields[1].ty,
+            Type::Vec(Box::new(Type::U8))
+        );
+        // Declared names are resolved to structs or accounts by the HIR
+        let types: Vec<&Type> = program.instructions[0]
+            .params
+            .iter()
+            .map(|p| &p.ty)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                &Type::Signer,
+                &Type::Named("Entry".to_string()),
+                &Type::Named("Point".to_string()),
+                &Type::Option(Box::new(Type::Vec(Box::new(Type::U8)))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_test_block_and_invariant() {
+        let source = r#"
+program Counter
+
+instruction increment(authority: Signer, state: CounterState) {
+  state.count += 1
+}
+
+test "increment requires authority" {
+  let s = pda("state", alice);
+  call increment(authority: bob, state: s) expect_err NotTheAuthority;
+  call increment(authority: alice, state: s) signed_by(bob)
+  expect s.count == 0
+}
+
+invariant s.count <= 10
+"#;
+        let program = parse(source).expect("parse failed");
+        assert_eq!(program.instructions.len(), 1);
+        assert_eq!(program.invariants[0].span.line, 15);
+        let test = &program.tests[0];
+        assert_eq!(test.name, "increment requires authority");
+        assert_eq!(test.span.line, 8);
+        assert_eq!(
+            test.body[0],
+            TestStatement::Let {
+                name: "s".to_string(),
+                value: TestValue::Pda(vec![
+                    Seed::Const("state".to_string()),
+                    Seed::Account("alice".to_string()),
+                ]),
+            }
+        );
+        match &test.body[1] {
+            TestStatement::Call(call) => {
+                assert_eq!(call.instruction, "increment");
+                assert_eq!(
+                    call.args[0],
+                    ("authority".to_string(), Expr::Ident("bob".to_string()))
+                );
+                assert_eq!(call.signers, None);
+                assert_eq!(call.expect_err.as_deref(), Some("NotTheAuthority"));
+            }
+            other => panic!("expected call, got {:?}", other),
+        }
+        match &test.body[2] {
+            TestStatement::Call(call) => {
+                assert_eq!(call.signers, Some(vec!["bob".to_string()]));
+                assert_eq!(call.expect_err, None);
+            }
+            other => panic!("expected call, got {:?}", other),
+        }
+        assert!(matches!(
+            test.body[3],
+            TestStatement::Expect {
+                span: Span { line: 12, .. },
+                ..
+            }
+        ));
+    }
 }
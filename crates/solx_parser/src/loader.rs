@@ -0,0 +1,177 @@
+//! Loading programs split across files.
+//!
+//! The root file starts with `program Name`; any file may pull in others
+//! with `import "accounts/vault.solx"`, resolved relative to the importing
+//! file. Imported files hold only declarations. Every file is loaded once,
+//! however many files import it, and its declarations come before those of
+//! the file that first imported it.
+
+use anyhow::{Context, Result};
+use solx_ast::{Program, Span};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::{add_items, parse_source};
+
+/// Parse the program rooted at `path` and every file it imports into a
+/// single [`Program`]. Spans refer to `Program::files`.
+pub fn load(path: &Path) -> Result<Program> {
+    let mut loader = Loader {
+        program: Program {
+            name: String::new(),
+            imports: Vec::new(),
+            accounts: Vec::new(),
+            structs: Vec::new(),
+            events: Vec::new(),
+            externs: Vec::new(),
+            instructions: Vec::new(),
+            tests: Vec::new(),
+            invariants: Vec::new(),
+            files: Vec::new(),
+        },
+        canonical: Vec::new(),
+        stack: Vec::new(),
+    };
+    loader.load_file(&normalize(path))?;
+    Ok(loader.program)
+}
+
+struct Loader {
+    program: Program,
+    /// Canonical path of each entry of `program.files`
+    canonical: Vec<PathBuf>,
+    /// Files whose imports are being loaded, outermost first
+    stack: Vec<usize>,
+}
+
+impl Loader {
+    fn load_file(&mut self, path: &Path) -> Result<()> {
+        let source = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let canonical = fs::canonicalize(path)
+            .with_context(|| format!("Failed to resolve {}", path.display()))?;
+
+        let index = self.program.files.len();
+        let display = path.display().to_string();
+        self.program.files.push(display.clone());
+        self.canonical.push(canonical);
+
+        let file = parse_source(&source, index, Some(&display))?;
+        let is_root = index == 0;
+        match (&file.header, is_root) {
+            (Some(name), true) => self.program.name = name.clone(),
+            (None, true) => anyhow::bail!("{}: expected `program <Name>`", display),
+            (Some(name), false) => anyhow::bail!(
+                "{}: `program {}` is only allowed in the root file",
+                display,
+                name
+            ),
+            (None, false) => {}
+        }
+        if !is_root && !file.program_imports.is_empty() {
+            anyhow::bail!(
+                "{}: `import program` is only allowed in the root file",
+                display
+            );
+        }
+        self.program.imports.extend(file.program_imports);
+
+        self.stack.push(index);
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for (import, span) in &file.file_imports {
+            self.load_import(&normalize(&dir.join(import)), *span)?;
+        }
+        self.stack.pop();
+
+        add_items(&mut self.program, file.items);
+        Ok(())
+    }
+
+    /// Load `path` unless it was already loaded, rejecting import cycles.
+    fn load_import(&mut self, path: &Path, span: Span) -> Result<()> {
+        let location = self.program.location(span).unwrap_or_default();
+        let canonical = fs::canonicalize(path)
+            .with_context(|| format!("{}: cannot find {}", location, path.display()))?;
+
+        let Some(index) = self.canonical.iter().position(|c| *c == canonical) else {
+            return self.load_file(path);
+        };
+        if let Some(start) = self.stack.iter().position(|&i| i == index) {
+            let cycle: Vec<&str> = self.stack[start..]
+                .iter()
+                .chain(std::iter::once(&index))
+                .map(|&i| self.program.files[i].as_str())
+                .collect();
+            anyhow::bail!("{}: import cycle: {}", location, cycle.join(" -> "));
+        }
+        Ok(())
+    }
+}
+
+/// Drop `.` and `..` components so diagnostics show `src/deposit.solx`
+/// rather than `src/accounts/../deposit.solx`.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(out.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_imports_and_reject_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        fs::create_dir_all(root.join("accounts")).unwrap();
+        let write = |file: &str, source: &str| fs::write(root.join(file), source).unwrap();
+
+        write(
+            "program.solx",
+            "program Vault\n\nimport \"accounts/vault.solx\"\nimport \"deposit.solx\"\n",
+        );
+        write(
+            "accounts/vault.solx",
+            "account VaultState {\n  total: u64\n}\n",
+        );
+        write(
+            "deposit.solx",
+            "import \"accounts/vault.solx\"\n\ninstruction deposit(user: Signer, state: VaultState) {\n  state.total += 1\n}\n",
+        );
+
+        let program = load(&root.join("program.solx")).unwrap();
+        assert_eq!(program.name, "Vault");
+        assert_eq!(program.files.len(), 3);
+        assert_eq!(program.accounts.len(), 1);
+        assert_eq!(
+            program.location(program.instructions[0].span),
+            Some(format!("{}:3:13", root.join("deposit.solx").display()))
+        );
+
+        write("accounts/vault.solx", "import \"../deposit.solx\"\n");
+        let err = load(&root.join("program.solx")).unwrap_err().to_string();
+        assert!(err.contains("import cycle"), "{}", err);
+        let vault = root
+            .join("accounts")
+            .join("vault.solx")
+            .display()
+            .to_string();
+        let deposit = root.join("deposit.solx").display().to_string();
+        assert!(
+            err.ends_with(&format!("{} -> {} -> {}", vault, deposit, vault)),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Verification of SOL-X programs with an SMT solver.
+//!
+//! Each instruction is lowered from the HIR to SMT-LIB ([`lower`]) as a
+//! list of obligations: its arithmetic cannot overflow or divide by zero
+//! under the `require`s before it, and it preserves the invariants of the
+//! accounts it writes. A local solver ([`Solver`]) then looks for inputs
+//! that break each obligation; the inputs it finds are a counterexample in
+//! terms of the instruction's arguments and the accounts' fields.
+
+pub mod lower;
+pub mod solver;
+
+use anyhow::Result;
+
+use solx_hir::Hir;
+
+pub use lower::{lower, Input, Kind, Lowered, Obligation};
+pub use solver::Solver;
+
+/// The solver's answer for one obligation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// The obligation holds for every input
+    Proved,
+    /// Input values, by SOL-X name, for which the obligation fails
+    Counterexample(Vec<(String, String)>),
+    /// The solver gave up, e.g. `unknown`
+    Unknown(String),
+}
+
+/// An obligation of an instruction and the solver's verdict.
+#[derive(Debug, Clone)]
+pub struct Checked {
+    pub instruction: String,
+    pub obligation: Obligation,
+    pub verdict: Verdict,
+}
+
+/// Lower every instruction of the program.
+pub fn lower_program(hir: &Hir) -> Result<Vec<Lowered>> {
+    hir.program
+        .instructions
+        .iter()
+        .map(|instruction| lower(hir, instruction))
+        .collect()
+}
+
+/// Check every obligation of every instruction with `solver`.
+pub fn verify(hir: &Hir, solver: &Solver) -> Result<Vec<Checked>> {
+    let mut checked = Vec::new();
+    for lowered in lower_program(hir)? {
+        let verdicts = solver.check(&lowered)?;
+        for (obligation, verdict) in lowered.obligations.iter().zip(verdicts) {
+            checked.push(Checked {
+                instruction: lowered.instruction.clone(),
+                obligation: obligation.clone(),
+                verdict,
+            });
+        }
+    }
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solx_hir::Arithmetic;
+
+    const VAULT: &str = r#"
+program Vault
+
+account Vault {
+  total_shares: u64
+  total_deposits: u64
+  invariant total_shares <= total_deposits
+}
+
+instruction deposit(user: Signer, vault: Vault, amount: u64) {
+  require amount > 0
+  vault.total_deposits += amount
+  vault.total_shares += amount
+}
+
+instruction withdraw(user: Signer, vault: Vault, shares: u64) {
+  vault.total_shares -= shares
+  vault.total_deposits -= shares / 2
+}
+"#;
+
+    fn hir(arithmetic: Arithmetic) -> Hir {
+        let program = solx_parser::parse(VAULT).unwrap();
+        Hir::from_ast(program).unwrap().with_arithmetic(arithmetic)
+    }
+
+    #[test]
+    fn test_lower_obligations() {
+        let lowered = lower_program(&hir(Arithmetic::Checked)).unwrap();
+        let descriptions: Vec<&str> = lowered[0]
+            .obligations
+            .iter()
+            .map(|o| o.description.as_str())
+            .collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                "vault.total_deposits + amount does not overflow u64",
+                "vault.total_shares + amount does not overflow u64",
+                "invariant vault.total_shares <= vault.total_deposits holds",
+            ]
+        );
+        let script = lowered[0].script();
+        assert!(script.contains(
+            "(define-fun |vault.total_deposits@1| () (_ BitVec 64) (bvadd |vault.total_deposits| |amount|))"
+        ));
+        // The invariant is assumed on entry and checked on the new fields
+        let invariant = &lowered[0].obligations[2];
+        assert!(lowered[0]
+            .refutation(invariant)
+            .contains("(assert (bvule |vault.total_shares| |vault.total_deposits|))"));
+        assert!(lowered[0]
+            .refutation(invariant)
+            .ends_with("(assert (not (bvule |vault.total_shares@1| |vault.total_deposits@1|)))\n"));
+
+        let kinds: Vec<Kind> = lowered[1].obligations.iter().map(|o| o.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Kind::Overflow,
+                Kind::DivisionByZero,
+                Kind::Overflow,
+                Kind::Invariant
+            ]
+        );
+
+        // Wrapping arithmetic cannot overflow, but can divide by zero
+        let lowered = lower_program(&hir(Arithmetic::Wrapping)).unwrap();
+        let kinds: Vec<Kind> = lowered[1].obligations.iter().map(|o| o.kind).collect();
+        assert_eq!(kinds, vec![Kind::DivisionByZero, Kind::Invariant]);
+
+        // A closed account no longer has to keep its invariant
+        let source = VAULT.replace(
+            "  vault.total_deposits -= shares / 2\n",
+            "  vault.total_deposits -= shares / 2\n  close vault to user\n",
+        );
+        let hir = Hir::from_ast(solx_parser::parse(&source).unwrap()).unwrap();
+        let lowered = lower_program(&hir.with_arithmetic(Arithmetic::Wrapping)).unwrap();
+        let kinds: Vec<Kind> = lowered[1].obligations.iter().map(|o| o.kind).collect();
+        assert_eq!(kinds, vec![Kind::DivisionByZero]);
+    }
+
+    #[test]
+    fn test_model_values() {
+        let model = solver::parse("((|amount| #xffffffffffffffff) (|x| (_ bv200 8)))\n").unwrap();
+        let solver::Sexp::List(pairs) = model else {
+            panic!("expected a list");
+        };
+        let value = |i: usize| match &pairs[i] {
+            solver::Sexp::List(pair) => pair[1].clone(),
+            _ => panic!("expected a pair"),
+        };
+        assert_eq!(
+            solver::format_value(&value(0), &solx_ast::Type::U64),
+            "18446744073709551615"
+        );
+        assert_eq!(solver::format_value(&value(1), &solx_ast::Type::I8), "-56");
+        assert_eq!(solver::parse("(error \"unclosed"), None);
+    }
+
+    /// Needs z3 or cvc5 on the PATH; run with
+    /// `cargo test -p solx_verify -- --ignored`.
+    #[test]
+    #[ignore = "needs z3 or cvc5"]
+    fn test_verify_with_local_solver() {
+        let solver = Solver::find(None).expect("z3 or cvc5 on the PATH");
+        let checked = verify(&hir(Arithmetic::Checked), &solver).unwrap();
+        for c in checked.iter().filter(|c| c.instruction == "deposit") {
+            assert_eq!(c.verdict, Verdict::Proved, "{}", c.obligation.description);
+        }
+        let underflow = &checked
+            .iter()
+            .find(|c| c.instruction == "withdraw")
+            .unwrap()
+            .verdict;
+        let Verdict::Counterexample(values) = underflow else {
+            panic!("expected a counterexample, got {:?}", underflow);
+        };
+        let value = |name: &str| {
+            values
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.parse::<u64>().unwrap())
+                .unwrap()
+        };
+        assert!(value("shares") > value("vault.total_shares"));
+    }
+}
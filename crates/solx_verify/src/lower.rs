@@ -0,0 +1,617 @@
+//! Lowering of one instruction to SMT-LIB.
+//!
+//! Integers are bit-vectors of their width, `bool` is `Bool`, and every
+//! other type is an uninterpreted sort that only supports equality. The
+//! body is walked in order, keeping the current term of every account
+//! field: an assignment defines a new version of the field, and a
+//! `require` adds its condition to the path condition of everything after
+//! it. Arithmetic adds an obligation that it cannot overflow under the
+//! path condition; under `panic` and `checked` arithmetic an overflow ends
+//! the instruction, so the path condition then also assumes there was none.
+//!
+//! The invariants of every account the instruction takes are assumed on
+//! entry, except for accounts it creates, whose fields start at zero. At
+//! the end, the invariants of every account it writes are obligations.
+//!
+//! Not modeled: two account parameters being the same account, CPIs
+//! changing accounts, and the results of `extern fn` helpers and sysvars,
+//! which are unconstrained inputs.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+
+use solx_ast::*;
+use solx_hir::accounts::close_recipient;
+use solx_hir::sysvar::Sysvar;
+use solx_hir::{Arithmetic, Hir};
+
+/// What an obligation asks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// `+ - * /`, `%` or negation stays within its type
+    Overflow,
+    /// A divisor is not zero
+    DivisionByZero,
+    /// An account invariant holds at the end of the instruction
+    Invariant,
+}
+
+/// A condition that must hold whenever execution reaches it.
+#[derive(Debug, Clone)]
+pub struct Obligation {
+    pub kind: Kind,
+    /// What must hold, in SOL-X terms, e.g. `state.count + amount does not overflow u64`
+    pub description: String,
+    /// The path condition
+    assumptions: Vec<String>,
+    goal: String,
+}
+
+/// A value the instruction starts from: an argument, an account field or
+/// key, a sysvar or the result of a helper.
+#[derive(Debug, Clone)]
+pub struct Input {
+    /// As written in SOL-X, e.g. `state.count`
+    pub name: String,
+    pub symbol: String,
+    pub ty: Type,
+}
+
+/// An instruction lowered to SMT-LIB declarations and obligations.
+#[derive(Debug, Clone)]
+pub struct Lowered {
+    pub instruction: String,
+    declarations: Vec<String>,
+    pub inputs: Vec<Input>,
+    pub obligations: Vec<Obligation>,
+}
+
+impl Lowered {
+    /// The declarations, to be sent to the solver once.
+    pub fn prelude(&self) -> String {
+        let mut out = String::new();
+        for declaration in &self.declarations {
+            out.push_str(declaration);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// The assertions that make `obligation` fail: its path condition and
+    /// the negated goal. Satisfiable means there is a counterexample.
+    pub fn refutation(&self, obligation: &Obligation) -> String {
+        let mut out = String::new();
+        for assumption in &obligation.assumptions {
+            out.push_str(&format!("(assert {})\n", assumption));
+        }
+        out.push_str(&format!("(assert (not {}))\n", obligation.goal));
+        out
+    }
+
+    /// A standalone script checking every obligation in turn.
+    pub fn script(&self) -> String {
+        let mut out = format!("; instruction {}\n", self.instruction);
+        out.push_str("(set-option :produce-models true)\n");
+        out.push_str(&self.prelude());
+        for obligation in &self.obligations {
+            out.push_str(&format!("\n; {}\n(push 1)\n", obligation.description));
+            out.push_str(&self.refutation(obligation));
+            out.push_str("(check-sat)\n(pop 1)\n");
+        }
+        out
+    }
+}
+
+/// Lower `instruction` of `hir`.
+pub fn lower(hir: &Hir, instruction: &Instruction) -> Result<Lowered> {
+    let mut lowerer = Lowerer {
+        hir,
+        instruction,
+        declarations: Vec::new(),
+        inputs: Vec::new(),
+        fields: BTreeMap::new(),
+        versions: BTreeMap::new(),
+        path: Vec::new(),
+        obligations: Vec::new(),
+        record: true,
+        fresh: 0,
+    };
+    lowerer
+        .instruction()
+        .with_context(|| format!("Cannot verify instruction {}", instruction.name))?;
+    Ok(Lowered {
+        instruction: instruction.name.clone(),
+        declarations: lowerer.declarations,
+        inputs: lowerer.inputs,
+        obligations: lowerer.obligations,
+    })
+}
+
+struct Lowerer<'a> {
+    hir: &'a Hir,
+    instruction: &'a Instruction,
+    declarations: Vec<String>,
+    inputs: Vec<Input>,
+    /// Current term of every account field read or written, by `param.field`
+    fields: BTreeMap<String, String>,
+    /// Assignments so far, by `param.field`
+    versions: BTreeMap<String, usize>,
+    path: Vec<String>,
+    obligations: Vec<Obligation>,
+    /// Whether arithmetic adds obligations; not for invariants themselves
+    record: bool,
+    fresh: usize,
+}
+
+impl<'a> Lowerer<'a> {
+    fn instruction(&mut self) -> Result<()> {
+        let created: Vec<&str> = self
+            .instruction
+            .body
+            .iter()
+            .filter_map(|s| match s {
+                Statement::InitAccount { var_name, .. } => Some(var_name.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        // Accounts created here start zeroed; the others keep their
+        // invariants on entry
+        let mut assumed = Vec::new();
+        for param in &self.instruction.params {
+            if created.contains(&param.name.as_str()) {
+                for field in self.account_fields(param) {
+                    let term = self.zero(&field.ty);
+                    self.fields
+                        .insert(format!("{}.{}", param.name, field.name), term);
+                }
+            } else {
+                assumed.extend(self.hir.account_invariants(param));
+            }
+        }
+        for check in assumed {
+            if let Statement::Require { condition, .. } = check {
+                let term = self.invariant(&condition)?;
+                self.path.push(term);
+            }
+        }
+
+        for stmt in &self.instruction.body {
+            self.statement(stmt)?;
+        }
+
+        // A closed account no longer exists, so it keeps no invariant
+        for param in self.hir.written_accounts(self.instruction) {
+            if close_recipient(self.instruction, &param.name).is_some() {
+                continue;
+            }
+            for check in self.hir.account_invariants(param) {
+                let Statement::Require { condition, .. } = check else {
+                    continue;
+                };
+                let goal = self.invariant(&condition)?;
+                self.oblige(
+                    Kind::Invariant,
+                    format!("invariant {} holds", condition),
+                    goal,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn account_fields(&self, param: &Param) -> Vec<Field> {
+        let Type::Account(account) = &param.ty else {
+            return Vec::new();
+        };
+        self.hir
+            .program
+            .accounts
+            .iter()
+            .find(|a| a.name == *account)
+            .map(|a| a.fields.clone())
+            .unwrap_or_default()
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Result<()> {
+        match stmt {
+            Statement::InitAccount { .. } | Statement::Close { .. } => {}
+            Statement::Require { condition, .. } => {
+                let term = self.bool(condition)?;
+                self.path.push(term);
+            }
+            Statement::Assign { target, value } => {
+                let Expr::FieldAccess { object, field } = target else {
+                    anyhow::bail!("cannot assign to {}", target);
+                };
+                let ty = self.hir.type_of(self.instruction, target)?;
+                let term = self.expr(value, Some(&ty))?.0;
+                let key = format!("{}.{}", object, field);
+                let version = self.versions.entry(key.clone()).or_insert(0);
+                *version += 1;
+                let symbol = quote(&format!("{}@{}", key, version));
+                let sort = self.sort(&ty);
+                self.declarations
+                    .push(format!("(define-fun {} () {} {})", symbol, sort, term));
+                self.fields.insert(key, symbol);
+            }
+            Statement::Emit { fields, .. } => {
+                for (_, value) in fields {
+                    self.expr(value, None)?;
+                }
+            }
+            Statement::Expr(Expr::MethodCall { object, args, .. }) if self.is_cpi(object) => {
+                // Accounts are passed by name; only the data arguments are values
+                for arg in args {
+                    if !matches!(arg, Expr::Ident(name) if self.is_account(name)) {
+                        self.expr(arg, None)?;
+                    }
+                }
+            }
+            Statement::Expr(Expr::MethodCall { object, args, .. }) if self.is_helpers(object) => {
+                for arg in args {
+                    self.expr(arg, None)?;
+                }
+            }
+            Statement::Expr(expr) => {
+                self.expr(expr, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// An invariant condition, whose own arithmetic is not checked.
+    fn invariant(&mut self, condition: &Expr) -> Result<String> {
+        self.record = false;
+        let term = self.bool(condition);
+        self.record = true;
+        term
+    }
+
+    fn bool(&mut self, expr: &Expr) -> Result<String> {
+        Ok(self.expr(expr, Some(&Type::Bool))?.0)
+    }
+
+    /// The term of `expr` and its type. `hint` types integer literals.
+    fn expr(&mut self, expr: &Expr, hint: Option<&Type>) -> Result<(String, Type)> {
+        Ok(match expr {
+            Expr::Literal(Literal::Bool(value)) => (value.to_string(), Type::Bool),
+            Expr::Literal(Literal::UInt(value)) => {
+                let ty = hint
+                    .filter(|t| t.is_integer())
+                    .cloned()
+                    .unwrap_or(Type::U64);
+                (bits(*value as i128, width(&ty)), ty)
+            }
+            Expr::Literal(Literal::Int(value)) => {
+                let ty = hint
+                    .filter(|t| t.is_integer())
+                    .cloned()
+                    .unwrap_or(Type::I64);
+                (bits(*value as i128, width(&ty)), ty)
+            }
+            Expr::Literal(Literal::String(value)) => {
+                let symbol = quote(&format!("{:?}", value));
+                self.declare(&symbol, &Type::String);
+                (symbol, Type::String)
+            }
+            Expr::Ident(name) => {
+                let ty = self.hir.type_of(self.instruction, expr)?;
+                (self.input(name, &ty), ty)
+            }
+            Expr::FieldAccess { object, field } => {
+                let ty = self.hir.type_of(self.instruction, expr)?;
+                let name = format!("{}.{}", object, field);
+                let is_data_field = match object.as_ref() {
+                    Expr::Ident(param) => self.is_data_field(param, field),
+                    // Only data fields hold structs
+                    Expr::FieldAccess { .. } => true,
+                    _ => false,
+                };
+                if is_data_field {
+                    let term = match self.fields.get(&name) {
+                        Some(term) => term.clone(),
+                        None => self.input(&name, &ty),
+                    };
+                    self.fields.insert(name, term.clone());
+                    (term, ty)
+                } else {
+                    // Account keys and sysvar fields are inputs
+                    (self.input(&name, &ty), ty)
+                }
+            }
+            Expr::UnaryOp { op, operand } => {
+                let (term, ty) = self.expr(operand, hint)?;
+                match op {
+                    UnOp::Not => (format!("(not {})", term), ty),
+                    UnOp::Neg => {
+                        let min = bits(int_min(&ty), width(&ty));
+                        self.arithmetic(
+                            Kind::Overflow,
+                            format!("{} does not overflow {}", expr, ty.to_rust_type()),
+                            format!("(not (= {} {}))", term, min),
+                        );
+                        (format!("(bvneg {})", term), ty)
+                    }
+                }
+            }
+            Expr::BinaryOp { op, left, right } => self.binary(expr, op, left, right, hint)?,
+            Expr::MethodCall { args, .. } => {
+                for arg in args {
+                    self.expr(arg, None)?;
+                }
+                // Helpers and sysvar methods return unconstrained values
+                let ty = self.hir.type_of(self.instruction, expr)?;
+                self.fresh += 1;
+                let symbol = quote(&format!("{}#{}", expr, self.fresh));
+                self.declare(&symbol, &ty);
+                self.inputs.push(Input {
+                    name: expr.to_string(),
+                    symbol: symbol.clone(),
+                    ty: ty.clone(),
+                });
+                (symbol, ty)
+            }
+        })
+    }
+
+    fn binary(
+        &mut self,
+        expr: &Expr,
+        op: &BinOp,
+        left: &Expr,
+        right: &Expr,
+        hint: Option<&Type>,
+    ) -> Result<(String, Type)> {
+        if matches!(op, BinOp::And | BinOp::Or) {
+            let l = self.bool(left)?;
+            // The right side only runs when the left does not decide
+            let guard = match op {
+                BinOp::And => l.clone(),
+                _ => format!("(not {})", l),
+            };
+            let (r, obligations, path) = self.guarded(&guard, |this| this.bool(right))?;
+            self.obligations.extend(obligations);
+            if !path.is_empty() {
+                self.path
+                    .push(format!("(=> {} (and true {}))", guard, path.join(" ")));
+            }
+            let smt_op = if *op == BinOp::And { "and" } else { "or" };
+            return Ok((format!("({} {} {})", smt_op, l, r), Type::Bool));
+        }
+
+        // The non-literal side decides the operand type
+        let is_literal = |e: &Expr| matches!(e, Expr::Literal(Literal::UInt(_) | Literal::Int(_)));
+        let (l, r, ty) = if is_literal(left) && !is_literal(right) {
+            let (r, ty) = self.expr(right, None)?;
+            let (l, _) = self.expr(left, Some(&ty))?;
+            (l, r, ty)
+        } else {
+            let operand_hint = if op.is_arithmetic() { hint } else { None };
+            let (l, ty) = self.expr(left, operand_hint)?;
+            let (r, _) = self.expr(right, Some(&ty))?;
+            (l, r, ty)
+        };
+        let signed = ty.is_signed();
+        let n = width(&ty);
+
+        let term = match op {
+            BinOp::Eq => return Ok((format!("(= {} {})", l, r), Type::Bool)),
+            BinOp::Ne => return Ok((format!("(not (= {} {}))", l, r), Type::Bool)),
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                let smt_op = match (op, signed) {
+                    (BinOp::Lt, false) => "bvult",
+                    (BinOp::Le, false) => "bvule",
+                    (BinOp::Gt, false) => "bvugt",
+                    (BinOp::Ge, false) => "bvuge",
+                    (BinOp::Lt, true) => "bvslt",
+                    (BinOp::Le, true) => "bvsle",
+                    (BinOp::Gt, true) => "bvsgt",
+                    _ => "bvsge",
+                };
+                return Ok((format!("({} {} {})", smt_op, l, r), Type::Bool));
+            }
+            BinOp::Add | BinOp::Sub | BinOp::Mul => {
+                let smt_op = match op {
+                    BinOp::Add => "bvadd",
+                    BinOp::Sub => "bvsub",
+                    _ => "bvmul",
+                };
+                // Compute in twice the width and check the result fits
+                let extend = if signed { "sign_extend" } else { "zero_extend" };
+                let wide = format!(
+                    "({} ((_ {} {}) {}) ((_ {} {}) {}))",
+                    smt_op, extend, n, l, extend, n, r
+                );
+                self.arithmetic(
+                    Kind::Overflow,
+                    format!("{} does not overflow {}", expr, ty.to_rust_type()),
+                    format!(
+                        "(= {} ((_ {} {}) ((_ extract {} 0) {})))",
+                        wide,
+                        extend,
+                        n,
+                        n - 1,
+                        wide
+                    ),
+                );
+                format!("({} {} {})", smt_op, l, r)
+            }
+            BinOp::Div | BinOp::Mod => {
+                self.arithmetic(
+                    Kind::DivisionByZero,
+                    format!("{} is not zero in {}", right, expr),
+                    format!("(not (= {} {}))", r, bits(0, n)),
+                );
+                if signed {
+                    self.arithmetic(
+                        Kind::Overflow,
+                        format!("{} does not overflow {}", expr, ty.to_rust_type()),
+                        format!(
+                            "(not (and (= {} {}) (= {} {})))",
+                            l,
+                            bits(int_min(&ty), n),
+                            r,
+                            bits(-1, n)
+                        ),
+                    );
+                }
+                let smt_op = match (op, signed) {
+                    (BinOp::Div, false) => "bvudiv",
+                    (BinOp::Div, true) => "bvsdiv",
+                    (_, false) => "bvurem",
+                    (_, true) => "bvsrem",
+                };
+                format!("({} {} {})", smt_op, l, r)
+            }
+            BinOp::And | BinOp::Or => unreachable!("handled above"),
+        };
+        Ok((term, ty))
+    }
+
+    /// Run `f` with `guard` assumed, returning its result and the
+    /// obligations and path conditions it added.
+    fn guarded(
+        &mut self,
+        guard: &str,
+        f: impl FnOnce(&mut Self) -> Result<String>,
+    ) -> Result<(String, Vec<Obligation>, Vec<String>)> {
+        let path_len = self.path.len();
+        let obligations_len = self.obligations.len();
+        self.path.push(guard.to_string());
+        let result = f(self)?;
+        let obligations = self.obligations.split_off(obligations_len);
+        let mut path = self.path.split_off(path_len);
+        path.remove(0);
+        Ok((result, obligations, path))
+    }
+
+    /// An arithmetic obligation. Where a failure ends the instruction, the
+    /// rest of it may assume the goal.
+    fn arithmetic(&mut self, kind: Kind, description: String, goal: String) {
+        if !self.record {
+            return;
+        }
+        if kind == Kind::Overflow && self.hir.arithmetic == Arithmetic::Wrapping {
+            return;
+        }
+        self.oblige(kind, description, goal.clone());
+        self.path.push(goal);
+    }
+
+    fn oblige(&mut self, kind: Kind, description: String, goal: String) {
+        self.obligations.push(Obligation {
+            kind,
+            description,
+            assumptions: self.path.clone(),
+            goal,
+        });
+    }
+
+    /// The symbol of an input, declared on first use.
+    fn input(&mut self, name: &str, ty: &Type) -> String {
+        let symbol = quote(name);
+        if !self.inputs.iter().any(|i| i.symbol == symbol) {
+            self.declare(&symbol, ty);
+            self.inputs.push(Input {
+                name: name.to_string(),
+                symbol: symbol.clone(),
+                ty: ty.clone(),
+            });
+        }
+        symbol
+    }
+
+    fn declare(&mut self, symbol: &str, ty: &Type) {
+        let sort = self.sort(ty);
+        let declaration = format!("(declare-const {} {})", symbol, sort);
+        if !self.declarations.contains(&declaration) {
+            self.declarations.push(declaration);
+        }
+    }
+
+    /// The SMT sort of `ty`, declaring uninterpreted sorts on first use.
+    fn sort(&mut self, ty: &Type) -> String {
+        if *ty == Type::Bool {
+            return "Bool".to_string();
+        }
+        if ty.is_integer() {
+            return format!("(_ BitVec {})", width(ty));
+        }
+        let sort = quote(&ty.to_rust_type());
+        let declaration = format!("(declare-sort {} 0)", sort);
+        if !self.declarations.contains(&declaration) {
+            self.declarations.push(declaration);
+        }
+        sort
+    }
+
+    /// The value of a new account's field: zero, `false`, or the default
+    /// of an uninterpreted sort.
+    fn zero(&mut self, ty: &Type) -> String {
+        if *ty == Type::Bool {
+            return "false".to_string();
+        }
+        if ty.is_integer() {
+            return bits(0, width(ty));
+        }
+        let symbol = quote(&format!("{}::default()", ty.to_rust_type()));
+        self.declare(&symbol, ty);
+        symbol
+    }
+
+    fn param(&self, name: &str) -> Option<&'a Param> {
+        self.instruction.params.iter().find(|p| p.name == name)
+    }
+
+    fn is_account(&self, name: &str) -> bool {
+        self.param(name).is_some_and(|p| p.ty.is_account())
+    }
+
+    fn is_data_field(&self, name: &str, field: &str) -> bool {
+        match self.param(name) {
+            Some(param) => self.account_fields(param).iter().any(|f| f.name == field),
+            None => false,
+        }
+    }
+
+    fn is_helpers(&self, object: &Expr) -> bool {
+        matches!(object, Expr::Ident(name)
+            if name == solx_hir::HELPERS_MODULE && self.param(name).is_none())
+    }
+
+    fn is_cpi(&self, object: &Expr) -> bool {
+        matches!(object, Expr::Ident(name)
+            if self.param(name).is_none()
+                && Sysvar::from_name(name).is_none()
+                && self.hir.import(name).is_some())
+    }
+}
+
+/// A quoted SMT-LIB symbol, which may contain dots and spaces.
+fn quote(name: &str) -> String {
+    format!("|{}|", name.replace(['|', '\\'], "_"))
+}
+
+pub(crate) fn width(ty: &Type) -> u32 {
+    match ty {
+        Type::U8 | Type::I8 => 8,
+        Type::U16 | Type::I16 => 16,
+        Type::U32 | Type::I32 => 32,
+        _ => 64,
+    }
+}
+
+fn int_min(ty: &Type) -> i128 {
+    if ty.is_signed() {
+        -(1i128 << (width(ty) - 1))
+    } else {
+        0
+    }
+}
+
+/// `value` as a bit-vector literal of `width` bits, in two's complement.
+fn bits(value: i128, width: u32) -> String {
+    let mask = (1u128 << width) - 1;
+    format!("(_ bv{} {})", (value as u128) & mask, width)
+}
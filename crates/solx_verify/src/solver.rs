@@ -0,0 +1,281 @@
+//! A local SMT solver, driven over stdin and stdout.
+//!
+//! Each instruction gets its own solver process: the declarations are sent
+//! once, then every obligation is checked between `(push 1)` and `(pop 1)`,
+//! asking for the inputs' values when it can fail.
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use solx_ast::Type;
+
+use crate::lower::{width, Input, Lowered, Obligation};
+use crate::Verdict;
+
+/// Solvers `solx verify` knows how to run, in order of preference.
+pub const SOLVERS: [&str; 2] = ["z3", "cvc5"];
+
+pub struct Solver {
+    /// `z3` or `cvc5`
+    pub name: String,
+    program: String,
+    args: Vec<&'static str>,
+}
+
+impl Solver {
+    /// The solver called `name`, or at that path; without a name, the first
+    /// of [`SOLVERS`] on the `PATH`.
+    pub fn find(name: Option<&str>) -> Result<Solver> {
+        if let Some(name) = name {
+            let solver = Solver::new(name).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown solver {}; expected one of {}",
+                    name,
+                    SOLVERS.join(", ")
+                )
+            })?;
+            if !solver.is_installed() {
+                anyhow::bail!("Solver {} not found", solver.program);
+            }
+            return Ok(solver);
+        }
+        SOLVERS
+            .iter()
+            .filter_map(|name| Solver::new(name))
+            .find(Solver::is_installed)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No SMT solver found; install {} or pass --solver",
+                    SOLVERS.join(" or ")
+                )
+            })
+    }
+
+    /// A solver by name or path, recognized by its file name.
+    fn new(program: &str) -> Option<Solver> {
+        let name = Path::new(program).file_stem()?.to_str()?;
+        let args = match name {
+            "z3" => vec!["-in", "-smt2"],
+            "cvc5" => vec!["--lang=smt2", "--incremental", "--produce-models"],
+            _ => return None,
+        };
+        Some(Solver {
+            name: name.to_string(),
+            program: program.to_string(),
+            args,
+        })
+    }
+
+    fn is_installed(&self) -> bool {
+        Command::new(&self.program)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    }
+
+    /// Check every obligation of `lowered`, in order.
+    pub fn check(&self, lowered: &Lowered) -> Result<Vec<Verdict>> {
+        let mut session = Session::start(self)?;
+        session.send("(set-option :produce-models true)\n")?;
+        session.send(&lowered.prelude())?;
+        let mut verdicts = Vec::new();
+        for obligation in &lowered.obligations {
+            verdicts.push(session.check(lowered, obligation)?);
+        }
+        session.send("(exit)\n")?;
+        let _ = session.child.wait();
+        Ok(verdicts)
+    }
+}
+
+struct Session {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Session {
+    fn start(solver: &Solver) -> Result<Session> {
+        let mut child = Command::new(&solver.program)
+            .args(&solver.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start {}", solver.program))?;
+        let stdin = child.stdin.take().context("solver stdin")?;
+        let stdout = BufReader::new(child.stdout.take().context("solver stdout")?);
+        Ok(Session {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    fn send(&mut self, text: &str) -> Result<()> {
+        self.stdin.write_all(text.as_bytes())?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn check(&mut self, lowered: &Lowered, obligation: &Obligation) -> Result<Verdict> {
+        self.send("(push 1)\n")?;
+        self.send(&lowered.refutation(obligation))?;
+        self.send("(check-sat)\n")?;
+        let verdict = match self.read()? {
+            Sexp::Atom(answer) if answer == "unsat" => Verdict::Proved,
+            Sexp::Atom(answer) if answer == "sat" => {
+                Verdict::Counterexample(self.model(&lowered.inputs)?)
+            }
+            Sexp::Atom(answer) => Verdict::Unknown(answer),
+            response => anyhow::bail!("Unexpected solver response: {}", response),
+        };
+        self.send("(pop 1)\n")?;
+        Ok(verdict)
+    }
+
+    /// The value of every input in the current model.
+    fn model(&mut self, inputs: &[Input]) -> Result<Vec<(String, String)>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let symbols: Vec<&str> = inputs.iter().map(|i| i.symbol.as_str()).collect();
+        self.send(&format!("(get-value ({}))\n", symbols.join(" ")))?;
+        let response = self.read()?;
+        let Sexp::List(pairs) = &response else {
+            anyhow::bail!("Unexpected solver response: {}", response);
+        };
+        let mut values = Vec::new();
+        for (input, pair) in inputs.iter().zip(pairs) {
+            let value = match pair {
+                Sexp::List(pair) if pair.len() == 2 => &pair[1],
+                _ => anyhow::bail!("Unexpected solver response: {}", response),
+            };
+            values.push((input.name.clone(), format_value(value, &input.ty)));
+        }
+        Ok(values)
+    }
+
+    /// The next response; solver errors are returned as errors.
+    fn read(&mut self) -> Result<Sexp> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stdout.read_line(&mut line)? == 0 {
+                anyhow::bail!("Solver exited unexpectedly");
+            }
+            text.push_str(&line);
+            if let Some(sexp) = parse(&text) {
+                if let Sexp::List(items) = &sexp {
+                    if items.first() == Some(&Sexp::Atom("error".to_string())) {
+                        anyhow::bail!("Solver error: {}", sexp);
+                    }
+                }
+                return Ok(sexp);
+            }
+        }
+    }
+}
+
+/// An s-expression from the solver.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+impl std::fmt::Display for Sexp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sexp::Atom(atom) => f.write_str(atom),
+            Sexp::List(items) => {
+                let items: Vec<String> = items.iter().map(Sexp::to_string).collect();
+                write!(f, "({})", items.join(" "))
+            }
+        }
+    }
+}
+
+/// The first complete s-expression in `text`, or `None` if it is not
+/// complete yet.
+pub(crate) fn parse(text: &str) -> Option<Sexp> {
+    let mut stack: Vec<Vec<Sexp>> = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let atom = match c {
+            '(' => {
+                stack.push(Vec::new());
+                continue;
+            }
+            ')' => {
+                let list = Sexp::List(stack.pop()?);
+                match stack.last_mut() {
+                    Some(parent) => {
+                        parent.push(list);
+                        continue;
+                    }
+                    None => return Some(list),
+                }
+            }
+            c if c.is_whitespace() => continue,
+            '|' | '"' => {
+                let mut atom = c.to_string();
+                loop {
+                    let next = chars.next()?;
+                    atom.push(next);
+                    if next == c {
+                        break;
+                    }
+                }
+                atom
+            }
+            c => {
+                let mut atom = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' || next == ')' {
+                        break;
+                    }
+                    atom.push(next);
+                    chars.next();
+                }
+                atom
+            }
+        };
+        match stack.last_mut() {
+            Some(list) => list.push(Sexp::Atom(atom)),
+            None => return Some(Sexp::Atom(atom)),
+        }
+    }
+    None
+}
+
+/// A model value as a SOL-X value: integers in decimal, in two's complement
+/// for signed types.
+pub(crate) fn format_value(value: &Sexp, ty: &Type) -> String {
+    let bits = match value {
+        Sexp::Atom(atom) if atom.starts_with("#x") => u128::from_str_radix(&atom[2..], 16).ok(),
+        Sexp::Atom(atom) if atom.starts_with("#b") => u128::from_str_radix(&atom[2..], 2).ok(),
+        Sexp::List(items) => match items.as_slice() {
+            [Sexp::Atom(underscore), Sexp::Atom(bv), _] if underscore == "_" => {
+                bv.strip_prefix("bv").and_then(|n| n.parse().ok())
+            }
+            _ => None,
+        },
+        _ => None,
+    };
+    match bits {
+        Some(bits) if ty.is_integer() => {
+            let width = width(ty);
+            if ty.is_signed() && bits >> (width - 1) & 1 == 1 {
+                (bits as i128 - (1i128 << width)).to_string()
+            } else {
+                bits.to_string()
+            }
+        }
+        _ => value.to_string(),
+    }
+}
@@ -0,0 +1,67 @@
+// Generated by SOL-X from program.solx. Do not edit: `solx build`
+// overwrites this file. Hand-written code belongs in lib.rs.
+
+declare_id!("11111111111111111111111111111111");
+
+use anchor_lang::prelude::*;
+
+#[account]
+pub struct CounterState {
+    pub authority: Pubkey,
+    pub count: u64,
+}
+
+#[program]
+pub mod counter {
+    use super::*;
+
+    pub fn initialize(ctx: Context<InitializeContext>) -> Result<()> {
+        ctx.accounts.state.authority = ctx.accounts.authority.key();
+        ctx.accounts.state.count = 0;
+        Ok(())
+    }
+
+    pub fn increment(ctx: Context<IncrementContext>) -> Result<()> {
+        require!(
+            ctx.accounts.state.authority == ctx.accounts.authority.key(),
+            anchor_lang::error::ErrorCode::RequireViolated
+        );
+        ctx.accounts.state.count = ctx.accounts.state.count + 1;
+        Ok(())
+    }
+
+    pub fn decrement(ctx: Context<DecrementContext>) -> Result<()> {
+        require!(
+            ctx.accounts.state.authority == ctx.accounts.authority.key(),
+            anchor_lang::error::ErrorCode::RequireViolated
+        );
+        require!(
+            ctx.accounts.state.count > 0, anchor_lang::error::ErrorCode::RequireViolated
+        );
+        ctx.accounts.state.count = ctx.accounts.state.count - 1;
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeContext<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(init, payer = authority, space = 48)]
+    pub state: Account<'info, CounterState>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct IncrementContext<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub state: Account<'info, CounterState>,
+}
+
+#[derive(Accounts)]
+pub struct DecrementContext<'info> {
+    pub authority: Signer<'info>,
+    #[account(mut)]
+    pub state: Account<'info, CounterState>,
+}